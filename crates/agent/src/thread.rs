@@ -719,14 +719,8 @@ impl Thread {
         let tool = self.tools.get(tool_use.name.as_ref()).cloned().or_else(|| {
             self.context_server_registry
                 .read(cx)
-                .servers()
-                .find_map(|(_, tools)| {
-                    if let Some(tool) = tools.get(tool_use.name.as_ref()) {
-                        Some(tool.clone())
-                    } else {
-                        None
-                    }
-                })
+                .resolve_tool(tool_use.name.as_ref())
+                .map(|(_, tool)| tool.clone())
         });
 
         let Some(tool) = tool else {
@@ -1928,18 +1922,18 @@ impl Thread {
             })
             .collect::<BTreeMap<_, _>>();
 
+        // The registry names the tools of different servers apart, but they may still
+        // collide with the built-in tools, or with each other once truncated.
         let mut context_server_tools = Vec::new();
         let mut seen_tools = tools.keys().cloned().collect::<HashSet<_>>();
         let mut duplicate_tool_names = HashSet::default();
-        for (server_id, server_tools) in self.context_server_registry.read(cx).servers() {
-            for (tool_name, tool) in server_tools {
-                if profile.is_context_server_tool_enabled(&server_id.0, &tool_name) {
-                    let tool_name = truncate(tool_name);
-                    if !seen_tools.insert(tool_name.clone()) {
-                        duplicate_tool_names.insert(tool_name.clone());
-                    }
-                    context_server_tools.push((server_id.clone(), tool_name, tool.clone()));
+        for (tool_name, (server_id, tool)) in self.context_server_registry.read(cx).all_tools() {
+            if profile.is_context_server_tool_enabled(&server_id.0, &tool.name()) {
+                let tool_name = truncate(tool_name);
+                if !seen_tools.insert(tool_name.clone()) {
+                    duplicate_tool_names.insert(tool_name.clone());
                 }
+                context_server_tools.push((server_id.clone(), tool_name, tool.clone()));
             }
         }
 
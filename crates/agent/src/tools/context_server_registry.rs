@@ -1,8 +1,12 @@
 use crate::{AgentToolOutput, AnyAgentTool, ToolCallEventStream};
 use agent_client_protocol::ToolKind;
-use anyhow::{Result, anyhow, bail};
+use anyhow::{Result, anyhow};
 use collections::{BTreeMap, HashMap};
-use context_server::ContextServerId;
+use context_server::{
+    ContextServerId, ListKind,
+    tool_result::{ToolContent, ToolResult},
+};
+use futures::StreamExt as _;
 use gpui::{App, Context, Entity, SharedString, Task};
 use project::context_server_store::{ContextServerStatus, ContextServerStore};
 use std::sync::Arc;
@@ -11,12 +15,16 @@ use util::ResultExt;
 pub struct ContextServerRegistry {
     server_store: Entity<ContextServerStore>,
     registered_servers: HashMap<ContextServerId, RegisteredContextServer>,
+    /// The tools of all servers by the names they're exposed under, rebuilt whenever the
+    /// tools of a server change.
+    qualified_tools: BTreeMap<SharedString, (ContextServerId, Arc<dyn AnyAgentTool>)>,
     _subscription: gpui::Subscription,
 }
 
 struct RegisteredContextServer {
     tools: BTreeMap<SharedString, Arc<dyn AnyAgentTool>>,
     load_tools: Task<Result<()>>,
+    _reload_on_list_change: Task<()>,
 }
 
 impl ContextServerRegistry {
@@ -24,6 +32,7 @@ impl ContextServerRegistry {
         let mut this = Self {
             server_store: server_store.clone(),
             registered_servers: HashMap::default(),
+            qualified_tools: BTreeMap::default(),
             _subscription: cx.subscribe(&server_store, Self::handle_context_server_store_event),
         };
         for server in server_store.read(cx).running_servers() {
@@ -56,6 +65,29 @@ impl ContextServerRegistry {
             .map(|(id, server)| (id, &server.tools))
     }
 
+    /// The tools of all servers, under names that are unique across servers, along with the
+    /// server each belongs to. See [`qualify_tool_names`] for how they're named.
+    pub fn all_tools(&self) -> &BTreeMap<SharedString, (ContextServerId, Arc<dyn AnyAgentTool>)> {
+        &self.qualified_tools
+    }
+
+    /// Finds the tool that [`Self::all_tools`] lists under `name`, and the server to call it
+    /// on.
+    pub fn resolve_tool(&self, name: &str) -> Option<&(ContextServerId, Arc<dyn AnyAgentTool>)> {
+        self.qualified_tools.get(name)
+    }
+
+    fn qualify_tools(&mut self, cx: &App) {
+        let store = self.server_store.read(cx);
+        self.qualified_tools =
+            qualify_tool_names(self.registered_servers.iter().map(|(server_id, server)| {
+                let tool_prefix = store
+                    .configuration_for_server(server_id)
+                    .and_then(|configuration| configuration.options().tool_prefix.clone());
+                (server_id, tool_prefix, &server.tools)
+            }));
+    }
+
     fn reload_tools_for_server(&mut self, server_id: ContextServerId, cx: &mut Context<Self>) {
         let Some(server) = self.server_store.read(cx).get_running_server(&server_id) else {
             return;
@@ -67,17 +99,32 @@ impl ContextServerRegistry {
             return;
         }
 
-        let registered_server =
-            self.registered_servers
-                .entry(server_id.clone())
-                .or_insert(RegisteredContextServer {
-                    tools: BTreeMap::default(),
-                    load_tools: Task::ready(Ok(())),
-                });
+        let registered_server = self
+            .registered_servers
+            .entry(server_id.clone())
+            .or_insert_with(|| RegisteredContextServer {
+                tools: BTreeMap::default(),
+                load_tools: Task::ready(Ok(())),
+                _reload_on_list_change: cx.spawn({
+                    let mut list_changes = server.list_changes();
+                    let server_id = server_id.clone();
+                    async move |this, cx| {
+                        while let Some(change) = list_changes.next().await {
+                            if change.kind != ListKind::Tools {
+                                continue;
+                            }
+                            let reloaded = this.update(cx, |this, cx| {
+                                this.reload_tools_for_server(server_id.clone(), cx)
+                            });
+                            if reloaded.is_err() {
+                                break;
+                            }
+                        }
+                    }
+                }),
+            });
         registered_server.load_tools = cx.spawn(async move |this, cx| {
-            let response = client
-                .request::<context_server::types::requests::ListTools>(())
-                .await;
+            let response = server.list_all_tools().await;
 
             this.update(cx, |this, cx| {
                 let Some(registered_server) = this.registered_servers.get_mut(&server_id) else {
@@ -85,17 +132,18 @@ impl ContextServerRegistry {
                 };
 
                 registered_server.tools.clear();
-                if let Some(response) = response.log_err() {
-                    for tool in response.tools {
+                if let Some(tool_list) = response.log_err() {
+                    for tool in tool_list.tools {
                         let tool = Arc::new(ContextServerTool::new(
                             this.server_store.clone(),
-                            server.id(),
+                            server_id.clone(),
                             tool,
                         ));
                         registered_server.tools.insert(tool.name(), tool);
                     }
-                    cx.notify();
                 }
+                this.qualify_tools(cx);
+                cx.notify();
             })
         });
     }
@@ -107,20 +155,110 @@ impl ContextServerRegistry {
         cx: &mut Context<Self>,
     ) {
         match event {
-            project::context_server_store::Event::ServerStatusChanged { server_id, status } => {
-                match status {
-                    ContextServerStatus::Starting => {}
-                    ContextServerStatus::Running => {
-                        self.reload_tools_for_server(server_id.clone(), cx);
-                    }
-                    ContextServerStatus::Stopped | ContextServerStatus::Error(_) => {
-                        self.registered_servers.remove(server_id);
-                        cx.notify();
-                    }
+            project::context_server_store::Event::ServerStatusChanged {
+                server_id, status, ..
+            } => match status {
+                ContextServerStatus::Starting => {}
+                ContextServerStatus::Running => {
+                    self.reload_tools_for_server(server_id.clone(), cx);
+                }
+                ContextServerStatus::Stopped
+                | ContextServerStatus::Error(_)
+                | ContextServerStatus::Crashed(_) => {
+                    self.registered_servers.remove(server_id);
+                    self.qualify_tools(cx);
+                    cx.notify();
+                }
+            },
+            project::context_server_store::Event::ServerSettingsChanged { .. } => {}
+        }
+    }
+}
+
+/// Names the tools of the given servers, along with their `tool_prefix` settings.
+///
+/// Tools keep their names, unless another server has a tool with the same name or their
+/// server has a `tool_prefix`, in which case they're qualified as `<prefix>_<tool>`. The
+/// prefix defaults to the server's ID. Models only accept letters, digits, `_` and `-` in
+/// tool names, hence the underscore.
+///
+/// Servers are handled in the order of their IDs. Tools whose names still collide once
+/// qualified, like those of servers with the same prefix, are dropped, except for the first.
+fn qualify_tool_names<'a, T: Clone + 'a>(
+    servers: impl IntoIterator<
+        Item = (
+            &'a ContextServerId,
+            Option<String>,
+            &'a BTreeMap<SharedString, T>,
+        ),
+    >,
+) -> BTreeMap<SharedString, (ContextServerId, T)> {
+    let mut servers = servers.into_iter().collect::<Vec<_>>();
+    servers.sort_by(|(a, _, _), (b, _, _)| a.0.cmp(&b.0));
+    let mut servers_by_tool_name = HashMap::<&SharedString, usize>::default();
+    for (_, _, tools) in &servers {
+        for tool_name in tools.keys() {
+            *servers_by_tool_name.entry(tool_name).or_default() += 1;
+        }
+    }
+
+    let mut qualified_tools = BTreeMap::default();
+    for (server_id, tool_prefix, tools) in &servers {
+        for (tool_name, tool) in tools.iter() {
+            let name: SharedString = match tool_prefix {
+                Some(prefix) => format!("{prefix}_{tool_name}").into(),
+                None if servers_by_tool_name[tool_name] > 1 => {
+                    format!("{server_id}_{tool_name}").into()
                 }
+                None => tool_name.clone(),
+            };
+            if qualified_tools.contains_key(&name) {
+                log::warn!(
+                    "ignoring tool {tool_name:?} of context server {server_id}, as another \
+                    server's tool is named {name:?}"
+                );
+                continue;
             }
+            qualified_tools.insert(name, ((*server_id).clone(), tool.clone()));
         }
     }
+    qualified_tools
+}
+
+/// What the agent gets from the result of a tool call: its text, followed by its structured
+/// content if there is any, which is the raw output then.
+fn tool_output(result: ToolResult) -> AgentToolOutput {
+    let mut text = String::new();
+    for content in result.content {
+        match content {
+            ToolContent::Text(chunk) => text.push_str(&chunk),
+            ToolContent::Image { .. } => {
+                log::warn!("Ignoring image content from tool response");
+            }
+            ToolContent::Audio { .. } => {
+                log::warn!("Ignoring audio content from tool response");
+            }
+            ToolContent::ResourceLink { .. } | ToolContent::EmbeddedResource { .. } => {
+                log::warn!("Ignoring resource content from tool response");
+            }
+        }
+    }
+
+    let Some(structured_content) = result.structured_content else {
+        return AgentToolOutput {
+            raw_output: text.clone().into(),
+            llm_output: text.into(),
+        };
+    };
+    // Servers are meant to describe structured content in text too, so the model gets both.
+    if !text.is_empty() {
+        text.push_str("\n\n");
+    }
+    text.push_str(&structured_content.to_string());
+    AgentToolOutput {
+        llm_output: text.into(),
+        raw_output: structured_content,
+    }
 }
 
 struct ContextServerTool {
@@ -192,10 +330,6 @@ impl AnyAgentTool for ContextServerTool {
         cx.spawn(async move |_cx| {
             authorize.await?;
 
-            let Some(protocol) = server.client() else {
-                bail!("Context server not initialized");
-            };
-
             let arguments = if let serde_json::Value::Object(map) = input {
                 Some(map.into_iter().collect())
             } else {
@@ -207,37 +341,19 @@ impl AnyAgentTool for ContextServerTool {
                 tool_name,
                 arguments
             );
-            let response = protocol
-                .request::<context_server::types::requests::CallTool>(
+            let response = server
+                .call_tool_checked(
                     context_server::types::CallToolParams {
                         name: tool_name,
                         arguments,
                         meta: None,
                     },
+                    None,
+                    None,
                 )
                 .await?;
 
-            let mut result = String::new();
-            for content in response.content {
-                match content {
-                    context_server::types::ToolResponseContent::Text { text } => {
-                        result.push_str(&text);
-                    }
-                    context_server::types::ToolResponseContent::Image { .. } => {
-                        log::warn!("Ignoring image content from tool response");
-                    }
-                    context_server::types::ToolResponseContent::Audio { .. } => {
-                        log::warn!("Ignoring audio content from tool response");
-                    }
-                    context_server::types::ToolResponseContent::Resource { .. } => {
-                        log::warn!("Ignoring resource content from tool response");
-                    }
-                }
-            }
-            Ok(AgentToolOutput {
-                raw_output: result.clone().into(),
-                llm_output: result.into(),
-            })
+            Ok(tool_output(response))
         })
     }
 
@@ -251,3 +367,137 @@ impl AnyAgentTool for ContextServerTool {
         Ok(())
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use language_model::LanguageModelToolResultContent;
+
+    fn qualified_names(
+        servers: &[(&str, Option<&str>, &[&str])],
+    ) -> BTreeMap<SharedString, (ContextServerId, SharedString)> {
+        let servers = servers
+            .iter()
+            .map(|(server_id, prefix, tools)| {
+                let tools = tools
+                    .iter()
+                    .map(|tool| (SharedString::from(*tool), SharedString::from(*tool)))
+                    .collect::<BTreeMap<_, _>>();
+                (
+                    ContextServerId((*server_id).into()),
+                    prefix.map(str::to_string),
+                    tools,
+                )
+            })
+            .collect::<Vec<_>>();
+        qualify_tool_names(
+            servers
+                .iter()
+                .map(|(server_id, prefix, tools)| (server_id, prefix.clone(), tools)),
+        )
+    }
+
+    fn names(tools: &BTreeMap<SharedString, (ContextServerId, SharedString)>) -> Vec<&str> {
+        tools.keys().map(|name| name.as_ref()).collect()
+    }
+
+    #[test]
+    fn test_tool_output() {
+        let output = tool_output(ToolResult {
+            content: vec![
+                ToolContent::Text("Found 2 issues.".to_string()),
+                ToolContent::Text(" Both are open.".to_string()),
+            ],
+            structured_content: Some(serde_json::json!({ "issues": [1, 2] })),
+            ..Default::default()
+        });
+        assert_eq!(
+            output.llm_output,
+            LanguageModelToolResultContent::from(
+                "Found 2 issues. Both are open.\n\n{\"issues\":[1,2]}"
+            )
+        );
+        assert_eq!(output.raw_output, serde_json::json!({ "issues": [1, 2] }));
+
+        let output = tool_output(ToolResult {
+            structured_content: Some(serde_json::json!({ "issues": [] })),
+            ..Default::default()
+        });
+        assert_eq!(
+            output.llm_output,
+            LanguageModelToolResultContent::from("{\"issues\":[]}")
+        );
+
+        let output = tool_output(ToolResult {
+            content: vec![ToolContent::Text("No issues.".to_string())],
+            ..Default::default()
+        });
+        assert_eq!(
+            output.llm_output,
+            LanguageModelToolResultContent::from("No issues.")
+        );
+        assert_eq!(output.raw_output, serde_json::json!("No issues."));
+    }
+
+    #[test]
+    fn test_qualify_tool_names() {
+        // Only the colliding tools are qualified with the server's ID, and the tools of
+        // servers with a prefix always are.
+        let qualified = qualified_names(&[
+            ("github", None, &["search", "create_issue"]),
+            ("docs", Some("d"), &["search", "fetch"]),
+        ]);
+        assert_eq!(
+            names(&qualified),
+            ["create_issue", "d_fetch", "d_search", "github_search"]
+        );
+
+        // Servers without collisions keep their tool names.
+        let qualified =
+            qualified_names(&[("github", None, &["search"]), ("docs", None, &["fetch"])]);
+        assert_eq!(names(&qualified), ["fetch", "search"]);
+    }
+
+    #[test]
+    fn test_qualify_tool_names_collisions() {
+        // Servers with the same prefix can't be told apart, so the tool of the server whose
+        // ID comes first wins.
+        let qualified = qualified_names(&[
+            ("b-server", Some("shared"), &["search"]),
+            ("a-server", Some("shared"), &["search", "fetch"]),
+        ]);
+        assert_eq!(names(&qualified), ["shared_fetch", "shared_search"]);
+        assert_eq!(
+            qualified["shared_search"].0,
+            ContextServerId("a-server".into())
+        );
+
+        // A unique tool name can look like a qualified one.
+        let qualified = qualified_names(&[
+            ("a", None, &["b_search"]),
+            ("b", None, &["search"]),
+            ("c", None, &["search"]),
+        ]);
+        assert_eq!(names(&qualified), ["b_search", "c_search"]);
+        assert_eq!(qualified["b_search"].0, ContextServerId("a".into()));
+    }
+
+    #[test]
+    fn test_resolve_qualified_tool_names() {
+        let qualified = qualified_names(&[
+            ("github", None, &["search", "create_issue"]),
+            ("docs", None, &["search"]),
+        ]);
+        let resolve = |name: &str| {
+            qualified
+                .get(name)
+                .map(|(server_id, tool)| (server_id.0.as_ref(), tool.as_ref()))
+        };
+        assert_eq!(resolve("github_search"), Some(("github", "search")));
+        assert_eq!(resolve("docs_search"), Some(("docs", "search")));
+        assert_eq!(resolve("create_issue"), Some(("github", "create_issue")));
+        // Colliding tools are only known by their qualified names.
+        assert_eq!(resolve("search"), None);
+        assert_eq!(resolve("github_create_issue"), None);
+    }
+}
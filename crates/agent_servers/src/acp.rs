@@ -281,6 +281,7 @@ impl AgentConnection for AcpConnection {
                         project::context_server_store::ContextServerConfiguration::Http {
                             url,
                             headers,
+                            ..
                         } => Some(acp::McpServer::Http {
                             name: id.0.to_string(),
                             url: url.to_string(),
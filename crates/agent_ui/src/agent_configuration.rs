@@ -659,10 +659,11 @@ impl AgentConfiguration {
             )
         });
 
-        let error = if let ContextServerStatus::Error(error) = server_status.clone() {
-            Some(error)
-        } else {
-            None
+        let is_crashed = matches!(server_status, ContextServerStatus::Crashed(_));
+        let restart_server_id = context_server_id.clone();
+        let error = match server_status.clone() {
+            ContextServerStatus::Error(error) | ContextServerStatus::Crashed(error) => Some(error),
+            _ => None,
         };
 
         let tool_count = self
@@ -683,7 +684,33 @@ impl AgentConfiguration {
             )
         };
 
-        let (status_indicator, tooltip_text) = match server_status {
+        let server_details = self
+            .context_server_store
+            .read(cx)
+            .get_server(&context_server_id)
+            .and_then(|server| {
+                let info = server.server_info()?;
+                let capabilities = [
+                    (server.supports_tools(), "tools"),
+                    (server.supports_prompts(), "prompts"),
+                    (server.supports_resources(), "resources"),
+                    (server.supports_completions(), "completions"),
+                    (server.supports_logging(), "logging"),
+                ]
+                .into_iter()
+                .filter_map(|(supported, name)| supported.then_some(name))
+                .collect::<Vec<_>>();
+                let mut details = format!(
+                    "Server is active: {} {}, using MCP {}.",
+                    info.name, info.version, info.protocol_version
+                );
+                if !capabilities.is_empty() {
+                    details.push_str(&format!("\nSupports {}.", capabilities.join(", ")));
+                }
+                Some(SharedString::from(details))
+            });
+
+        let (status_indicator, tooltip_text): (_, SharedString) = match server_status {
             ContextServerStatus::Starting => (
                 Icon::new(IconName::LoadCircle)
                     .size(IconSize::XSmall)
@@ -693,19 +720,23 @@ impl AgentConfiguration {
                         3,
                     )
                     .into_any_element(),
-                "Server is starting.",
+                "Server is starting.".into(),
             ),
             ContextServerStatus::Running => (
                 Indicator::dot().color(Color::Success).into_any_element(),
-                "Server is active.",
+                server_details.unwrap_or_else(|| "Server is active.".into()),
             ),
             ContextServerStatus::Error(_) => (
                 Indicator::dot().color(Color::Error).into_any_element(),
-                "Server has an error.",
+                "Server has an error.".into(),
+            ),
+            ContextServerStatus::Crashed(_) => (
+                Indicator::dot().color(Color::Error).into_any_element(),
+                "Server crashed.".into(),
             ),
             ContextServerStatus::Stopped => (
                 Indicator::dot().color(Color::Muted).into_any_element(),
-                "Server is stopped.",
+                "Server is stopped.".into(),
             ),
         };
         let is_remote = server_configuration
@@ -917,6 +948,7 @@ impl AgentConfiguration {
                                                         settings::ContextServerSettingsContent::Extension {
                                                             enabled: is_enabled,
                                                             settings: serde_json::json!({}),
+                                                            options: Default::default(),
                                                         }
                                                     })
                                                     .set_enabled(is_enabled);
@@ -952,7 +984,26 @@ impl AgentConfiguration {
                                         .color(Color::Muted)
                                         .size(LabelSize::Small),
                                 ),
-                            ),
+                            )
+                            .when(is_crashed, |this| {
+                                this.child(
+                                    Button::new("restart-context-server", "Restart")
+                                        .label_size(LabelSize::Small)
+                                        .on_click({
+                                            let context_server_store =
+                                                self.context_server_store.clone();
+                                            move |_, _, cx| {
+                                                context_server_store.update(cx, |store, cx| {
+                                                    if let Some(server) =
+                                                        store.get_server(&restart_server_id)
+                                                    {
+                                                        store.start_server(server, cx);
+                                                    }
+                                                });
+                                            }
+                                        }),
+                                )
+                            }),
                     );
                 }
                 parent
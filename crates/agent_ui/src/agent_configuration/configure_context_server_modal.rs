@@ -5,7 +5,7 @@ use std::{
 
 use anyhow::{Context as _, Result};
 use collections::HashMap;
-use context_server::{ContextServerCommand, ContextServerId};
+use context_server::{ContextServerCommand, ContextServerId, ContextServerOptions};
 use editor::{Editor, EditorElement, EditorStyle};
 use gpui::{
     AsyncWindowContext, DismissEvent, Entity, EventEmitter, FocusHandle, Focusable, ScrollHandle,
@@ -16,7 +16,8 @@ use markdown::{Markdown, MarkdownElement, MarkdownStyle};
 use notifications::status_toast::{StatusToast, ToastIcon};
 use project::{
     context_server_store::{
-        ContextServerStatus, ContextServerStore, registry::ContextServerDescriptorRegistry,
+        ContextServerStatus, ContextServerStore,
+        registry::{ContextServerDescriptorRegistry, ContextServerSource},
     },
     project_settings::{ContextServerSettings, ProjectSettings},
     worktree_store::WorktreeStore,
@@ -169,22 +170,55 @@ impl ConfigurationSource {
             | ConfigurationSource::Existing { editor, is_http } => {
                 if *is_http {
                     parse_http_input(&editor.read(cx).text(cx)).map(|(id, url, auth)| {
+                        let options = existing_options(&id, cx);
+                        let (timeout, transport, proxy, no_proxy, tls, connection, header_command) =
+                            match ProjectSettings::get_global(cx).context_servers.get(&id.0) {
+                                Some(ContextServerSettings::Http {
+                                    timeout,
+                                    transport,
+                                    proxy,
+                                    no_proxy,
+                                    tls,
+                                    connection,
+                                    header_command,
+                                    ..
+                                }) => (
+                                    *timeout,
+                                    *transport,
+                                    proxy.clone(),
+                                    no_proxy.clone(),
+                                    tls.clone(),
+                                    connection.clone(),
+                                    header_command.clone(),
+                                ),
+                                _ => (None, None, None, None, None, None, None),
+                            };
                         (
                             id,
                             ContextServerSettings::Http {
                                 enabled: true,
                                 url,
                                 headers: auth,
+                                timeout,
+                                transport,
+                                proxy,
+                                no_proxy,
+                                tls,
+                                connection,
+                                header_command,
+                                options,
                             },
                         )
                     })
                 } else {
                     parse_input(&editor.read(cx).text(cx)).map(|(id, command)| {
+                        let options = existing_options(&id, cx);
                         (
                             id,
                             ContextServerSettings::Custom {
                                 enabled: true,
                                 command,
+                                options,
                             },
                         )
                     })
@@ -212,6 +246,7 @@ impl ConfigurationSource {
                     ContextServerSettings::Extension {
                         enabled: true,
                         settings,
+                        options: existing_options(id, cx),
                     },
                 ))
             }
@@ -219,6 +254,16 @@ impl ConfigurationSource {
     }
 }
 
+/// The modal only edits the command or URL, so options configured by hand in
+/// the settings file are carried over.
+fn existing_options(id: &ContextServerId, cx: &App) -> ContextServerOptions {
+    ProjectSettings::get_global(cx)
+        .context_servers
+        .get(&id.0)
+        .map(|settings| settings.options().clone())
+        .unwrap_or_default()
+}
+
 fn context_server_input(existing: Option<(ContextServerId, ContextServerCommand)>) -> String {
     let (name, command, args, env) = match existing {
         Some((id, cmd)) => {
@@ -406,6 +451,7 @@ impl ConfigureContextServerModal {
                 ContextServerSettings::Custom {
                     enabled: _,
                     command,
+                    ..
                 } => Some(ConfigurationTarget::Existing {
                     id: server_id,
                     command,
@@ -414,6 +460,7 @@ impl ConfigureContextServerModal {
                     enabled: _,
                     url,
                     headers,
+                    ..
                 } => Some(ConfigurationTarget::ExistingHttp {
                     id: server_id,
                     url,
@@ -496,6 +543,20 @@ impl ConfigureContextServerModal {
             }
         };
 
+        if self.original_server_id.as_ref() != Some(&id) {
+            let source = ContextServerSource::Settings {
+                path: Some(paths::settings_file().as_path().into()),
+            };
+            if let Err(error) = self
+                .context_server_store
+                .read(cx)
+                .check_new_server_id(&id, source, cx)
+            {
+                self.set_error(error.to_string(), cx);
+                return;
+            }
+        }
+
         self.state = State::Waiting;
 
         let existing_server = self.context_server_store.read(cx).get_running_server(&id);
@@ -880,32 +941,33 @@ fn wait_for_context_server(
     let tx = Arc::new(Mutex::new(Some(tx)));
 
     let subscription = cx.subscribe(context_server_store, move |_, event, _cx| match event {
-        project::context_server_store::Event::ServerStatusChanged { server_id, status } => {
-            match status {
-                ContextServerStatus::Running => {
-                    if server_id == &context_server_id
-                        && let Some(tx) = tx.lock().unwrap().take()
-                    {
-                        let _ = tx.send(Ok(()));
-                    }
+        project::context_server_store::Event::ServerStatusChanged {
+            server_id, status, ..
+        } => match status {
+            ContextServerStatus::Running => {
+                if server_id == &context_server_id
+                    && let Some(tx) = tx.lock().unwrap().take()
+                {
+                    let _ = tx.send(Ok(()));
                 }
-                ContextServerStatus::Stopped => {
-                    if server_id == &context_server_id
-                        && let Some(tx) = tx.lock().unwrap().take()
-                    {
-                        let _ = tx.send(Err("Context server stopped running".into()));
-                    }
+            }
+            ContextServerStatus::Stopped => {
+                if server_id == &context_server_id
+                    && let Some(tx) = tx.lock().unwrap().take()
+                {
+                    let _ = tx.send(Err("Context server stopped running".into()));
                 }
-                ContextServerStatus::Error(error) => {
-                    if server_id == &context_server_id
-                        && let Some(tx) = tx.lock().unwrap().take()
-                    {
-                        let _ = tx.send(Err(error.clone()));
-                    }
+            }
+            ContextServerStatus::Error(error) | ContextServerStatus::Crashed(error) => {
+                if server_id == &context_server_id
+                    && let Some(tx) = tx.lock().unwrap().take()
+                {
+                    let _ = tx.send(Err(error.clone()));
                 }
-                _ => {}
             }
-        }
+            _ => {}
+        },
+        project::context_server_store::Event::ServerSettingsChanged { .. } => {}
     });
 
     cx.spawn(async move |_cx| {
@@ -1,4 +1,4 @@
-use anyhow::{Context as _, Result, anyhow};
+use anyhow::{Result, anyhow};
 use assistant_slash_command::{
     AfterCompletion, ArgumentCompletion, SlashCommand, SlashCommandOutput,
     SlashCommandOutputSection, SlashCommandResult,
@@ -85,28 +85,20 @@ impl SlashCommand for ContextServerSlashCommand {
 
         if let Some(server) = self.store.read(cx).get_running_server(&server_id) {
             cx.foreground_executor().spawn(async move {
-                let protocol = server.client().context("Context server not initialized")?;
-
-                let response = protocol
-                    .request::<context_server::types::requests::CompletionComplete>(
-                        context_server::types::CompletionCompleteParams {
-                            reference: context_server::types::CompletionReference::Prompt(
-                                context_server::types::PromptReference {
-                                    ty: context_server::types::PromptReferenceType::Prompt,
-                                    name: prompt_name,
-                                },
-                            ),
-                            argument: context_server::types::CompletionArgument {
-                                name: arg_name,
-                                value: arg_value,
+                let completion = server
+                    .complete(
+                        context_server::types::CompletionReference::Prompt(
+                            context_server::types::PromptReference {
+                                ty: context_server::types::PromptReferenceType::Prompt,
+                                name: prompt_name,
                             },
-                            meta: None,
-                        },
+                        ),
+                        &arg_name,
+                        &arg_value,
                     )
                     .await?;
 
-                let completions = response
-                    .completion
+                let completions = completion
                     .values
                     .into_iter()
                     .map(|value| ArgumentCompletion {
@@ -144,16 +136,7 @@ impl SlashCommand for ContextServerSlashCommand {
         let store = self.store.read(cx);
         if let Some(server) = store.get_running_server(&server_id) {
             cx.foreground_executor().spawn(async move {
-                let protocol = server.client().context("Context server not initialized")?;
-                let response = protocol
-                    .request::<context_server::types::requests::PromptsGet>(
-                        context_server::types::PromptsGetParams {
-                            name: prompt_name.clone(),
-                            arguments: Some(prompt_args),
-                            meta: None,
-                        },
-                    )
-                    .await?;
+                let response = server.get_prompt(&prompt_name, prompt_args).await?;
 
                 anyhow::ensure!(
                     response
@@ -166,12 +149,9 @@ impl SlashCommand for ContextServerSlashCommand {
                 // Extract text from user messages into a single prompt string
                 let mut prompt = response
                     .messages
-                    .into_iter()
-                    .filter_map(|msg| match msg.content {
-                        context_server::types::MessageContent::Text { text, .. } => Some(text),
-                        _ => None,
-                    })
-                    .collect::<Vec<String>>()
+                    .iter()
+                    .filter_map(|msg| msg.content.text())
+                    .collect::<Vec<_>>()
                     .join("\n\n");
 
                 // We must normalize the line endings here, since servers might return CR characters.
@@ -845,25 +845,28 @@ impl TextThreadStore {
         cx: &mut Context<Self>,
     ) {
         match event {
-            project::context_server_store::Event::ServerStatusChanged { server_id, status } => {
-                match status {
-                    ContextServerStatus::Running => {
-                        self.load_context_server_slash_commands(
-                            server_id.clone(),
-                            context_server_store,
-                            cx,
-                        );
-                    }
-                    ContextServerStatus::Stopped | ContextServerStatus::Error(_) => {
-                        if let Some(slash_command_ids) =
-                            self.context_server_slash_command_ids.remove(server_id)
-                        {
-                            self.slash_commands.remove(&slash_command_ids);
-                        }
+            project::context_server_store::Event::ServerStatusChanged {
+                server_id, status, ..
+            } => match status {
+                ContextServerStatus::Running => {
+                    self.load_context_server_slash_commands(
+                        server_id.clone(),
+                        context_server_store,
+                        cx,
+                    );
+                }
+                ContextServerStatus::Stopped
+                | ContextServerStatus::Error(_)
+                | ContextServerStatus::Crashed(_) => {
+                    if let Some(slash_command_ids) =
+                        self.context_server_slash_command_ids.remove(server_id)
+                    {
+                        self.slash_commands.remove(&slash_command_ids);
                     }
-                    _ => {}
                 }
-            }
+                _ => {}
+            },
+            project::context_server_store::Event::ServerSettingsChanged { .. } => {}
         }
     }
 
@@ -883,13 +886,9 @@ impl TextThreadStore {
             };
 
             if protocol.capable(context_server::protocol::ServerCapability::Prompts)
-                && let Some(response) = protocol
-                    .request::<context_server::types::requests::PromptsList>(())
-                    .await
-                    .log_err()
+                && let Some(prompts) = server.list_all_prompts().await.log_err()
             {
-                let slash_command_ids = response
-                    .prompts
+                let slash_command_ids = prompts
                     .into_iter()
                     .filter(assistant_slash_commands::acceptable_prompt)
                     .map(|prompt| {
@@ -0,0 +1,123 @@
+//! Limits how many calls to a server run at once.
+
+use std::sync::Arc;
+
+use collections::VecDeque;
+use futures::channel::oneshot;
+use parking_lot::Mutex;
+
+/// A semaphore that hands out permits in the order they were requested.
+///
+/// Calls that stop waiting for a permit, because they were cancelled or timed out, give up
+/// their place in the queue.
+pub struct CallLimiter {
+    max_concurrent_calls: usize,
+    state: Mutex<State>,
+}
+
+#[derive(Default)]
+struct State {
+    running: usize,
+    waiting: VecDeque<oneshot::Sender<CallPermit>>,
+}
+
+/// Allows a call to run until it is dropped, when it's handed to the next call in line.
+pub struct CallPermit {
+    limiter: Option<Arc<CallLimiter>>,
+}
+
+impl CallLimiter {
+    /// Creates a limiter allowing `max_concurrent_calls` calls at once, or one if it's zero.
+    pub fn new(max_concurrent_calls: usize) -> Arc<Self> {
+        Arc::new(Self {
+            max_concurrent_calls: max_concurrent_calls.max(1),
+            state: Mutex::default(),
+        })
+    }
+
+    /// Waits until fewer than the maximum number of calls are running.
+    pub async fn acquire(self: &Arc<Self>) -> CallPermit {
+        let permit_rx = {
+            let mut state = self.state.lock();
+            if state.running < self.max_concurrent_calls {
+                state.running += 1;
+                return CallPermit {
+                    limiter: Some(self.clone()),
+                };
+            }
+            let (permit_tx, permit_rx) = oneshot::channel();
+            state.waiting.push_back(permit_tx);
+            permit_rx
+        };
+        // The senders are only dropped after their receiver is.
+        permit_rx
+            .await
+            .expect("call limiter dropped a waiting call")
+    }
+
+    /// How many calls are waiting for a permit.
+    pub fn queue_depth(&self) -> usize {
+        self.state
+            .lock()
+            .waiting
+            .iter()
+            .filter(|permit_tx| !permit_tx.is_canceled())
+            .count()
+    }
+}
+
+impl Drop for CallPermit {
+    fn drop(&mut self) {
+        let Some(limiter) = self.limiter.take() else {
+            return;
+        };
+        let mut state = limiter.state.lock();
+        while let Some(permit_tx) = state.waiting.pop_front() {
+            let permit = CallPermit {
+                limiter: Some(limiter.clone()),
+            };
+            match permit_tx.send(permit) {
+                Ok(()) => return,
+                // The call stopped waiting, so the permit goes to the next one.
+                Err(mut permit) => permit.limiter = None,
+            }
+        }
+        state.running -= 1;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use futures::FutureExt as _;
+
+    #[test]
+    fn test_call_limiter() {
+        let limiter = CallLimiter::new(1);
+        let first = limiter.acquire().now_or_never().unwrap();
+
+        let mut second = Box::pin(limiter.acquire());
+        let mut third = Box::pin(limiter.acquire());
+        let mut fourth = Box::pin(limiter.acquire());
+        assert!(second.as_mut().now_or_never().is_none());
+        assert!(third.as_mut().now_or_never().is_none());
+        assert!(fourth.as_mut().now_or_never().is_none());
+        assert_eq!(limiter.queue_depth(), 3);
+
+        // Calls that stop waiting give up their place.
+        drop(third);
+        assert_eq!(limiter.queue_depth(), 2);
+
+        drop(first);
+        let second = second.now_or_never().unwrap();
+        assert!(fourth.as_mut().now_or_never().is_none());
+        assert_eq!(limiter.queue_depth(), 1);
+
+        drop(second);
+        let fourth = fourth.now_or_never().unwrap();
+        assert_eq!(limiter.queue_depth(), 0);
+
+        drop(fourth);
+        let _fifth = limiter.acquire().now_or_never().unwrap();
+    }
+}
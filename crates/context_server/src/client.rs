@@ -8,6 +8,7 @@ use serde::{Deserialize, Serialize, de::DeserializeOwned};
 use serde_json::{Value, value::RawValue};
 use smol::channel;
 use std::{
+    collections::VecDeque,
     fmt,
     path::PathBuf,
     pin::pin,
@@ -20,12 +21,21 @@ use std::{
 use util::{ResultExt, TryFutureExt};
 
 use crate::{
-    transport::{StdioTransport, Transport},
-    types::{CancelledParams, ClientNotification, Notification as _, notifications::Cancelled},
+    resource_limits::ResourceLimits,
+    traffic_log::{Direction, TrafficLog},
+    transport::{
+        ConnectionStatus, HttpRequestTimedOut, MessageTooLarge, RejectedMessage, Shutdown,
+        StdioTransport, Transport,
+    },
+    types::{
+        self, CancelledParams, ClientNotification, Notification as _, notifications::Cancelled,
+    },
 };
 
 const JSON_RPC_VERSION: &str = "2.0";
 const DEFAULT_REQUEST_TIMEOUT: Duration = Duration::from_secs(60);
+/// How many of the most recent stderr lines are kept to explain failures.
+const STDERR_TAIL_LINES: usize = 20;
 
 // Standard JSON-RPC error codes
 pub const PARSE_ERROR: i32 = -32700;
@@ -34,9 +44,11 @@ pub const METHOD_NOT_FOUND: i32 = -32601;
 pub const INVALID_PARAMS: i32 = -32602;
 pub const INTERNAL_ERROR: i32 = -32603;
 
-type ResponseHandler = Box<dyn Send + FnOnce(Result<String, Error>)>;
+type ResponseHandler = Box<dyn Send + FnOnce(Result<String>)>;
 type NotificationHandler = Box<dyn Send + FnMut(Value, AsyncApp)>;
 type RequestHandler = Box<dyn Send + FnMut(RequestId, &RawValue, AsyncApp)>;
+type StderrHandler = Box<dyn Send + FnMut(&str)>;
+type ConnectionStatusHandler = Box<dyn Send + FnMut(ConnectionStatus)>;
 
 #[derive(Debug, Clone, Eq, PartialEq, Hash, Serialize, Deserialize)]
 #[serde(untagged)]
@@ -52,21 +64,101 @@ pub(crate) struct Client {
     name: Arc<str>,
     notification_handlers: Arc<Mutex<HashMap<&'static str, NotificationHandler>>>,
     response_handlers: Arc<Mutex<Option<HashMap<RequestId, ResponseHandler>>>>,
+    request_handlers: Arc<Mutex<HashMap<&'static str, RequestHandler>>>,
+    /// Cancellation handles for requests the server sent to us that are still being handled.
+    pending_server_requests: Arc<Mutex<HashMap<RequestId, oneshot::Sender<()>>>>,
+    stderr_handler: Arc<Mutex<Option<StderrHandler>>>,
+    stderr_tail: StderrTail,
+    connection_status_handler: Arc<Mutex<Option<ConnectionStatusHandler>>>,
     #[allow(clippy::type_complexity)]
     #[allow(dead_code)]
     io_tasks: Mutex<Option<(Task<Option<()>>, Task<Option<()>>)>>,
     #[allow(dead_code)]
     output_done_rx: Mutex<Option<barrier::Receiver>>,
     executor: BackgroundExecutor,
-    #[allow(dead_code)]
     transport: Arc<dyn Transport>,
     request_timeout: Option<Duration>,
+    max_message_bytes: usize,
 }
 
 #[derive(Clone, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
 #[repr(transparent)]
 pub(crate) struct ContextServerId(pub Arc<str>);
 
+/// The last lines a context server wrote to stderr, which usually explain why it failed.
+#[derive(Clone, Default)]
+pub(crate) struct StderrTail(Arc<Mutex<VecDeque<String>>>);
+
+impl StderrTail {
+    fn push(&self, line: &str) {
+        let mut lines = self.0.lock();
+        if lines.len() == STDERR_TAIL_LINES {
+            lines.pop_front();
+        }
+        lines.push_back(line.to_string());
+    }
+
+    /// The recorded lines, oldest first.
+    pub(crate) fn lines(&self) -> Vec<String> {
+        self.0.lock().iter().cloned().collect()
+    }
+
+    /// Appends the recorded stderr output to the error's message, if there is any and it
+    /// wasn't appended already.
+    pub(crate) fn attach(&self, error: anyhow::Error) -> anyhow::Error {
+        let lines = self.0.lock();
+        if lines.is_empty() || error.is::<WithStderr>() {
+            return error;
+        }
+        let output = lines
+            .iter()
+            .map(String::as_str)
+            .collect::<Vec<_>>()
+            .join("\n");
+        WithStderr(format!("{error:#}\n\nstderr:\n{output}")).into()
+    }
+}
+
+/// An error message followed by the server's stderr output.
+#[derive(Debug)]
+struct WithStderr(String);
+
+impl fmt::Display for WithStderr {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.0)
+    }
+}
+
+impl std::error::Error for WithStderr {}
+
+/// Describes the parameters of a request without their values, which may hold secrets: the
+/// name of the tool or prompt, the names of its arguments and the size of the parameters.
+fn summarize_params(params: &Value) -> Option<String> {
+    if params.is_null() {
+        return None;
+    }
+    let mut summary = Vec::new();
+    if let Some(name) = params.get("name").and_then(Value::as_str) {
+        summary.push(format!("name: {name:?}"));
+    }
+    if let Some(arguments) = params.get("arguments").and_then(Value::as_object) {
+        let names = arguments.keys().map(String::as_str).collect::<Vec<_>>();
+        summary.push(format!("arguments: [{}]", names.join(", ")));
+    }
+    summary.push(format!("{} bytes", params.to_string().len()));
+    Some(summary.join(", "))
+}
+
+/// Whether the request failed because the connection to the server broke, rather than
+/// because of anything the server answered.
+pub(crate) fn is_transport_failure(error: &anyhow::Error) -> bool {
+    // Stderr output is only attached to errors of the connection.
+    error.is::<TransportFailed>()
+        || error.is::<ConnectionInterrupted>()
+        || error.is::<WithStderr>()
+        || error.is::<HttpRequestTimedOut>()
+}
+
 fn is_null_value<T: Serialize>(value: &T) -> bool {
     matches!(serde_json::to_value(value), Ok(Value::Null))
 }
@@ -149,6 +241,8 @@ pub struct ModelContextServerBinary {
     pub args: Vec<String>,
     pub env: Option<HashMap<String, String>>,
     pub timeout: Option<u64>,
+    #[serde(skip)]
+    pub resource_limits: ResourceLimits,
 }
 
 impl Client {
@@ -161,12 +255,13 @@ impl Client {
         server_id: ContextServerId,
         binary: ModelContextServerBinary,
         working_directory: &Option<PathBuf>,
+        max_message_bytes: usize,
+        traffic_log: TrafficLog,
         cx: AsyncApp,
     ) -> Result<Self> {
         log::debug!(
-            "starting context server (executable={:?}, args={:?})",
-            binary.executable,
-            &binary.args
+            "starting context server (executable={:?})",
+            binary.executable
         );
 
         let server_name = binary
@@ -176,16 +271,33 @@ impl Client {
             .unwrap_or_else(String::new);
 
         let timeout = binary.timeout.map(Duration::from_millis);
-        let transport = Arc::new(StdioTransport::new(binary, working_directory, &cx)?);
-        Self::new(server_id, server_name.into(), transport, timeout, cx)
+        let transport = Arc::new(StdioTransport::new(
+            binary,
+            working_directory,
+            max_message_bytes,
+            &cx,
+        )?);
+        Self::new(
+            server_id,
+            server_name.into(),
+            transport,
+            timeout,
+            max_message_bytes,
+            traffic_log,
+            cx,
+        )
     }
 
-    /// Creates a new Client instance for a context server.
+    /// Creates a new Client instance for a context server. Messages larger than
+    /// `max_message_bytes` aren't sent, and fail the request they answer when received. All
+    /// other messages are written to `traffic_log` while it's enabled.
     pub fn new(
         server_id: ContextServerId,
         server_name: Arc<str>,
         transport: Arc<dyn Transport>,
         request_timeout: Option<Duration>,
+        max_message_bytes: usize,
+        traffic_log: TrafficLog,
         cx: AsyncApp,
     ) -> Result<Self> {
         let (outbound_tx, outbound_rx) = channel::unbounded::<String>();
@@ -196,18 +308,42 @@ impl Client {
         let response_handlers =
             Arc::new(Mutex::new(Some(HashMap::<_, ResponseHandler>::default())));
         let request_handlers = Arc::new(Mutex::new(HashMap::<_, RequestHandler>::default()));
+        let pending_server_requests = Arc::new(Mutex::new(
+            HashMap::<RequestId, oneshot::Sender<()>>::default(),
+        ));
+        let stderr_handler = Arc::new(Mutex::new(None::<StderrHandler>));
+        let stderr_tail = StderrTail::default();
+        let connection_status_handler = Arc::new(Mutex::new(None::<ConnectionStatusHandler>));
+
+        notification_handlers.lock().insert(
+            Cancelled::METHOD,
+            Box::new({
+                let pending_server_requests = pending_server_requests.clone();
+                move |params, _| {
+                    if let Ok(params) = serde_json::from_value::<CancelledParams>(params) {
+                        // Dropping the sender cancels the in-flight handler.
+                        pending_server_requests.lock().remove(&params.request_id);
+                    }
+                }
+            }),
+        );
 
         let receive_input_task = cx.spawn({
             let notification_handlers = notification_handlers.clone();
             let response_handlers = response_handlers.clone();
             let request_handlers = request_handlers.clone();
             let transport = transport.clone();
+            let outbound_tx = outbound_tx.clone();
+            let traffic_log = traffic_log.clone();
             async move |cx| {
                 Self::handle_input(
                     transport,
                     notification_handlers,
                     request_handlers,
                     response_handlers,
+                    outbound_tx,
+                    max_message_bytes,
+                    traffic_log,
                     cx,
                 )
                 .log_err()
@@ -215,11 +351,41 @@ impl Client {
             }
         });
         let receive_err_task = cx.spawn({
+            let server_id = server_id.clone();
+            let transport = transport.clone();
+            let stderr_handler = stderr_handler.clone();
+            let stderr_tail = stderr_tail.clone();
+            async move |_| {
+                Self::handle_err(server_id, transport, stderr_handler, stderr_tail)
+                    .log_err()
+                    .await
+            }
+        });
+        let receive_status_task = cx.spawn({
+            let transport = transport.clone();
+            let response_handlers = response_handlers.clone();
+            let connection_status_handler = connection_status_handler.clone();
+            async move |_| {
+                Self::handle_connection_status(
+                    transport,
+                    response_handlers,
+                    connection_status_handler,
+                )
+                .await
+            }
+        });
+        let receive_rejected_task = cx.spawn({
             let transport = transport.clone();
-            async move |_| Self::handle_err(transport).log_err().await
+            let response_handlers = response_handlers.clone();
+            async move |_| Self::handle_rejected_messages(transport, response_handlers).await
         });
         let input_task = cx.spawn(async move |_| {
-            let (input, err) = futures::join!(receive_input_task, receive_err_task);
+            let (input, err, (), ()) = futures::join!(
+                receive_input_task,
+                receive_err_task,
+                receive_status_task,
+                receive_rejected_task
+            );
             input.or(err)
         });
 
@@ -230,6 +396,7 @@ impl Client {
                 outbound_rx,
                 output_done_tx,
                 response_handlers.clone(),
+                traffic_log,
             )
             .log_err()
         });
@@ -238,6 +405,11 @@ impl Client {
             server_id,
             notification_handlers,
             response_handlers,
+            request_handlers,
+            pending_server_requests,
+            stderr_handler,
+            stderr_tail,
+            connection_status_handler,
             name: server_name,
             next_id: Default::default(),
             outbound_tx,
@@ -246,6 +418,7 @@ impl Client {
             output_done_rx: Mutex::new(Some(output_done_rx)),
             transport,
             request_timeout,
+            max_message_bytes,
         })
     }
 
@@ -260,12 +433,22 @@ impl Client {
         notification_handlers: Arc<Mutex<HashMap<&'static str, NotificationHandler>>>,
         request_handlers: Arc<Mutex<HashMap<&'static str, RequestHandler>>>,
         response_handlers: Arc<Mutex<Option<HashMap<RequestId, ResponseHandler>>>>,
+        outbound_tx: channel::Sender<String>,
+        max_message_bytes: usize,
+        traffic_log: TrafficLog,
         cx: &mut AsyncApp,
     ) -> anyhow::Result<()> {
         let mut receiver = transport.receive();
 
         while let Some(message) = receiver.next().await {
+            // Transports that read messages whole are only checked once they were read.
+            if message.trim_end().len() > max_message_bytes {
+                let rejected = RejectedMessage::new(&message, max_message_bytes);
+                Self::reject_message(&response_handlers, rejected);
+                continue;
+            }
             log::trace!("recv: {}", &message);
+            traffic_log.record(Direction::Received, &message);
             if let Ok(request) = serde_json::from_str::<AnyRequest>(&message) {
                 let mut request_handlers = request_handlers.lock();
                 if let Some(handler) = request_handlers.get_mut(request.method) {
@@ -274,6 +457,17 @@ impl Client {
                         request.params.unwrap_or(RawValue::NULL),
                         cx.clone(),
                     );
+                } else {
+                    log::debug!("unhandled request from context server: {}", request.method);
+                    let response = serde_json::to_string(&Response::<()> {
+                        jsonrpc: JSON_RPC_VERSION,
+                        id: request.id,
+                        value: CspResult::Error(Some(Error {
+                            message: format!("method not found: {}", request.method),
+                            code: METHOD_NOT_FOUND,
+                        })),
+                    })?;
+                    outbound_tx.try_send(response).log_err();
                 }
             } else if let Ok(response) = serde_json::from_str::<AnyResponse>(&message) {
                 if let Some(handlers) = response_handlers.lock().as_mut()
@@ -291,21 +485,89 @@ impl Client {
             }
         }
 
+        // The server closed the connection, so requests waiting for a response fail.
+        response_handlers.lock().take();
         smol::future::yield_now().await;
 
         Ok(())
     }
 
     /// Handles the stderr output from the context server.
-    /// Continuously reads and logs any error messages from the server.
-    async fn handle_err(transport: Arc<dyn Transport>) -> anyhow::Result<()> {
-        while let Some(err) = transport.receive_err().next().await {
-            log::debug!("context server stderr: {}", err.trim());
+    /// Continuously reads and logs any error messages from the server, keeping the most recent
+    /// lines around to explain failures. Ends once the server closes its stderr.
+    async fn handle_err(
+        server_id: ContextServerId,
+        transport: Arc<dyn Transport>,
+        stderr_handler: Arc<Mutex<Option<StderrHandler>>>,
+        stderr_tail: StderrTail,
+    ) -> anyhow::Result<()> {
+        let mut receiver = transport.receive_err();
+        while let Some(line) = receiver.next().await {
+            let line = line.trim_end();
+            log::debug!("[context server {server_id}] stderr: {line}");
+            stderr_tail.push(line);
+            if let Some(handler) = stderr_handler.lock().as_mut() {
+                handler(line);
+            }
         }
 
         Ok(())
     }
 
+    /// Handles the changes in the connection of a transport that reconnects by itself,
+    /// failing the requests in flight when it's interrupted.
+    async fn handle_connection_status(
+        transport: Arc<dyn Transport>,
+        response_handlers: Arc<Mutex<Option<HashMap<RequestId, ResponseHandler>>>>,
+        connection_status_handler: Arc<Mutex<Option<ConnectionStatusHandler>>>,
+    ) {
+        let mut statuses = transport.connection_status();
+        while let Some(status) = statuses.next().await {
+            if status == ConnectionStatus::Interrupted {
+                // Unlike when the connection is closed, requests can still be made, as they
+                // are sent once the connection is reestablished.
+                let handlers = response_handlers
+                    .lock()
+                    .as_mut()
+                    .map(std::mem::take)
+                    .unwrap_or_default();
+                for (_, handler) in handlers {
+                    handler(Err(anyhow!(ConnectionInterrupted)));
+                }
+            }
+            if let Some(handler) = connection_status_handler.lock().as_mut() {
+                handler(status);
+            }
+        }
+    }
+
+    /// Fails the requests answered by the messages the transport dropped for being too large.
+    async fn handle_rejected_messages(
+        transport: Arc<dyn Transport>,
+        response_handlers: Arc<Mutex<Option<HashMap<RequestId, ResponseHandler>>>>,
+    ) {
+        let mut rejected_messages = transport.rejected_messages();
+        while let Some(rejected) = rejected_messages.next().await {
+            Self::reject_message(&response_handlers, rejected);
+        }
+    }
+
+    fn reject_message(
+        response_handlers: &Mutex<Option<HashMap<RequestId, ResponseHandler>>>,
+        rejected: RejectedMessage,
+    ) {
+        let handler = rejected.response_to.as_ref().and_then(|id| {
+            response_handlers
+                .lock()
+                .as_mut()
+                .and_then(|handlers| handlers.remove(id))
+        });
+        match handler {
+            Some(handler) => handler(Err(anyhow!(rejected.error))),
+            None => log::warn!("dropped message from context server: {}", rejected.error),
+        }
+    }
+
     /// Handles the output to the context server's stdin.
     /// This function continuously receives messages from the outbound channel,
     /// writes them to the server's stdin, and manages the lifecycle of response handlers.
@@ -314,6 +576,7 @@ impl Client {
         outbound_rx: channel::Receiver<String>,
         output_done_tx: barrier::Sender,
         response_handlers: Arc<Mutex<Option<HashMap<RequestId, ResponseHandler>>>>,
+        traffic_log: TrafficLog,
     ) -> anyhow::Result<()> {
         let _clear_response_handlers = util::defer({
             let response_handlers = response_handlers.clone();
@@ -323,7 +586,18 @@ impl Client {
         });
         while let Ok(message) = outbound_rx.recv().await {
             log::trace!("outgoing message: {}", message);
-            transport.send(message).await?;
+            traffic_log.record(Direction::Sent, &message);
+            if let Err(error) = transport.send(message).await {
+                // Requests waiting for a response fail with the reason, rather than as if the
+                // server closed the connection.
+                let handlers = response_handlers.lock().take().unwrap_or_default();
+                for (_, handler) in handlers {
+                    handler(Err(anyhow!("{error:#}").context(TransportFailed(
+                        "failed to send a message to the context server",
+                    ))));
+                }
+                return Err(error);
+            }
         }
         drop(output_done_tx);
         Ok(())
@@ -336,15 +610,24 @@ impl Client {
         method: &str,
         params: impl Serialize,
     ) -> Result<T> {
-        self.request_with(
-            method,
-            params,
-            None,
-            self.request_timeout.or(Some(DEFAULT_REQUEST_TIMEOUT)),
-        )
-        .await
+        self.request_with(method, params, None, self.default_request_timeout())
+            .await
+    }
+
+    pub(crate) fn default_request_timeout(&self) -> Option<Duration> {
+        self.request_timeout.or(Some(DEFAULT_REQUEST_TIMEOUT))
+    }
+
+    pub(crate) fn executor(&self) -> &BackgroundExecutor {
+        &self.executor
+    }
+
+    pub(crate) fn transport(&self) -> Arc<dyn Transport> {
+        self.transport.clone()
     }
 
+    /// Sends a request, logging when it starts and ends at debug level with the server's ID
+    /// and the request's ID, which errors mention too.
     pub async fn request_with<T: DeserializeOwned>(
         &self,
         method: &str,
@@ -353,6 +636,45 @@ impl Client {
         timeout: Option<Duration>,
     ) -> Result<T> {
         let id = self.next_id.fetch_add(1, SeqCst);
+        let params = serde_json::to_value(params).context("serializing request params")?;
+        let server_id = &self.server_id;
+        match summarize_params(&params) {
+            Some(summary) => {
+                log::debug!("[context server {server_id}] [request {id}] {method} ({summary})")
+            }
+            None => log::debug!("[context server {server_id}] [request {id}] {method}"),
+        }
+
+        let started = Instant::now();
+        let result = self
+            .send_request(id, method, params, cancel_rx, timeout)
+            .await;
+        let elapsed = started.elapsed();
+        match &result {
+            Ok(_) => log::debug!(
+                "[context server {server_id}] [request {id}] {method} succeeded after {elapsed:?}"
+            ),
+            Err(error) => log::debug!(
+                "[context server {server_id}] [request {id}] {method} failed after {elapsed:?}: \
+                {error:#}"
+            ),
+        }
+        result.map_err(|error| {
+            error.context(RequestFailed {
+                method: method.to_string(),
+                id,
+            })
+        })
+    }
+
+    async fn send_request<T: DeserializeOwned>(
+        &self,
+        id: i32,
+        method: &str,
+        params: Value,
+        cancel_rx: Option<oneshot::Receiver<()>>,
+        timeout: Option<Duration>,
+    ) -> Result<T> {
         let request = serde_json::to_string(&Request {
             jsonrpc: JSON_RPC_VERSION,
             id: RequestId::Int(id),
@@ -360,13 +682,19 @@ impl Client {
             params,
         })
         .unwrap();
+        if request.len() > self.max_message_bytes {
+            anyhow::bail!(MessageTooLarge {
+                size: request.len(),
+                limit: self.max_message_bytes,
+            });
+        }
 
         let (tx, rx) = oneshot::channel();
         let handle_response = self
             .response_handlers
             .lock()
             .as_mut()
-            .context("server shut down")
+            .ok_or_else(|| anyhow!(TransportFailed("server shut down")))
             .map(|handlers| {
                 handlers.insert(
                     RequestId::Int(id),
@@ -379,12 +707,11 @@ impl Client {
         let send = self
             .outbound_tx
             .try_send(request)
-            .context("failed to write to context server's stdin");
+            .map_err(|_| anyhow!(TransportFailed("failed to write to context server's stdin")));
 
         let executor = self.executor.clone();
-        let started = Instant::now();
-        handle_response?;
-        send?;
+        handle_response.map_err(|error| self.stderr_tail.attach(error))?;
+        send.map_err(|error| self.stderr_tail.attach(error))?;
 
         let mut timeout_fut = pin!(
             match timeout {
@@ -405,23 +732,31 @@ impl Client {
 
         select! {
             response = rx.fuse() => {
-                let elapsed = started.elapsed();
-                log::trace!("took {elapsed:?} to receive response to {method:?} id {id}");
-                match response? {
+                let response = response
+                    .map_err(|_| anyhow!(TransportFailed("context server closed the connection")))
+                    .map_err(|error| self.stderr_tail.attach(error))?;
+                match response {
                     Ok(response) => {
                         let parsed: AnyResponse = serde_json::from_str(&response)?;
                         if let Some(error) = parsed.error {
-                            Err(anyhow!(error.message))
+                            Err(anyhow!(RpcError {
+                                code: error.code,
+                                message: error.message,
+                            }))
                         } else if let Some(result) = parsed.result {
                             Ok(serde_json::from_str(result.get())?)
                         } else {
                             anyhow::bail!("Invalid response: no result or error");
                         }
                     }
-                    Err(_) => anyhow::bail!("cancelled")
+                    Err(error) if error.is::<TransportFailed>() => {
+                        Err(self.stderr_tail.attach(error))
+                    }
+                    Err(error) => Err(error)
                 }
             }
             _ = cancel_fut => {
+                self.remove_response_handler(id);
                 self.notify(
                     Cancelled::METHOD,
                     ClientNotification::Cancelled(CancelledParams {
@@ -432,12 +767,27 @@ impl Client {
                 anyhow::bail!(RequestCanceled)
             }
             _ = timeout_fut => {
+                self.remove_response_handler(id);
                 log::error!("cancelled csp request task for {method:?} id {id} which took over {:?}", timeout.unwrap());
-                anyhow::bail!("Context server request timeout");
+                self.notify(
+                    Cancelled::METHOD,
+                    ClientNotification::Cancelled(CancelledParams {
+                        request_id: RequestId::Int(id),
+                        reason: Some("request timed out".to_string())
+                    })
+                ).log_err();
+                anyhow::bail!(RequestTimedOut)
             }
         }
     }
 
+    /// Forgets a request we stopped waiting for, so a late response is dropped.
+    fn remove_response_handler(&self, id: i32) {
+        if let Some(handlers) = self.response_handlers.lock().as_mut() {
+            handlers.remove(&RequestId::Int(id));
+        }
+    }
+
     /// Sends a notification to the context server without expecting a response.
     /// This function serializes the notification and sends it through the outbound channel.
     pub fn notify(&self, method: &str, params: impl Serialize) -> Result<()> {
@@ -458,6 +808,84 @@ impl Client {
     ) {
         self.notification_handlers.lock().insert(method, f);
     }
+
+    /// Closes the connection and waits for the server to exit, see [`Transport::shutdown`].
+    pub async fn shutdown(&self, grace_period: Duration) -> Result<Shutdown> {
+        self.outbound_tx.close();
+        self.transport.shutdown(grace_period).await
+    }
+
+    /// Registers a handler called with each line the server writes to stderr.
+    pub fn on_stderr(&self, f: Box<dyn 'static + Send + FnMut(&str)>) {
+        *self.stderr_handler.lock() = Some(f);
+    }
+
+    /// Registers a handler called when the connection of a transport that reconnects by
+    /// itself is interrupted or reestablished.
+    pub fn on_connection_status(&self, f: Box<dyn 'static + Send + FnMut(ConnectionStatus)>) {
+        *self.connection_status_handler.lock() = Some(f);
+    }
+
+    pub(crate) fn stderr_tail(&self) -> StderrTail {
+        self.stderr_tail.clone()
+    }
+
+    /// Registers a handler for requests the server sends to the client.
+    ///
+    /// The handler's task is dropped, and no response is sent, if the server
+    /// cancels the request with `notifications/cancelled`.
+    pub fn on_request<T, F>(&self, mut handler: F)
+    where
+        T: types::Request,
+        F: 'static + Send + FnMut(T::Params, AsyncApp) -> Task<Result<T::Response>>,
+    {
+        let outbound_tx = self.outbound_tx.clone();
+        let pending_server_requests = self.pending_server_requests.clone();
+        self.request_handlers.lock().insert(
+            T::METHOD,
+            Box::new(move |id, params, cx| {
+                let response = match serde_json::from_str::<T::Params>(params.get()) {
+                    Ok(params) => handler(params, cx.clone()),
+                    Err(error) => Task::ready(Err(error.into())),
+                };
+                let (cancel_tx, cancel_rx) = oneshot::channel();
+                pending_server_requests.lock().insert(id.clone(), cancel_tx);
+
+                let outbound_tx = outbound_tx.clone();
+                let pending_server_requests = pending_server_requests.clone();
+                cx.spawn(async move |_| {
+                    let result = select! {
+                        result = response.fuse() => result,
+                        _ = cancel_rx.fuse() => {
+                            log::debug!("context server cancelled request {id:?}");
+                            return;
+                        }
+                    };
+                    pending_server_requests.lock().remove(&id);
+
+                    let response = match result {
+                        Ok(result) => serde_json::to_string(&Response {
+                            jsonrpc: JSON_RPC_VERSION,
+                            id,
+                            value: CspResult::Ok(Some(result)),
+                        }),
+                        Err(error) => serde_json::to_string(&Response::<T::Response> {
+                            jsonrpc: JSON_RPC_VERSION,
+                            id,
+                            value: CspResult::Error(Some(Error {
+                                message: error.to_string(),
+                                code: INTERNAL_ERROR,
+                            })),
+                        }),
+                    };
+                    if let Some(response) = response.log_err() {
+                        outbound_tx.try_send(response).log_err();
+                    }
+                })
+                .detach();
+            }),
+        );
+    }
 }
 
 #[derive(Debug)]
@@ -471,6 +899,71 @@ impl std::fmt::Display for RequestCanceled {
     }
 }
 
+#[derive(Debug)]
+pub struct RequestTimedOut;
+
+impl std::error::Error for RequestTimedOut {}
+
+impl std::fmt::Display for RequestTimedOut {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("Context server request timeout")
+    }
+}
+
+/// A request failed because the connection to the server was interrupted before it was
+/// answered. The server may or may not have handled it.
+#[derive(Debug)]
+pub struct ConnectionInterrupted;
+
+impl std::error::Error for ConnectionInterrupted {}
+
+impl std::fmt::Display for ConnectionInterrupted {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("Context server connection was interrupted, the request can be retried")
+    }
+}
+
+/// A request failed because the connection to the server was closed, or couldn't be
+/// written to.
+#[derive(Debug)]
+pub struct TransportFailed(pub(crate) &'static str);
+
+impl std::error::Error for TransportFailed {}
+
+impl std::fmt::Display for TransportFailed {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.0)
+    }
+}
+
+/// Says which request failed, as the context of the errors of requests.
+#[derive(Debug)]
+pub struct RequestFailed {
+    pub method: String,
+    pub id: i32,
+}
+
+impl std::fmt::Display for RequestFailed {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} request {} failed", self.method, self.id)
+    }
+}
+
+/// The server answered a request with a JSON-RPC error.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RpcError {
+    pub code: i32,
+    pub message: String,
+}
+
+impl std::error::Error for RpcError {}
+
+impl std::fmt::Display for RpcError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.message)
+    }
+}
+
 impl fmt::Display for ContextServerId {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         self.0.fmt(f)
@@ -485,3 +978,28 @@ impl fmt::Debug for Client {
             .finish_non_exhaustive()
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_summarize_params() {
+        assert_eq!(summarize_params(&Value::Null), None);
+        let params = serde_json::json!({
+            "name": "search",
+            "arguments": { "query": "api key sk-123", "limit": 10 },
+        });
+        assert_eq!(
+            summarize_params(&params).unwrap(),
+            format!(
+                "name: \"search\", arguments: [query, limit], {} bytes",
+                params.to_string().len()
+            )
+        );
+        assert_eq!(
+            summarize_params(&serde_json::json!({ "uri": "file:///secret.txt" })).unwrap(),
+            "28 bytes"
+        );
+    }
+}
@@ -0,0 +1,295 @@
+//! Support for servers asking the user for input (`elicitation/create`).
+
+use anyhow::{Context as _, Result, anyhow, bail};
+use gpui::{AsyncApp, Task};
+use serde_json::{Map, Value};
+
+use crate::ContextServerId;
+use crate::types::{ElicitAction, ElicitRequest, ElicitResult};
+
+/// Asks the user for the input a context server requested, typically in the middle of a
+/// tool call.
+pub trait ElicitationHandler: Send + Sync {
+    /// Shows the form to the user and returns what they did with it.
+    fn elicit(
+        &self,
+        server_id: ContextServerId,
+        form: ElicitationForm,
+        cx: &mut AsyncApp,
+    ) -> Task<Result<ElicitationResponse>>;
+}
+
+/// An [`ElicitationHandler`] that declines every request.
+pub struct DeclineElicitation;
+
+impl ElicitationHandler for DeclineElicitation {
+    fn elicit(
+        &self,
+        _server_id: ContextServerId,
+        _form: ElicitationForm,
+        _cx: &mut AsyncApp,
+    ) -> Task<Result<ElicitationResponse>> {
+        Task::ready(Ok(ElicitationResponse::Decline))
+    }
+}
+
+/// What the user did with a form.
+#[derive(Debug, Clone, PartialEq)]
+pub enum ElicitationResponse {
+    /// The user submitted the form with these values, keyed by field name.
+    Accept(Map<String, Value>),
+    /// The user refused to provide the input.
+    Decline,
+    /// The user dismissed the form without choosing.
+    Cancel,
+}
+
+impl From<ElicitationResponse> for ElicitResult {
+    fn from(response: ElicitationResponse) -> Self {
+        match response {
+            ElicitationResponse::Accept(content) => ElicitResult {
+                action: ElicitAction::Accept,
+                content: Some(content),
+            },
+            ElicitationResponse::Decline => ElicitResult {
+                action: ElicitAction::Decline,
+                content: None,
+            },
+            ElicitationResponse::Cancel => ElicitResult {
+                action: ElicitAction::Cancel,
+                content: None,
+            },
+        }
+    }
+}
+
+/// The input a server asked for, as a form of flat fields.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ElicitationForm {
+    /// Why the server asks for the input.
+    pub message: String,
+    /// The fields in the order of the schema's properties.
+    pub fields: Vec<FormField>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct FormField {
+    pub name: String,
+    pub title: Option<String>,
+    pub description: Option<String>,
+    pub kind: FieldKind,
+    pub required: bool,
+    pub default: Option<Value>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum FieldKind {
+    /// Text, with a format like `email`, `uri`, `date` or `date-time`.
+    String {
+        format: Option<String>,
+    },
+    Number {
+        minimum: Option<f64>,
+        maximum: Option<f64>,
+    },
+    Integer {
+        minimum: Option<f64>,
+        maximum: Option<f64>,
+    },
+    Boolean,
+    /// One of the given values, with the labels to show for them if the server provided
+    /// any.
+    Enum {
+        values: Vec<String>,
+        labels: Option<Vec<String>>,
+    },
+}
+
+impl ElicitationForm {
+    /// Converts the schema of the request into fields. Only the primitive types the
+    /// protocol allows are supported, not nested objects or arrays.
+    pub fn parse(request: &ElicitRequest) -> Result<Self> {
+        let schema = &request.requested_schema;
+        if schema.get("type").and_then(Value::as_str) != Some("object") {
+            bail!("requested schema must be an object schema");
+        }
+        let required = schema
+            .get("required")
+            .and_then(Value::as_array)
+            .map(|required| {
+                required
+                    .iter()
+                    .filter_map(Value::as_str)
+                    .collect::<Vec<_>>()
+            })
+            .unwrap_or_default();
+        let no_properties = Map::new();
+        let properties = match schema.get("properties") {
+            Some(properties) => properties
+                .as_object()
+                .context("requested schema has invalid properties")?,
+            None => &no_properties,
+        };
+
+        let fields = properties
+            .iter()
+            .map(|(name, property)| {
+                let kind = field_kind(property)
+                    .with_context(|| format!("unsupported schema for field {name:?}"))?;
+                let text = |key: &str| -> Option<String> {
+                    property.get(key).and_then(Value::as_str).map(Into::into)
+                };
+                Ok(FormField {
+                    name: name.clone(),
+                    title: text("title"),
+                    description: text("description"),
+                    kind,
+                    required: required.contains(&name.as_str()),
+                    default: property.get("default").cloned(),
+                })
+            })
+            .collect::<Result<_>>()?;
+        Ok(Self {
+            message: request.message.clone(),
+            fields,
+        })
+    }
+}
+
+fn field_kind(property: &Value) -> Result<FieldKind> {
+    let number = |key: &str| property.get(key).and_then(Value::as_f64);
+    let strings = |key: &str| -> Result<Option<Vec<String>>> {
+        property
+            .get(key)
+            .map(|values| {
+                values
+                    .as_array()
+                    .and_then(|values| {
+                        values
+                            .iter()
+                            .map(|value| value.as_str().map(Into::into))
+                            .collect()
+                    })
+                    .with_context(|| format!("{key} must be a list of strings"))
+            })
+            .transpose()
+    };
+    match property.get("type").and_then(Value::as_str) {
+        Some("string") => match strings("enum")? {
+            Some(values) => Ok(FieldKind::Enum {
+                values,
+                labels: strings("enumNames")?,
+            }),
+            None => Ok(FieldKind::String {
+                format: property
+                    .get("format")
+                    .and_then(Value::as_str)
+                    .map(Into::into),
+            }),
+        },
+        Some("number") => Ok(FieldKind::Number {
+            minimum: number("minimum"),
+            maximum: number("maximum"),
+        }),
+        Some("integer") => Ok(FieldKind::Integer {
+            minimum: number("minimum"),
+            maximum: number("maximum"),
+        }),
+        Some("boolean") => Ok(FieldKind::Boolean),
+        Some(ty) => Err(anyhow!("type {ty:?} is not supported")),
+        None => Err(anyhow!("missing type")),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn parse(schema: Value) -> Result<ElicitationForm> {
+        ElicitationForm::parse(&ElicitRequest {
+            message: "Configure the deployment".into(),
+            requested_schema: schema,
+        })
+    }
+
+    #[test]
+    fn test_parse_form() {
+        let form = parse(json!({
+            "type": "object",
+            "properties": {
+                "environment": {
+                    "type": "string",
+                    "title": "Environment",
+                    "enum": ["staging", "production"],
+                    "enumNames": ["Staging", "Production"],
+                },
+                "replicas": { "type": "integer", "minimum": 1, "default": 2 },
+                "notify": { "type": "boolean", "description": "Send an email when done" },
+                "email": { "type": "string", "format": "email" },
+            },
+            "required": ["environment", "replicas"],
+        }))
+        .unwrap();
+        assert_eq!(form.message, "Configure the deployment");
+
+        let field = |name: &str| form.fields.iter().find(|field| field.name == name).unwrap();
+        assert_eq!(
+            field("environment"),
+            &FormField {
+                name: "environment".into(),
+                title: Some("Environment".into()),
+                description: None,
+                kind: FieldKind::Enum {
+                    values: vec!["staging".into(), "production".into()],
+                    labels: Some(vec!["Staging".into(), "Production".into()]),
+                },
+                required: true,
+                default: None,
+            }
+        );
+        assert_eq!(
+            field("replicas").kind,
+            FieldKind::Integer {
+                minimum: Some(1.),
+                maximum: None,
+            }
+        );
+        assert_eq!(field("replicas").default, Some(json!(2)));
+        assert_eq!(field("notify").kind, FieldKind::Boolean);
+        assert!(!field("notify").required);
+        assert_eq!(
+            field("notify").description.as_deref(),
+            Some("Send an email when done")
+        );
+        assert_eq!(
+            field("email").kind,
+            FieldKind::String {
+                format: Some("email".into()),
+            }
+        );
+    }
+
+    #[test]
+    fn test_parse_unsupported_schemas() {
+        let error = |schema: Value| format!("{:#}", parse(schema).unwrap_err());
+        assert_eq!(
+            error(json!({ "type": "array" })),
+            "requested schema must be an object schema"
+        );
+        assert_eq!(
+            error(json!({
+                "type": "object",
+                "properties": { "address": { "type": "object" } },
+            })),
+            "unsupported schema for field \"address\": type \"object\" is not supported"
+        );
+        assert_eq!(
+            error(json!({
+                "type": "object",
+                "properties": { "size": { "type": "string", "enum": [1, 2] } },
+            })),
+            "unsupported schema for field \"size\": enum must be a list of strings"
+        );
+    }
+}
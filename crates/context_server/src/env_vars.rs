@@ -0,0 +1,147 @@
+//! Expansion of environment variable references in context server commands.
+
+use anyhow::{Context as _, Result, anyhow, bail};
+
+/// Expands `$VAR`, `${VAR}` and `${VAR:-default}` references in `input`, looking up
+/// variables with `lookup`.
+///
+/// `$$` stands for a literal `$`, and a `$` that isn't followed by a variable name is kept
+/// as is. Defaults are expanded themselves, so `${A:-${B}}` falls back to `B`. Referencing
+/// a variable that isn't set, and has no default, is an error naming the variable.
+pub fn expand_env_vars(input: &str, lookup: impl Fn(&str) -> Option<String>) -> Result<String> {
+    expand(input, &lookup)
+}
+
+fn expand(input: &str, lookup: &dyn Fn(&str) -> Option<String>) -> Result<String> {
+    let mut output = String::with_capacity(input.len());
+    let mut rest = input;
+    while let Some(ix) = rest.find('$') {
+        output.push_str(&rest[..ix]);
+        rest = &rest[ix + 1..];
+
+        if let Some(after) = rest.strip_prefix('$') {
+            output.push('$');
+            rest = after;
+        } else if let Some(after) = rest.strip_prefix('{') {
+            let end = closing_brace(after)
+                .with_context(|| format!("unterminated variable reference in {input:?}"))?;
+            let reference = &after[..end];
+            rest = &after[end + 1..];
+
+            let (name, default) = match reference.split_once(":-") {
+                Some((name, default)) => (name, Some(default)),
+                None => (reference, None),
+            };
+            if !is_variable_name(name) {
+                bail!("invalid variable name {name:?} in {input:?}");
+            }
+            // Like in shells, defaults also replace variables that are set but empty.
+            let value = lookup(name).filter(|value| !value.is_empty() || default.is_none());
+            match (value, default) {
+                (Some(value), _) => output.push_str(&value),
+                (None, Some(default)) => output.push_str(&expand(default, lookup)?),
+                (None, None) => return Err(unset_variable(name)),
+            }
+        } else {
+            let len = variable_name_len(rest);
+            if len == 0 {
+                output.push('$');
+                continue;
+            }
+            let name = &rest[..len];
+            rest = &rest[len..];
+            output.push_str(&lookup(name).ok_or_else(|| unset_variable(name))?);
+        }
+    }
+    output.push_str(rest);
+    Ok(output)
+}
+
+/// Finds the `}` closing a `${`, skipping over nested `${...}` references and `$$` escapes.
+fn closing_brace(text: &str) -> Option<usize> {
+    let mut depth = 0;
+    let mut chars = text.char_indices().peekable();
+    while let Some((ix, char)) = chars.next() {
+        match char {
+            '$' => {
+                if let Some((_, '{')) = chars.next_if(|(_, next)| matches!(next, '$' | '{')) {
+                    depth += 1;
+                }
+            }
+            '}' if depth == 0 => return Some(ix),
+            '}' => depth -= 1,
+            _ => {}
+        }
+    }
+    None
+}
+
+fn variable_name_len(text: &str) -> usize {
+    text.char_indices()
+        .find(|&(ix, char)| {
+            !(char == '_' || char.is_ascii_alphabetic() || (ix > 0 && char.is_ascii_digit()))
+        })
+        .map_or(text.len(), |(ix, _)| ix)
+}
+
+fn is_variable_name(name: &str) -> bool {
+    !name.is_empty() && variable_name_len(name) == name.len()
+}
+
+fn unset_variable(name: &str) -> anyhow::Error {
+    anyhow!("environment variable {name} is not set")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn expand(input: &str) -> Result<String> {
+        expand_env_vars(input, |name| match name {
+            "HOME" => Some("/home/user".to_string()),
+            "TOKEN" => Some("secret".to_string()),
+            "EMPTY" => Some(String::new()),
+            _ => None,
+        })
+    }
+
+    #[test]
+    fn test_expand_env_vars() {
+        assert_eq!(expand("--token").unwrap(), "--token");
+        assert_eq!(expand("$TOKEN").unwrap(), "secret");
+        assert_eq!(
+            expand("${HOME}/creds.json").unwrap(),
+            "/home/user/creds.json"
+        );
+        assert_eq!(expand("$HOME/$TOKEN").unwrap(), "/home/user/secret");
+        assert_eq!(expand("${HOME}_DIR").unwrap(), "/home/user_DIR");
+        assert_eq!(expand("${UNSET:-fallback}").unwrap(), "fallback");
+        assert_eq!(expand("${EMPTY:-fallback}").unwrap(), "fallback");
+        assert_eq!(expand("${EMPTY}").unwrap(), "");
+        assert_eq!(expand("${TOKEN:-fallback}").unwrap(), "secret");
+        assert_eq!(expand("${UNSET:-}").unwrap(), "");
+        assert_eq!(expand("cost: 5$").unwrap(), "cost: 5$");
+        assert_eq!(expand("$1 $-").unwrap(), "$1 $-");
+
+        let error = expand("--token=$GITHUB_TOKEN").unwrap_err().to_string();
+        assert_eq!(error, "environment variable GITHUB_TOKEN is not set");
+        assert!(expand("${HOME").is_err());
+        assert!(expand("${}").is_err());
+    }
+
+    #[test]
+    fn test_expand_nested_looking_references() {
+        assert_eq!(expand("${UNSET:-${HOME}}").unwrap(), "/home/user");
+        assert_eq!(
+            expand("${UNSET:-${ALSO_UNSET:-$TOKEN}}/x").unwrap(),
+            "secret/x"
+        );
+        assert_eq!(expand("${TOKEN:-${UNSET}}").unwrap(), "secret");
+        assert_eq!(expand("$${HOME}").unwrap(), "${HOME}");
+        assert_eq!(expand("$$HOME").unwrap(), "$HOME");
+        assert_eq!(expand("$$$HOME").unwrap(), "$/home/user");
+        assert_eq!(expand("${UNSET:-$$}").unwrap(), "$");
+        assert_eq!(expand("${UNSET:-$${HOME}}").unwrap(), "${HOME}");
+        assert!(expand("${UNSET:-${ALSO_UNSET}}").is_err());
+    }
+}
@@ -0,0 +1,333 @@
+//! The ways operations on a context server fail.
+
+use std::fmt::{self, Debug, Display};
+use std::time::Duration;
+
+use crate::ContextServerId;
+use crate::client::{self, RpcError};
+use crate::tool_result::ToolContent;
+use crate::transport::MessageTooLarge;
+
+/// Why an operation on a context server failed, distinguishing failures worth retrying from
+/// ones the server or tool reported.
+///
+/// Converts into an [`anyhow::Error`] with `?`, and back with [`From`], which recovers the
+/// variant from the error's type.
+#[derive(Debug)]
+pub enum ContextServerError {
+    /// The server isn't running.
+    NotConnected { server: ContextServerId },
+    /// The connection to the server broke before it answered, for example because its
+    /// process exited.
+    Transport(anyhow::Error),
+    /// The server answered with a JSON-RPC error.
+    Rpc {
+        code: i32,
+        message: String,
+        /// The ID of the request, which the debug logs of the request mention.
+        request_id: Option<i32>,
+    },
+    /// The tool ran, but reported that it failed. The content describes the failure.
+    ToolError {
+        tool: String,
+        content: Vec<ToolContent>,
+    },
+    /// The request, or the server's answer to it, was larger than the server's messages may
+    /// be. The connection stays usable.
+    MessageTooLarge {
+        /// The size of the message in bytes.
+        size: usize,
+        limit: usize,
+        request_id: Option<i32>,
+    },
+    /// The server didn't answer in time, so the request was cancelled.
+    TimedOut {
+        /// The tool being called, for tool calls.
+        tool: Option<String>,
+        timeout: Option<Duration>,
+    },
+    /// The call kept failing after being retried, see [`crate::tool_retry::RetryPolicy`].
+    Retried {
+        /// How many times the call was made.
+        attempts: u32,
+        last_error: Box<ContextServerError>,
+    },
+    /// Anything else, like a cancelled request or an operation the server doesn't support.
+    Other(anyhow::Error),
+}
+
+impl ContextServerError {
+    /// Whether the error is, or was caused by, an `E`, like [`anyhow::Error::is`].
+    pub fn is<E: Display + Debug + Send + Sync + 'static>(&self) -> bool {
+        self.downcast_ref::<E>().is_some()
+    }
+
+    pub fn downcast_ref<E: Display + Debug + Send + Sync + 'static>(&self) -> Option<&E> {
+        match self {
+            Self::Transport(error) | Self::Other(error) => error.downcast_ref(),
+            Self::Retried { last_error, .. } => last_error.downcast_ref(),
+            _ => None,
+        }
+    }
+
+    pub fn downcast<E: Display + Debug + Send + Sync + 'static>(self) -> Result<E, Self> {
+        match self {
+            Self::Transport(error) => error.downcast().map_err(Self::Transport),
+            Self::Other(error) => error.downcast().map_err(Self::Other),
+            Self::Retried {
+                attempts,
+                last_error,
+            } => last_error.downcast().map_err(|last_error| Self::Retried {
+                attempts,
+                last_error: Box::new(last_error),
+            }),
+            error => Err(error),
+        }
+    }
+}
+
+impl From<anyhow::Error> for ContextServerError {
+    fn from(error: anyhow::Error) -> Self {
+        let error = match error.downcast::<Self>() {
+            Ok(error) => return error,
+            Err(error) => error,
+        };
+        if let Some(RpcError { code, message }) = error.downcast_ref::<RpcError>() {
+            Self::Rpc {
+                code: *code,
+                message: message.clone(),
+                request_id: error
+                    .downcast_ref::<client::RequestFailed>()
+                    .map(|request| request.id),
+            }
+        } else if let Some(MessageTooLarge { size, limit }) = error.downcast_ref() {
+            Self::MessageTooLarge {
+                size: *size,
+                limit: *limit,
+                request_id: error
+                    .downcast_ref::<client::RequestFailed>()
+                    .map(|request| request.id),
+            }
+        } else if error.is::<client::RequestTimedOut>() {
+            Self::TimedOut {
+                tool: None,
+                timeout: None,
+            }
+        } else if client::is_transport_failure(&error) {
+            Self::Transport(error)
+        } else {
+            Self::Other(error)
+        }
+    }
+}
+
+impl std::error::Error for ContextServerError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            // The wrapped error and its causes are displayed in place of this one.
+            Self::Transport(_) | Self::Other(_) => None,
+            Self::Retried { last_error, .. } => last_error.source(),
+            _ => None,
+        }
+    }
+}
+
+impl Display for ContextServerError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::NotConnected { server } => write!(f, "context server {server} is not running"),
+            // Failed requests have the request they were made in as context.
+            Self::Transport(error) | Self::Other(error) => write!(f, "{error:#}"),
+            Self::Rpc {
+                message,
+                request_id: Some(request_id),
+                ..
+            } => write!(f, "{message} (request {request_id})"),
+            Self::Rpc { message, .. } => f.write_str(message),
+            Self::MessageTooLarge {
+                size,
+                limit,
+                request_id,
+            } => {
+                Display::fmt(
+                    &MessageTooLarge {
+                        size: *size,
+                        limit: *limit,
+                    },
+                    f,
+                )?;
+                if let Some(request_id) = request_id {
+                    write!(f, " (request {request_id})")?;
+                }
+                Ok(())
+            }
+            Self::ToolError { tool, content } => {
+                write!(f, "tool {tool:?} failed")?;
+                let text = content
+                    .iter()
+                    .filter_map(|content| match content {
+                        ToolContent::Text(text) => Some(text.as_str()),
+                        _ => None,
+                    })
+                    .collect::<String>();
+                if !text.is_empty() {
+                    write!(f, ": {text}")?;
+                }
+                Ok(())
+            }
+            Self::TimedOut {
+                tool: Some(tool),
+                timeout: Some(timeout),
+            } => write!(f, "tool {tool:?} timed out after {}ms", timeout.as_millis()),
+            Self::TimedOut { .. } => Display::fmt(&client::RequestTimedOut, f),
+            Self::Retried {
+                attempts,
+                last_error,
+            } => write!(f, "{last_error} (gave up after {attempts} attempts)"),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use anyhow::anyhow;
+
+    use super::*;
+    use crate::client::{ConnectionInterrupted, RequestCanceled, TransportFailed};
+    use crate::protocol::{CapabilityNotSupported, ServerCapability};
+
+    #[test]
+    fn test_from_anyhow() {
+        let error = ContextServerError::from(anyhow!(RpcError {
+            code: -32602,
+            message: "unknown prompt".into(),
+        }));
+        assert!(matches!(
+            &error,
+            ContextServerError::Rpc { code: -32602, message, request_id: None }
+                if message == "unknown prompt"
+        ));
+        assert_eq!(error.to_string(), "unknown prompt");
+
+        let error = ContextServerError::from(
+            anyhow!(RpcError {
+                code: -32602,
+                message: "unknown prompt".into(),
+            })
+            .context(client::RequestFailed {
+                method: "prompts/get".into(),
+                id: 7,
+            }),
+        );
+        assert!(matches!(
+            error,
+            ContextServerError::Rpc {
+                request_id: Some(7),
+                ..
+            }
+        ));
+        assert_eq!(error.to_string(), "unknown prompt (request 7)");
+
+        let error = ContextServerError::from(
+            anyhow!(MessageTooLarge {
+                size: 2048,
+                limit: 1024,
+            })
+            .context(client::RequestFailed {
+                method: "tools/call".into(),
+                id: 4,
+            }),
+        );
+        assert!(matches!(
+            error,
+            ContextServerError::MessageTooLarge {
+                size: 2048,
+                limit: 1024,
+                request_id: Some(4),
+            }
+        ));
+        assert_eq!(
+            error.to_string(),
+            "Context server message of 2048 bytes exceeds the limit of 1024 bytes (request 4)"
+        );
+
+        let error = ContextServerError::from(anyhow!(client::RequestTimedOut));
+        assert!(matches!(
+            error,
+            ContextServerError::TimedOut {
+                tool: None,
+                timeout: None
+            }
+        ));
+        assert_eq!(error.to_string(), "Context server request timeout");
+
+        for error in [
+            anyhow!(ConnectionInterrupted),
+            anyhow!(TransportFailed("context server closed the connection")),
+        ] {
+            let error = ContextServerError::from(error);
+            assert!(matches!(error, ContextServerError::Transport(_)), "{error}");
+        }
+        let error = ContextServerError::from(
+            anyhow!(TransportFailed("context server closed the connection")).context(
+                client::RequestFailed {
+                    method: "tools/call".into(),
+                    id: 3,
+                },
+            ),
+        );
+        assert!(matches!(error, ContextServerError::Transport(_)));
+        assert_eq!(
+            error.to_string(),
+            "tools/call request 3 failed: context server closed the connection"
+        );
+
+        let error = ContextServerError::from(anyhow!(RequestCanceled));
+        assert!(matches!(error, ContextServerError::Other(_)));
+        assert!(error.is::<RequestCanceled>());
+
+        // Errors that went through `anyhow` keep their variant.
+        let error = anyhow::Error::from(ContextServerError::NotConnected {
+            server: ContextServerId("test".into()),
+        });
+        let error = ContextServerError::from(error);
+        assert!(matches!(error, ContextServerError::NotConnected { .. }));
+        assert_eq!(error.to_string(), "context server test is not running");
+
+        let error = anyhow::Error::from(ContextServerError::ToolError {
+            tool: "search".into(),
+            content: vec![ToolContent::Text("rate limited".into())],
+        });
+        assert_eq!(error.to_string(), "tool \"search\" failed: rate limited");
+        assert!(matches!(
+            ContextServerError::from(error),
+            ContextServerError::ToolError { tool, .. } if tool == "search"
+        ));
+
+        let error = ContextServerError::TimedOut {
+            tool: Some("hang".into()),
+            timeout: Some(Duration::from_secs(1)),
+        };
+        assert_eq!(error.to_string(), "tool \"hang\" timed out after 1000ms");
+        assert!(matches!(
+            ContextServerError::from(anyhow::Error::from(error)),
+            ContextServerError::TimedOut { tool: Some(_), .. }
+        ));
+
+        let error = ContextServerError::Retried {
+            attempts: 3,
+            last_error: Box::new(ContextServerError::from(anyhow!(ConnectionInterrupted))),
+        };
+        assert!(error.is::<ConnectionInterrupted>());
+        assert_eq!(
+            error.to_string(),
+            "Context server connection was interrupted, the request can be retried (gave up \
+            after 3 attempts)"
+        );
+
+        let error = ContextServerError::from(anyhow!(CapabilityNotSupported {
+            capability: ServerCapability::Tools
+        }));
+        assert!(error.downcast::<CapabilityNotSupported>().is_ok());
+    }
+}
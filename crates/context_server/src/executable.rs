@@ -0,0 +1,116 @@
+//! Locating the executables of stdio context servers.
+
+use std::ffi::OsStr;
+use std::path::{Component, Path, PathBuf};
+
+use anyhow::{Result, anyhow};
+use util::paths::home_dir;
+
+/// Resolves the executable of a context server command.
+///
+/// A leading `~` is expanded to the home directory. Bare names like `npx` are searched for in
+/// `search_path`, usually the `PATH` of the user's shell, followed by Zed's own `PATH`, which
+/// matters when Zed was launched from the GUI and didn't inherit the shell's `PATH`. On
+/// Windows, the extensions in `PATHEXT` (such as `.exe` and `.cmd`) are tried as well.
+pub fn resolve_executable(
+    executable: &Path,
+    search_path: Option<&OsStr>,
+    working_directory: Option<&Path>,
+) -> Result<PathBuf> {
+    let executable = expand_home(executable);
+
+    let mut components = executable.components();
+    let Some(Component::Normal(name)) = components.next() else {
+        return Ok(executable);
+    };
+    if components.next().is_some() {
+        return Ok(executable);
+    }
+
+    let process_path = std::env::var_os("PATH");
+    let mut directories = Vec::new();
+    for path in search_path.into_iter().chain(process_path.as_deref()) {
+        for directory in std::env::split_paths(path) {
+            if !directories.contains(&directory) {
+                directories.push(directory);
+            }
+        }
+    }
+
+    let working_directory = working_directory
+        .map(Path::to_path_buf)
+        .or_else(|| std::env::current_dir().ok())
+        .unwrap_or_default();
+    let paths = std::env::join_paths(&directories)?;
+    which::which_in(name, Some(paths), working_directory).map_err(|_| {
+        let searched = directories
+            .iter()
+            .map(|directory| format!("  {}", directory.display()))
+            .collect::<Vec<_>>()
+            .join("\n");
+        anyhow!(
+            "could not find {:?} in any of these directories:\n{searched}",
+            name
+        )
+    })
+}
+
+/// Expands a leading `~` in `path` to the home directory.
+pub fn expand_home(path: &Path) -> PathBuf {
+    match path.strip_prefix("~") {
+        Ok(rest) => home_dir().join(rest),
+        Err(_) => path.to_path_buf(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::ffi::OsString;
+
+    #[test]
+    fn test_resolve_executable() {
+        let directory = tempfile::tempdir().unwrap();
+        let bin = directory.path().join("bin");
+        std::fs::create_dir(&bin).unwrap();
+        let server = bin.join(if cfg!(windows) {
+            "some-server.cmd"
+        } else {
+            "some-server"
+        });
+        std::fs::write(&server, "").unwrap();
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt as _;
+            std::fs::set_permissions(&server, std::fs::Permissions::from_mode(0o755)).unwrap();
+        }
+
+        let search_path = std::env::join_paths([directory.path().join("empty"), bin]).unwrap();
+        let resolve = |executable: &str, search_path: Option<&OsString>| {
+            resolve_executable(
+                Path::new(executable),
+                search_path.map(OsString::as_os_str),
+                Some(directory.path()),
+            )
+        };
+
+        assert_eq!(resolve("some-server", Some(&search_path)).unwrap(), server);
+        assert_eq!(
+            resolve("~/.local/bin/some-server", None).unwrap(),
+            home_dir().join(".local/bin/some-server")
+        );
+        assert_eq!(
+            resolve("/opt/some-server", None).unwrap(),
+            PathBuf::from("/opt/some-server")
+        );
+
+        let error = resolve("some-missing-server", Some(&search_path))
+            .unwrap_err()
+            .to_string();
+        assert!(error.contains("\"some-missing-server\""), "{error}");
+        assert!(
+            error.contains(&directory.path().join("empty").display().to_string()),
+            "{error}"
+        );
+    }
+}
@@ -0,0 +1,127 @@
+//! Headers that are computed when connecting, for servers expecting short-lived tokens.
+
+use std::path::Path;
+use std::process::Stdio;
+use std::time::Duration;
+
+use anyhow::{Context as _, Result, bail};
+use async_trait::async_trait;
+use collections::HashMap;
+use futures::future::{self, Either};
+use settings::ContextServerHeaderCommand;
+
+use crate::executable::expand_home;
+
+const COMMAND_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// Supplies headers for the requests to an HTTP server, on top of its configured headers.
+///
+/// Headers are requested before the server connects, and periodically while it runs, so
+/// that tokens can be replaced before they expire.
+#[async_trait]
+pub trait HeaderProvider: Send + Sync {
+    /// The name of the provider, used in error messages.
+    fn name(&self) -> String;
+
+    async fn headers(&self) -> Result<HashMap<String, String>>;
+}
+
+/// Runs a command and sends what it prints as a token, similar to Docker's credential
+/// helpers.
+pub struct CommandHeaderProvider {
+    command: ContextServerHeaderCommand,
+}
+
+impl CommandHeaderProvider {
+    pub fn new(command: ContextServerHeaderCommand) -> Self {
+        Self { command }
+    }
+}
+
+#[async_trait]
+impl HeaderProvider for CommandHeaderProvider {
+    fn name(&self) -> String {
+        format!("header command {:?}", self.command.command)
+    }
+
+    async fn headers(&self) -> Result<HashMap<String, String>> {
+        let mut command =
+            util::command::new_smol_command(expand_home(Path::new(&self.command.command)));
+        command
+            .args(&self.command.args)
+            .stdin(Stdio::null())
+            .kill_on_drop(true);
+        let output = command.output();
+        let timeout = smol::Timer::after(COMMAND_TIMEOUT);
+        let output = match future::select(Box::pin(output), timeout).await {
+            Either::Left((output, _)) => output.context("failed to run command")?,
+            Either::Right(_) => bail!("command timed out after {COMMAND_TIMEOUT:?}"),
+        };
+        if !output.status.success() {
+            bail!(
+                "command failed ({}): {}",
+                output.status,
+                String::from_utf8_lossy(&output.stderr).trim()
+            );
+        }
+
+        let token = String::from_utf8(output.stdout).context("command printed invalid UTF-8")?;
+        let token = token.trim();
+        if token.is_empty() {
+            bail!("command printed no token");
+        }
+        let header = self.command.header.as_deref().unwrap_or("Authorization");
+        let value = match self.command.scheme.as_deref().unwrap_or("Bearer") {
+            "" => token.to_string(),
+            scheme => format!("{scheme} {token}"),
+        };
+        Ok(HashMap::from_iter([(header.to_string(), value)]))
+    }
+}
+
+#[cfg(all(test, unix))]
+mod tests {
+    use super::*;
+
+    fn provider(script: &str) -> CommandHeaderProvider {
+        CommandHeaderProvider::new(ContextServerHeaderCommand {
+            command: "sh".into(),
+            args: vec!["-c".into(), script.into()],
+            ..Default::default()
+        })
+    }
+
+    #[test]
+    fn test_command_header_provider() {
+        let headers = smol::block_on(provider("echo token-1").headers()).unwrap();
+        assert_eq!(
+            headers,
+            HashMap::from_iter([("Authorization".to_string(), "Bearer token-1".to_string())])
+        );
+
+        let provider = CommandHeaderProvider::new(ContextServerHeaderCommand {
+            header: Some("X-Api-Key".into()),
+            scheme: Some("".into()),
+            ..provider("printf 'token-2\\n\\n'").command
+        });
+        let headers = smol::block_on(provider.headers()).unwrap();
+        assert_eq!(
+            headers,
+            HashMap::from_iter([("X-Api-Key".to_string(), "token-2".to_string())])
+        );
+    }
+
+    #[test]
+    fn test_command_header_provider_errors() {
+        let error = |script: &str| {
+            smol::block_on(provider(script).headers())
+                .unwrap_err()
+                .to_string()
+        };
+        assert_eq!(
+            error("echo 'not logged in' >&2; exit 1"),
+            "command failed (exit status: 1): not logged in"
+        );
+        assert_eq!(error("true"), "command printed no token");
+    }
+}
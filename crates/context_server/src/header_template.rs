@@ -0,0 +1,206 @@
+//! Placeholders in the headers of remote context servers.
+
+use std::fmt;
+
+use anyhow::{Result, anyhow, bail};
+
+/// A header value that may reference environment variables with `${env:NAME}` and secrets
+/// from Zed's credential store with `${secret:NAME}`.
+///
+/// `$${` stands for a literal `${`, and any other `$` is kept as is.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct HeaderTemplate {
+    parts: Vec<Part>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Part {
+    Literal(String),
+    Env(String),
+    Secret(String),
+}
+
+impl HeaderTemplate {
+    pub fn parse(input: &str) -> Result<Self> {
+        let mut parts = Vec::new();
+        let mut literal = String::new();
+        let mut rest = input;
+        while let Some(ix) = rest.find('$') {
+            literal.push_str(&rest[..ix]);
+            rest = &rest[ix..];
+
+            if let Some(after) = rest.strip_prefix("$${") {
+                literal.push_str("${");
+                rest = after;
+            } else if let Some(after) = rest.strip_prefix("${") {
+                let end = after
+                    .find('}')
+                    .ok_or_else(|| anyhow!("unterminated placeholder in {input:?}"))?;
+                let placeholder = &after[..end];
+                rest = &after[end + 1..];
+
+                let part = match placeholder.split_once(':') {
+                    Some(("env", name)) if is_variable_name(name) => Part::Env(name.to_string()),
+                    Some(("env", name)) => bail!("invalid environment variable name {name:?}"),
+                    Some(("secret", name)) if !name.trim().is_empty() => {
+                        Part::Secret(name.to_string())
+                    }
+                    Some(("secret", _)) => bail!("missing secret name in {input:?}"),
+                    _ => bail!(
+                        "unknown placeholder ${{{placeholder}}}, expected ${{env:NAME}} or \
+                        ${{secret:NAME}}"
+                    ),
+                };
+                if !literal.is_empty() {
+                    parts.push(Part::Literal(std::mem::take(&mut literal)));
+                }
+                parts.push(part);
+            } else {
+                literal.push('$');
+                rest = &rest[1..];
+            }
+        }
+        literal.push_str(rest);
+        if !literal.is_empty() {
+            parts.push(Part::Literal(literal));
+        }
+        Ok(Self { parts })
+    }
+
+    /// The names of the secrets the template references.
+    pub fn secret_names(&self) -> impl Iterator<Item = &str> {
+        self.parts.iter().filter_map(|part| match part {
+            Part::Secret(name) => Some(name.as_str()),
+            _ => None,
+        })
+    }
+
+    /// Fills in the placeholders, failing on the first variable or secret that isn't set.
+    pub fn resolve(
+        &self,
+        env: impl Fn(&str) -> Option<String>,
+        secret: impl Fn(&str) -> Option<String>,
+    ) -> Result<String> {
+        let mut output = String::new();
+        for part in &self.parts {
+            match part {
+                Part::Literal(text) => output.push_str(text),
+                Part::Env(name) => output.push_str(
+                    &env(name).ok_or_else(|| anyhow!("environment variable {name} is not set"))?,
+                ),
+                Part::Secret(name) => output.push_str(
+                    &secret(name)
+                        .ok_or_else(|| anyhow!("secret {name} is not in the credential store"))?,
+                ),
+            }
+        }
+        Ok(output)
+    }
+}
+
+impl fmt::Display for HeaderTemplate {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for part in &self.parts {
+            match part {
+                Part::Literal(text) => write!(f, "{}", text.replace("${", "$${"))?,
+                Part::Env(name) => write!(f, "${{env:{name}}}")?,
+                Part::Secret(name) => write!(f, "${{secret:{name}}}")?,
+            }
+        }
+        Ok(())
+    }
+}
+
+/// The key a secret referenced by `${secret:NAME}` is stored under in the credential store.
+pub fn secret_credentials_url(name: &str) -> String {
+    format!("zed-context-server-secret:{name}")
+}
+
+fn is_variable_name(name: &str) -> bool {
+    name.chars()
+        .next()
+        .is_some_and(|char| char == '_' || char.is_ascii_alphabetic())
+        && name
+            .chars()
+            .all(|char| char == '_' || char.is_ascii_alphanumeric())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn resolve(input: &str) -> Result<String> {
+        HeaderTemplate::parse(input)?.resolve(
+            |name| (name == "TOKEN").then(|| "env-token".to_string()),
+            |name| (name == "github").then(|| "secret-token".to_string()),
+        )
+    }
+
+    #[test]
+    fn test_resolve() {
+        assert_eq!(resolve("Bearer abc").unwrap(), "Bearer abc");
+        assert_eq!(resolve("Bearer ${env:TOKEN}").unwrap(), "Bearer env-token");
+        assert_eq!(
+            resolve("token ${secret:github}").unwrap(),
+            "token secret-token"
+        );
+        assert_eq!(
+            resolve("${env:TOKEN}:${secret:github}").unwrap(),
+            "env-token:secret-token"
+        );
+        assert_eq!(resolve("$${env:TOKEN}").unwrap(), "${env:TOKEN}");
+        assert_eq!(resolve("$$${env:TOKEN}").unwrap(), "$${env:TOKEN}");
+        assert_eq!(resolve("cost: 5$ $TOKEN").unwrap(), "cost: 5$ $TOKEN");
+
+        assert_eq!(
+            resolve("${env:UNSET}").unwrap_err().to_string(),
+            "environment variable UNSET is not set"
+        );
+        assert_eq!(
+            resolve("${secret:gitlab}").unwrap_err().to_string(),
+            "secret gitlab is not in the credential store"
+        );
+    }
+
+    #[test]
+    fn test_parse_errors() {
+        let error = |input: &str| HeaderTemplate::parse(input).unwrap_err().to_string();
+        assert_eq!(
+            error("Bearer ${env:TOKEN"),
+            "unterminated placeholder in \"Bearer ${env:TOKEN\""
+        );
+        assert_eq!(
+            error("${TOKEN}"),
+            "unknown placeholder ${TOKEN}, expected ${env:NAME} or ${secret:NAME}"
+        );
+        assert_eq!(
+            error("${file:token}"),
+            "unknown placeholder ${file:token}, expected ${env:NAME} or ${secret:NAME}"
+        );
+        assert_eq!(
+            error("${env:1TOKEN}"),
+            "invalid environment variable name \"1TOKEN\""
+        );
+        assert_eq!(error("${secret:}"), "missing secret name in \"${secret:}\"");
+    }
+
+    #[test]
+    fn test_display_round_trips() {
+        for input in [
+            "Bearer abc",
+            "Bearer ${env:TOKEN}",
+            "${secret:github}",
+            "$${env:TOKEN} is ${env:TOKEN}",
+            "$$${secret:github}",
+            "5$ and $$",
+            "",
+        ] {
+            let template = HeaderTemplate::parse(input).unwrap();
+            assert_eq!(template.to_string(), input);
+            assert_eq!(
+                HeaderTemplate::parse(&template.to_string()).unwrap(),
+                template
+            );
+        }
+    }
+}
@@ -5,14 +5,15 @@
 //! read/write messages and the types from types.rs for serialization/deserialization
 //! of messages.
 
-use std::time::Duration;
+use std::{fmt, time::Duration};
 
-use anyhow::Result;
+use anyhow::{Context as _, Result};
 use futures::channel::oneshot;
-use gpui::AsyncApp;
+use gpui::{AsyncApp, BackgroundExecutor};
 use serde_json::Value;
 
 use crate::client::Client;
+use crate::transport::Shutdown;
 use crate::types::{self, Notification, Request};
 
 pub struct ModelContextProtocol {
@@ -24,6 +25,8 @@ impl ModelContextProtocol {
         Self { inner }
     }
 
+    /// The protocol versions Zed supports, newest first. The newest is requested, and servers
+    /// answer with it or an older version they support instead.
     fn supported_protocols() -> Vec<types::ProtocolVersion> {
         vec![
             types::ProtocolVersion(types::LATEST_PROTOCOL_VERSION.to_string()),
@@ -31,31 +34,54 @@ impl ModelContextProtocol {
         ]
     }
 
+    /// Performs the initialization handshake, failing if the server doesn't respond within
+    /// `timeout`, or with [`IncompatibleProtocol`] if it answers with a protocol version that
+    /// isn't supported.
     pub async fn initialize(
         self,
         client_info: types::Implementation,
+        capabilities: types::ClientCapabilities,
+        timeout: Duration,
     ) -> Result<InitializedContextServerProtocol> {
         let params = types::InitializeParams {
             protocol_version: types::ProtocolVersion(types::LATEST_PROTOCOL_VERSION.to_string()),
-            capabilities: types::ClientCapabilities {
-                experimental: None,
-                sampling: None,
-                roots: None,
-            },
+            capabilities,
             meta: None,
             client_info,
         };
 
-        let response: types::InitializeResponse = self
+        let response: Value = self
             .inner
-            .request(types::requests::Initialize::METHOD, params)
+            .request_with(
+                types::requests::Initialize::METHOD,
+                params,
+                None,
+                Some(timeout),
+            )
             .await?;
 
-        anyhow::ensure!(
-            Self::supported_protocols().contains(&response.protocol_version),
-            "Unsupported protocol version: {:?}",
-            response.protocol_version
-        );
+        // The version is checked before the rest of the response, which servers speaking
+        // other versions may have shaped differently.
+        let server_version = response
+            .get("protocolVersion")
+            .and_then(Value::as_str)
+            .context("the server didn't send a protocol version")?;
+        let supported_versions = Self::supported_protocols();
+        if !supported_versions
+            .iter()
+            .any(|version| version.0 == server_version)
+        {
+            return Err(IncompatibleProtocol {
+                server_version: server_version.to_string(),
+                supported_versions: supported_versions
+                    .into_iter()
+                    .map(|version| version.0)
+                    .collect(),
+            }
+            .into());
+        }
+        let response: types::InitializeResponse =
+            serde_json::from_value(response).context("invalid initialize response")?;
 
         log::trace!("mcp server info {:?}", response.server_info);
 
@@ -77,6 +103,7 @@ pub struct InitializedContextServerProtocol {
 
 #[derive(Debug, PartialEq, Clone, Copy)]
 pub enum ServerCapability {
+    Completions,
     Experimental,
     Logging,
     Prompts,
@@ -88,6 +115,7 @@ impl InitializedContextServerProtocol {
     /// Check if the server supports a specific capability
     pub fn capable(&self, capability: ServerCapability) -> bool {
         match capability {
+            ServerCapability::Completions => self.initialize.capabilities.completions.is_some(),
             ServerCapability::Experimental => self.initialize.capabilities.experimental.is_some(),
             ServerCapability::Logging => self.initialize.capabilities.logging.is_some(),
             ServerCapability::Prompts => self.initialize.capabilities.prompts.is_some(),
@@ -96,6 +124,15 @@ impl InitializedContextServerProtocol {
         }
     }
 
+    /// Returns a [`CapabilityNotSupported`] error if the server did not declare the capability
+    pub fn ensure_capable(&self, capability: ServerCapability) -> Result<()> {
+        if self.capable(capability) {
+            Ok(())
+        } else {
+            Err(CapabilityNotSupported { capability }.into())
+        }
+    }
+
     pub async fn request<T: Request>(&self, params: T::Params) -> Result<T::Response> {
         self.inner.request(T::METHOD, params).await
     }
@@ -111,10 +148,18 @@ impl InitializedContextServerProtocol {
             .await
     }
 
+    pub(crate) fn executor(&self) -> &BackgroundExecutor {
+        self.inner.executor()
+    }
+
     pub fn notify<T: Notification>(&self, params: T::Params) -> Result<()> {
         self.inner.notify(T::METHOD, params)
     }
 
+    pub async fn shutdown(&self, grace_period: Duration) -> Result<Shutdown> {
+        self.inner.shutdown(grace_period).await
+    }
+
     pub fn on_notification(
         &self,
         method: &'static str,
@@ -123,3 +168,41 @@ impl InitializedContextServerProtocol {
         self.inner.on_notification(method, f);
     }
 }
+
+/// The server answered the initialize request with a protocol version Zed doesn't support.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct IncompatibleProtocol {
+    pub server_version: String,
+    /// The versions Zed supports, newest first.
+    pub supported_versions: Vec<String>,
+}
+
+impl std::error::Error for IncompatibleProtocol {}
+
+impl fmt::Display for IncompatibleProtocol {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "Context server uses MCP protocol version {}, but only {} are supported",
+            self.server_version,
+            self.supported_versions.join(", ")
+        )
+    }
+}
+
+#[derive(Debug)]
+pub struct CapabilityNotSupported {
+    pub capability: ServerCapability,
+}
+
+impl std::error::Error for CapabilityNotSupported {}
+
+impl fmt::Display for CapabilityNotSupported {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "Context server does not support the {:?} capability",
+            self.capability
+        )
+    }
+}
@@ -0,0 +1,187 @@
+//! Limiting the resources the processes of servers started with a command may use.
+
+/// Limits on the resources a server's process may use, and the priority it runs at.
+///
+/// Limits are inherited by the processes the server starts. On Unix they are applied to
+/// each of those processes separately, while on Windows the memory limit applies to each
+/// process and the others to all of them together.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ResourceLimits {
+    /// The niceness to run the server at, from -20 (the highest priority) to 19 (the
+    /// lowest). On Windows, this selects the closest priority class.
+    pub nice: Option<i32>,
+    /// How many CPUs the server may run on. Linux and Windows only.
+    pub cpu_count: Option<usize>,
+    /// How much memory the server may allocate, in bytes. Linux and Windows only.
+    pub max_memory_bytes: Option<u64>,
+}
+
+impl ResourceLimits {
+    pub fn is_empty(&self) -> bool {
+        self == &Self::default()
+    }
+
+    /// The number of CPUs to limit the server to, or `None` if it may run on all of them.
+    #[cfg(any(target_os = "linux", windows))]
+    fn limited_cpu_count(&self) -> Option<usize> {
+        let available = std::thread::available_parallelism().map_or(usize::MAX, |n| n.get());
+        self.cpu_count
+            .map(|count| count.max(1))
+            .filter(|count| *count < available)
+    }
+
+    /// Makes the process `command` spawns run with the limits.
+    #[cfg(unix)]
+    pub(crate) fn apply_to_command(&self, command: &mut std::process::Command) {
+        use std::os::unix::process::CommandExt as _;
+
+        #[cfg(not(target_os = "linux"))]
+        self.warn_unsupported();
+        if self.is_empty() {
+            return;
+        }
+
+        let nice = self.nice.map(|nice| nice.clamp(-20, 19));
+        #[cfg(target_os = "linux")]
+        let cpu_set = self.limited_cpu_count().and_then(cpu_set);
+        #[cfg(target_os = "linux")]
+        let max_memory_bytes = self.max_memory_bytes;
+
+        // Safety: the closure only makes system calls, which is safe between fork and exec.
+        unsafe {
+            command.pre_exec(move || {
+                if let Some(nice) = nice {
+                    // Raising the priority needs privileges, so the server runs at the
+                    // default priority if that fails.
+                    libc::setpriority(libc::PRIO_PROCESS, 0, nice);
+                }
+
+                #[cfg(target_os = "linux")]
+                {
+                    if let Some(cpu_set) = &cpu_set
+                        && libc::sched_setaffinity(
+                            0,
+                            std::mem::size_of::<libc::cpu_set_t>(),
+                            cpu_set,
+                        ) != 0
+                    {
+                        return Err(std::io::Error::last_os_error());
+                    }
+
+                    // RLIMIT_AS would count the address space runtimes like V8 reserve
+                    // up front without using it, which makes them fail to start.
+                    if let Some(max_memory_bytes) = max_memory_bytes {
+                        let mut limit = libc::rlimit {
+                            rlim_cur: 0,
+                            rlim_max: 0,
+                        };
+                        if libc::getrlimit(libc::RLIMIT_DATA, &mut limit) != 0 {
+                            return Err(std::io::Error::last_os_error());
+                        }
+                        limit.rlim_cur = (max_memory_bytes as libc::rlim_t).min(limit.rlim_max);
+                        if libc::setrlimit(libc::RLIMIT_DATA, &limit) != 0 {
+                            return Err(std::io::Error::last_os_error());
+                        }
+                    }
+                }
+
+                Ok(())
+            });
+        }
+    }
+
+    /// Sets the limits of the job the server's process is assigned to.
+    #[cfg(windows)]
+    pub(crate) fn apply_to_job(
+        &self,
+        info: &mut windows::Win32::System::JobObjects::JOBOBJECT_EXTENDED_LIMIT_INFORMATION,
+    ) {
+        use windows::Win32::System::JobObjects::{
+            JOB_OBJECT_LIMIT_AFFINITY, JOB_OBJECT_LIMIT_PRIORITY_CLASS,
+            JOB_OBJECT_LIMIT_PROCESS_MEMORY,
+        };
+        use windows::Win32::System::Threading::{
+            ABOVE_NORMAL_PRIORITY_CLASS, BELOW_NORMAL_PRIORITY_CLASS, HIGH_PRIORITY_CLASS,
+            IDLE_PRIORITY_CLASS, NORMAL_PRIORITY_CLASS,
+        };
+
+        let limits = &mut info.BasicLimitInformation;
+        if let Some(nice) = self.nice {
+            let priority_class = match nice {
+                ..=-10 => HIGH_PRIORITY_CLASS,
+                -9..=-1 => ABOVE_NORMAL_PRIORITY_CLASS,
+                0 => NORMAL_PRIORITY_CLASS,
+                1..=9 => BELOW_NORMAL_PRIORITY_CLASS,
+                10.. => IDLE_PRIORITY_CLASS,
+            };
+            limits.LimitFlags |= JOB_OBJECT_LIMIT_PRIORITY_CLASS;
+            limits.PriorityClass = priority_class.0;
+        }
+        if let Some(count) = self.limited_cpu_count() {
+            limits.LimitFlags |= JOB_OBJECT_LIMIT_AFFINITY;
+            limits.Affinity = usize::MAX >> (usize::BITS as usize - count);
+        }
+        if let Some(max_memory_bytes) = self.max_memory_bytes {
+            limits.LimitFlags |= JOB_OBJECT_LIMIT_PROCESS_MEMORY;
+            info.ProcessMemoryLimit = max_memory_bytes as usize;
+        }
+    }
+
+    #[cfg(all(unix, not(target_os = "linux")))]
+    fn warn_unsupported(&self) {
+        static CPU_COUNT: std::sync::Once = std::sync::Once::new();
+        static MAX_MEMORY: std::sync::Once = std::sync::Once::new();
+
+        if self.cpu_count.is_some() {
+            CPU_COUNT.call_once(|| {
+                log::warn!(
+                    "limiting the CPUs of context servers isn't supported on this platform, \
+                     ignoring `cpu_count`"
+                )
+            });
+        }
+        if self.max_memory_bytes.is_some() {
+            MAX_MEMORY.call_once(|| {
+                log::warn!(
+                    "limiting the memory of context servers isn't supported on this platform, \
+                     ignoring `max_memory`"
+                )
+            });
+        }
+    }
+}
+
+/// The first `count` of the CPUs Zed may run on.
+#[cfg(target_os = "linux")]
+fn cpu_set(count: usize) -> Option<libc::cpu_set_t> {
+    unsafe {
+        let mut available: libc::cpu_set_t = std::mem::zeroed();
+        if libc::sched_getaffinity(0, std::mem::size_of::<libc::cpu_set_t>(), &mut available) != 0 {
+            return None;
+        }
+        let mut set: libc::cpu_set_t = std::mem::zeroed();
+        let mut remaining = count;
+        for cpu in 0..libc::CPU_SETSIZE as usize {
+            if remaining == 0 {
+                break;
+            }
+            if libc::CPU_ISSET(cpu, &available) {
+                libc::CPU_SET(cpu, &mut set);
+                remaining -= 1;
+            }
+        }
+        Some(set)
+    }
+}
+
+impl From<&settings::ContextServerResourceLimits> for ResourceLimits {
+    fn from(settings: &settings::ContextServerResourceLimits) -> Self {
+        Self {
+            nice: settings.nice,
+            cpu_count: settings.cpu_count,
+            max_memory_bytes: settings
+                .max_memory
+                .map(|mebibytes| mebibytes.saturating_mul(1024 * 1024)),
+        }
+    }
+}
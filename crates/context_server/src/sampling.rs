@@ -0,0 +1,57 @@
+//! Support for servers asking the client to run an LLM completion (`sampling/createMessage`).
+//!
+//! Zed doesn't install a delegate for the servers it starts yet, so they aren't offered
+//! sampling and their requests fail with method not found.
+
+use anyhow::{Result, anyhow};
+use gpui::{AsyncApp, Task};
+
+use crate::ContextServerId;
+use crate::types::{CreateMessageRequest, CreateMessageResult};
+
+/// Runs completions on behalf of a context server.
+///
+/// Sampling spends the user's tokens, so the embedding layer is expected to
+/// confirm each request with the user before forwarding it to a language model.
+pub trait SamplingDelegate: Send + Sync {
+    /// Asks the user whether `server_id` may run the given completion.
+    fn confirm(
+        &self,
+        server_id: ContextServerId,
+        request: &CreateMessageRequest,
+        cx: &mut AsyncApp,
+    ) -> Task<Result<bool>>;
+
+    /// Runs the completion, typically against the active language model provider.
+    fn create_message(
+        &self,
+        server_id: ContextServerId,
+        request: CreateMessageRequest,
+        cx: &mut AsyncApp,
+    ) -> Task<Result<CreateMessageResult>>;
+}
+
+/// A [`SamplingDelegate`] that rejects every request.
+pub struct DenySampling;
+
+impl SamplingDelegate for DenySampling {
+    fn confirm(
+        &self,
+        _server_id: ContextServerId,
+        _request: &CreateMessageRequest,
+        _cx: &mut AsyncApp,
+    ) -> Task<Result<bool>> {
+        Task::ready(Ok(false))
+    }
+
+    fn create_message(
+        &self,
+        server_id: ContextServerId,
+        _request: CreateMessageRequest,
+        _cx: &mut AsyncApp,
+    ) -> Task<Result<CreateMessageResult>> {
+        Task::ready(Err(anyhow!(
+            "sampling is not allowed for context server {server_id}"
+        )))
+    }
+}
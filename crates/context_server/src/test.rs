@@ -1,14 +1,33 @@
 use anyhow::Context as _;
 use collections::HashMap;
-use futures::{FutureExt, Stream, StreamExt as _, future::BoxFuture, lock::Mutex};
+use futures::{
+    FutureExt, Stream, StreamExt as _,
+    channel::oneshot,
+    future::{BoxFuture, Shared},
+    lock::Mutex,
+};
 use gpui::BackgroundExecutor;
-use std::{pin::Pin, sync::Arc};
+use serde_json::Value;
+use std::{pin::Pin, sync::Arc, time::Duration};
+use util::ResultExt as _;
 
 use crate::{
-    transport::Transport,
-    types::{Implementation, InitializeResponse, ProtocolVersion, ServerCapabilities},
+    ContextServer, ContextServerId,
+    client::{INVALID_PARAMS, RpcError},
+    transport::{ConnectionStatus, Transport},
+    types::{
+        self, Implementation, InitializeResponse, PromptMessage, PromptsCapabilities,
+        ProtocolVersion, ResourceContentsType, ResourcesCapabilities, ServerCapabilities,
+        TextResourceContents, ToolsCapabilities, requests,
+    },
 };
 
+type RequestHandler =
+    Arc<dyn Send + Sync + Fn(Value) -> BoxFuture<'static, Result<Value, RpcError>>>;
+type ToolHandler = Arc<dyn Send + Sync + Fn(Option<Value>) -> types::CallToolResponse>;
+type PromptHandler =
+    Arc<dyn Send + Sync + Fn(Option<HashMap<String, String>>) -> Vec<PromptMessage>>;
+
 pub fn create_fake_transport(
     name: impl Into<String>,
     executor: BackgroundExecutor,
@@ -35,33 +54,67 @@ fn create_initialize_response(server_name: String) -> InitializeResponse {
 }
 
 pub struct FakeTransport {
-    request_handlers: HashMap<
-        &'static str,
-        Arc<dyn Send + Sync + Fn(serde_json::Value) -> BoxFuture<'static, serde_json::Value>>,
-    >,
+    request_handlers: HashMap<&'static str, RequestHandler>,
+    /// How long to wait before answering requests of a method.
+    delays: HashMap<&'static str, Duration>,
+    /// The errors to answer requests of a method with instead of calling their handler.
+    failures: HashMap<&'static str, RpcError>,
+    notification_handlers: HashMap<&'static str, Arc<dyn Send + Sync + Fn(serde_json::Value)>>,
     tx: futures::channel::mpsc::UnboundedSender<String>,
     rx: Arc<Mutex<futures::channel::mpsc::UnboundedReceiver<String>>>,
+    stderr_tx: futures::channel::mpsc::UnboundedSender<String>,
+    stderr_rx: Arc<Mutex<futures::channel::mpsc::UnboundedReceiver<String>>>,
+    connection_status_tx: futures::channel::mpsc::UnboundedSender<ConnectionStatus>,
+    connection_status_rx: Arc<Mutex<futures::channel::mpsc::UnboundedReceiver<ConnectionStatus>>>,
+    pending_responses: Arc<parking_lot::Mutex<HashMap<u64, oneshot::Sender<serde_json::Value>>>>,
+    close_tx: parking_lot::Mutex<Option<oneshot::Sender<()>>>,
+    closed_rx: Shared<oneshot::Receiver<()>>,
     executor: BackgroundExecutor,
 }
 
 impl FakeTransport {
     pub fn new(executor: BackgroundExecutor) -> Self {
         let (tx, rx) = futures::channel::mpsc::unbounded();
+        let (stderr_tx, stderr_rx) = futures::channel::mpsc::unbounded();
+        let (connection_status_tx, connection_status_rx) = futures::channel::mpsc::unbounded();
+        let (close_tx, closed_rx) = oneshot::channel();
         Self {
             request_handlers: Default::default(),
+            delays: Default::default(),
+            failures: Default::default(),
+            notification_handlers: Default::default(),
             tx,
             rx: Arc::new(Mutex::new(rx)),
+            stderr_tx,
+            stderr_rx: Arc::new(Mutex::new(stderr_rx)),
+            connection_status_tx,
+            connection_status_rx: Arc::new(Mutex::new(connection_status_rx)),
+            pending_responses: Default::default(),
+            close_tx: parking_lot::Mutex::new(Some(close_tx)),
+            closed_rx: closed_rx.shared(),
             executor,
         }
     }
 
     pub fn on_request<T, Fut>(
-        mut self,
+        self,
         handler: impl 'static + Send + Sync + Fn(T::Params) -> Fut,
     ) -> Self
     where
         T: crate::types::Request,
         Fut: 'static + Send + Future<Output = T::Response>,
+    {
+        self.on_fallible_request::<T, _>(move |params| handler(params).map(Ok))
+    }
+
+    /// Handles requests with a handler that can answer with a JSON-RPC error.
+    pub fn on_fallible_request<T, Fut>(
+        mut self,
+        handler: impl 'static + Send + Sync + Fn(T::Params) -> Fut,
+    ) -> Self
+    where
+        T: crate::types::Request,
+        Fut: 'static + Send + Future<Output = Result<T::Response, RpcError>>,
     {
         self.request_handlers.insert(
             T::METHOD,
@@ -73,11 +126,112 @@ impl FakeTransport {
                 let params: T::Params =
                     serde_json::from_value(params).expect("Invalid parameters received");
                 let response = handler(params);
-                async move { serde_json::to_value(response.await).unwrap() }.boxed()
+                async move { Ok::<_, RpcError>(serde_json::to_value(response.await?).unwrap()) }
+                    .boxed()
             }),
         );
         self
     }
+
+    /// Waits for `delay` before answering requests of `method`, on the test's clock.
+    pub fn with_delay(mut self, method: &'static str, delay: Duration) -> Self {
+        self.delays.insert(method, delay);
+        self
+    }
+
+    /// Answers all requests of `method` with `error`, whether they have a handler or not.
+    pub fn with_failure(mut self, method: &'static str, error: RpcError) -> Self {
+        self.failures.insert(method, error);
+        self
+    }
+
+    pub fn on_notification<T>(mut self, handler: impl 'static + Send + Sync + Fn(T::Params)) -> Self
+    where
+        T: crate::types::Notification,
+    {
+        self.notification_handlers.insert(
+            T::METHOD,
+            Arc::new(move |value| {
+                let params = value
+                    .get("params")
+                    .cloned()
+                    .unwrap_or(serde_json::Value::Null);
+                let params: T::Params =
+                    serde_json::from_value(params).expect("Invalid parameters received");
+                handler(params);
+            }),
+        );
+        self
+    }
+
+    /// Sends a notification from the fake server to the connected client.
+    pub fn notify<T: crate::types::Notification>(&self, params: T::Params) {
+        let notification = serde_json::json!({
+            "jsonrpc": "2.0",
+            "method": T::METHOD,
+            "params": params,
+        });
+        self.tx
+            .unbounded_send(notification.to_string())
+            .expect("fake transport receiver dropped");
+    }
+
+    /// Writes a line to the fake server's stderr.
+    pub fn write_stderr(&self, line: &str) {
+        self.stderr_tx
+            .unbounded_send(format!("{line}\n"))
+            .expect("fake transport stderr receiver dropped");
+    }
+
+    /// Reports that the connection was lost, failing the requests waiting for a response.
+    pub fn interrupt_connection(&self) {
+        self.connection_status_tx
+            .unbounded_send(ConnectionStatus::Interrupted)
+            .expect("fake transport connection status receiver dropped");
+    }
+
+    /// Closes the connection as if the fake server went away by itself.
+    pub fn close(&self) {
+        if let Some(close_tx) = self.close_tx.lock().take() {
+            close_tx.send(()).ok();
+        }
+    }
+
+    /// Sends a request from the fake server to the connected client, resolving
+    /// to the client's raw JSON-RPC response.
+    pub fn request<T: crate::types::Request>(
+        &self,
+        id: u64,
+        params: T::Params,
+    ) -> oneshot::Receiver<serde_json::Value> {
+        let (tx, rx) = oneshot::channel();
+        self.pending_responses.lock().insert(id, tx);
+        let request = serde_json::json!({
+            "jsonrpc": "2.0",
+            "id": id,
+            "method": T::METHOD,
+            "params": params,
+        });
+        self.tx
+            .unbounded_send(request.to_string())
+            .expect("fake transport receiver dropped");
+        rx
+    }
+}
+
+impl FakeTransport {
+    /// The response to a request, or `None` if there's no handler for its method.
+    fn respond(
+        &self,
+        method: &str,
+        request: Value,
+    ) -> Option<BoxFuture<'static, Result<Value, RpcError>>> {
+        if let Some(error) = self.failures.get(method).cloned() {
+            return Some(futures::future::ready(Err(error)).boxed());
+        }
+        let handler = self.request_handlers.get(method)?;
+        Some(handler(request))
+    }
 }
 
 #[async_trait::async_trait]
@@ -88,19 +242,43 @@ impl Transport for FakeTransport {
 
             if let Some(method) = msg.get("method") {
                 let method = method.as_str().expect("Invalid method received");
-                if let Some(handler) = self.request_handlers.get(method) {
-                    let payload = handler(msg).await;
-                    let response = serde_json::json!({
-                        "jsonrpc": "2.0",
-                        "id": id,
-                        "result": payload
-                    });
-                    self.tx
-                        .unbounded_send(response.to_string())
-                        .context("sending a message")?;
+                if msg.get("id").is_none() {
+                    if let Some(handler) = self.notification_handlers.get(method) {
+                        handler(msg);
+                    }
+                } else if let Some(response) = self.respond(method, msg.clone()) {
+                    // Respond in the background so that slow handlers don't block
+                    // the messages the client sends after this one.
+                    let delay = self.delays.get(method).copied();
+                    let executor = self.executor.clone();
+                    let tx = self.tx.clone();
+                    self.executor
+                        .spawn(async move {
+                            if let Some(delay) = delay {
+                                executor.timer(delay).await;
+                            }
+                            let response = match response.await {
+                                Ok(result) => serde_json::json!({
+                                    "jsonrpc": "2.0",
+                                    "id": id,
+                                    "result": result
+                                }),
+                                Err(error) => serde_json::json!({
+                                    "jsonrpc": "2.0",
+                                    "id": id,
+                                    "error": { "code": error.code, "message": error.message }
+                                }),
+                            };
+                            tx.unbounded_send(response.to_string())
+                                .context("sending a message")
+                                .log_err();
+                        })
+                        .detach();
                 } else {
                     log::debug!("No handler registered for MCP request '{method}'");
                 }
+            } else if let Some(tx) = self.pending_responses.lock().remove(&id) {
+                tx.send(msg).ok();
             }
         }
         Ok(())
@@ -125,6 +303,212 @@ impl Transport for FakeTransport {
     }
 
     fn receive_err(&self) -> Pin<Box<dyn Stream<Item = String> + Send>> {
-        Box::pin(futures::stream::empty())
+        let stderr_rx = self.stderr_rx.clone();
+        Box::pin(futures::stream::unfold(stderr_rx, |stderr_rx| async move {
+            let line = stderr_rx.lock().await.next().await?;
+            Some((line, stderr_rx))
+        }))
+    }
+
+    async fn closed(&self) {
+        self.closed_rx.clone().await.ok();
+    }
+
+    fn connection_status(&self) -> Pin<Box<dyn Stream<Item = ConnectionStatus> + Send>> {
+        let connection_status_rx = self.connection_status_rx.clone();
+        Box::pin(futures::stream::unfold(
+            connection_status_rx,
+            |connection_status_rx| async move {
+                let status = connection_status_rx.lock().await.next().await?;
+                Some((status, connection_status_rx))
+            },
+        ))
+    }
+}
+
+/// A context server that runs in the test, answering with canned tools, prompts and
+/// resources over a [`FakeTransport`], which can also delay answers or fail requests.
+pub struct FakeContextServer {
+    name: String,
+    tools: Vec<(types::Tool, ToolHandler)>,
+    prompts: Vec<(types::Prompt, PromptHandler)>,
+    resources: Vec<(types::Resource, String)>,
+    delays: Vec<(&'static str, Duration)>,
+    failures: Vec<(&'static str, RpcError)>,
+}
+
+impl FakeContextServer {
+    pub fn new(name: impl Into<String>) -> Self {
+        Self {
+            name: name.into(),
+            tools: Vec::new(),
+            prompts: Vec::new(),
+            resources: Vec::new(),
+            delays: Vec::new(),
+            failures: Vec::new(),
+        }
+    }
+
+    /// Adds a tool, whose calls are answered by `handler` given their arguments.
+    pub fn with_tool(
+        mut self,
+        tool: types::Tool,
+        handler: impl 'static + Send + Sync + Fn(Option<Value>) -> types::CallToolResponse,
+    ) -> Self {
+        self.tools.push((tool, Arc::new(handler)));
+        self
+    }
+
+    /// Adds a prompt, whose messages are made by `handler` given its arguments.
+    pub fn with_prompt<F>(mut self, prompt: types::Prompt, handler: F) -> Self
+    where
+        F: 'static + Send + Sync + Fn(Option<HashMap<String, String>>) -> Vec<PromptMessage>,
+    {
+        self.prompts.push((prompt, Arc::new(handler)));
+        self
+    }
+
+    /// Adds a resource, which is read as `text`.
+    pub fn with_resource(mut self, resource: types::Resource, text: impl Into<String>) -> Self {
+        self.resources.push((resource, text.into()));
+        self
+    }
+
+    /// Waits for `delay` before answering requests of `method`, on the test's clock.
+    pub fn with_delay(mut self, method: &'static str, delay: Duration) -> Self {
+        self.delays.push((method, delay));
+        self
+    }
+
+    /// Answers all requests of `method` with `error`.
+    pub fn with_failure(mut self, method: &'static str, error: RpcError) -> Self {
+        self.failures.push((method, error));
+        self
+    }
+
+    /// Creates the server, along with the transport it's connected over, which sends
+    /// notifications and requests from the server. The server declares the capabilities of
+    /// the tools, prompts and resources it was given.
+    pub fn build(self, executor: BackgroundExecutor) -> (ContextServer, Arc<FakeTransport>) {
+        let id = ContextServerId(self.name.as_str().into());
+        let capabilities = ServerCapabilities {
+            tools: (!self.tools.is_empty()).then_some(ToolsCapabilities {
+                list_changed: Some(true),
+            }),
+            prompts: (!self.prompts.is_empty()).then_some(PromptsCapabilities {
+                list_changed: Some(true),
+            }),
+            resources: (!self.resources.is_empty()).then_some(ResourcesCapabilities {
+                subscribe: None,
+                list_changed: Some(true),
+            }),
+            ..ServerCapabilities::default()
+        };
+        let name = self.name;
+        let tools = Arc::new(self.tools);
+        let prompts = Arc::new(self.prompts);
+        let resources = Arc::new(self.resources);
+
+        let mut transport = FakeTransport::new(executor)
+            .on_request::<requests::Initialize, _>(move |_| {
+                let response = InitializeResponse {
+                    capabilities: capabilities.clone(),
+                    ..create_initialize_response(name.clone())
+                };
+                async move { response }
+            })
+            .on_request::<requests::ListTools, _>({
+                let tools = tools.clone();
+                move |_| {
+                    let tools = tools.iter().map(|(tool, _)| tool.clone()).collect();
+                    async move {
+                        types::ListToolsResponse {
+                            tools,
+                            next_cursor: None,
+                            meta: None,
+                        }
+                    }
+                }
+            })
+            .on_fallible_request::<requests::CallTool, _>(move |params| {
+                let response = tools
+                    .iter()
+                    .find(|(tool, _)| tool.name == params.name)
+                    .map(|(_, handler)| handler(params.arguments))
+                    .ok_or_else(|| invalid_params(format!("unknown tool {}", params.name)));
+                async move { response }
+            })
+            .on_request::<requests::PromptsList, _>({
+                let prompts = prompts.clone();
+                move |_| {
+                    let prompts = prompts.iter().map(|(prompt, _)| prompt.clone()).collect();
+                    async move {
+                        types::PromptsListResponse {
+                            prompts,
+                            next_cursor: None,
+                            meta: None,
+                        }
+                    }
+                }
+            })
+            .on_fallible_request::<requests::PromptsGet, _>(move |params| {
+                let response = prompts
+                    .iter()
+                    .find(|(prompt, _)| prompt.name == params.name)
+                    .map(|(prompt, handler)| types::PromptsGetResponse {
+                        description: prompt.description.clone(),
+                        messages: handler(params.arguments),
+                        meta: None,
+                    })
+                    .ok_or_else(|| invalid_params(format!("unknown prompt {}", params.name)));
+                async move { response }
+            })
+            .on_request::<requests::ResourcesList, _>({
+                let resources = resources.clone();
+                move |_| {
+                    let resources = resources
+                        .iter()
+                        .map(|(resource, _)| resource.clone())
+                        .collect();
+                    async move {
+                        types::ResourcesListResponse {
+                            resources,
+                            next_cursor: None,
+                            meta: None,
+                        }
+                    }
+                }
+            })
+            .on_fallible_request::<requests::ResourcesRead, _>(move |params| {
+                let response = resources
+                    .iter()
+                    .find(|(resource, _)| resource.uri == params.uri)
+                    .map(|(resource, text)| types::ResourcesReadResponse {
+                        contents: vec![ResourceContentsType::Text(TextResourceContents {
+                            uri: resource.uri.clone(),
+                            mime_type: resource.mime_type.clone(),
+                            text: text.clone(),
+                        })],
+                        meta: None,
+                    })
+                    .ok_or_else(|| invalid_params(format!("unknown resource {}", params.uri)));
+                async move { response }
+            });
+        for (method, delay) in self.delays {
+            transport = transport.with_delay(method, delay);
+        }
+        for (method, error) in self.failures {
+            transport = transport.with_failure(method, error);
+        }
+
+        let transport = Arc::new(transport);
+        (ContextServer::new(id, transport.clone()), transport)
+    }
+}
+
+fn invalid_params(message: String) -> RpcError {
+    RpcError {
+        code: INVALID_PARAMS,
+        message,
     }
 }
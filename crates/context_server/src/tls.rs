@@ -0,0 +1,285 @@
+//! TLS configuration for remote context servers.
+
+use std::path::Path;
+use std::sync::Arc;
+
+use anyhow::{Context as _, Result, bail};
+use rustls::client::danger::{HandshakeSignatureValid, ServerCertVerified, ServerCertVerifier};
+use rustls::pki_types::pem::PemObject as _;
+use rustls::pki_types::{CertificateDer, PrivateKeyDer, ServerName, UnixTime};
+use rustls::{ClientConfig, DigitallySignedStruct, SignatureScheme};
+use rustls_platform_verifier::Verifier;
+use settings::ContextServerTlsSettings;
+
+use crate::executable::expand_home;
+
+/// Builds the TLS configuration for a remote server, which is the one Zed's HTTP client uses
+/// unless the server has TLS settings.
+pub fn client_config(settings: Option<&ContextServerTlsSettings>) -> Result<Arc<ClientConfig>> {
+    // This also installs the default crypto provider the builders below rely on.
+    let default_config = http_client_tls::tls_config();
+    let Some(settings) = settings else {
+        return Ok(Arc::new(default_config));
+    };
+
+    let builder = ClientConfig::builder().dangerous();
+    let builder = if settings.accept_invalid_certs == Some(true) {
+        log::warn!(
+            "accepting invalid TLS certificates from a context server, so its connection can \
+            be intercepted. Only use accept_invalid_certs for testing."
+        );
+        builder.with_custom_certificate_verifier(Arc::new(AcceptAnyCertificate))
+    } else {
+        let extra_roots = match &settings.ca_cert_path {
+            Some(path) => load_certificates(&expand_home(Path::new(path)))
+                .context("failed to load CA certificates")?,
+            None => Vec::new(),
+        };
+        let verifier = Verifier::new_with_extra_roots(extra_roots)?;
+        builder.with_custom_certificate_verifier(Arc::new(verifier))
+    };
+
+    let config = match (&settings.client_cert_path, &settings.client_key_path) {
+        (Some(cert_path), Some(key_path)) => {
+            let certificates = load_certificates(&expand_home(Path::new(cert_path)))
+                .context("failed to load client certificate")?;
+            let key = load_private_key(&expand_home(Path::new(key_path)))
+                .context("failed to load client key")?;
+            builder
+                .with_client_auth_cert(certificates, key)
+                .context("invalid client certificate or key")?
+        }
+        (None, None) => builder.with_no_client_auth(),
+        (Some(_), None) => bail!("client_cert_path is set without client_key_path"),
+        (None, Some(_)) => bail!("client_key_path is set without client_cert_path"),
+    };
+    Ok(Arc::new(config))
+}
+
+fn load_certificates(path: &Path) -> Result<Vec<CertificateDer<'static>>> {
+    let certificates = CertificateDer::pem_file_iter(path)
+        .and_then(|certificates| certificates.collect::<Result<Vec<_>, _>>())
+        .with_context(|| format!("could not read {}", path.display()))?;
+    if certificates.is_empty() {
+        bail!("{} contains no PEM certificates", path.display());
+    }
+    Ok(certificates)
+}
+
+fn load_private_key(path: &Path) -> Result<PrivateKeyDer<'static>> {
+    PrivateKeyDer::from_pem_file(path).with_context(|| format!("could not read {}", path.display()))
+}
+
+#[derive(Debug)]
+struct AcceptAnyCertificate;
+
+impl ServerCertVerifier for AcceptAnyCertificate {
+    fn verify_server_cert(
+        &self,
+        _end_entity: &CertificateDer<'_>,
+        _intermediates: &[CertificateDer<'_>],
+        _server_name: &ServerName<'_>,
+        _ocsp_response: &[u8],
+        _now: UnixTime,
+    ) -> Result<ServerCertVerified, rustls::Error> {
+        Ok(ServerCertVerified::assertion())
+    }
+
+    fn verify_tls12_signature(
+        &self,
+        _message: &[u8],
+        _certificate: &CertificateDer<'_>,
+        _signature: &DigitallySignedStruct,
+    ) -> Result<HandshakeSignatureValid, rustls::Error> {
+        Ok(HandshakeSignatureValid::assertion())
+    }
+
+    fn verify_tls13_signature(
+        &self,
+        _message: &[u8],
+        _certificate: &CertificateDer<'_>,
+        _signature: &DigitallySignedStruct,
+    ) -> Result<HandshakeSignatureValid, rustls::Error> {
+        Ok(HandshakeSignatureValid::assertion())
+    }
+
+    fn supported_verify_schemes(&self) -> Vec<SignatureScheme> {
+        rustls::crypto::CryptoProvider::get_default()
+            .map(|provider| {
+                provider
+                    .signature_verification_algorithms
+                    .supported_schemes()
+            })
+            .unwrap_or_default()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::transport::{TcpTransport, Transport as _};
+    use futures::StreamExt as _;
+    use rustls::ServerConfig;
+    use rustls::server::WebPkiClientVerifier;
+    use tokio::io::{AsyncBufReadExt as _, AsyncWriteExt as _, BufReader};
+    use tokio::net::TcpListener;
+    use tokio_rustls::TlsAcceptor;
+
+    fn test_data(name: &str) -> String {
+        format!("{}/test_data/tls/{name}", env!("CARGO_MANIFEST_DIR"))
+    }
+
+    fn trusting_test_ca() -> ContextServerTlsSettings {
+        ContextServerTlsSettings {
+            ca_cert_path: Some(test_data("ca.pem")),
+            ..Default::default()
+        }
+    }
+
+    /// Serves TLS with a certificate for 127.0.0.1 signed by the test CA, echoing every line
+    /// back. With `require_client_cert`, clients must present a certificate from the test CA.
+    fn start_echo_server(runtime: &tokio::runtime::Runtime, require_client_cert: bool) -> u16 {
+        http_client_tls::tls_config();
+        let certificates = load_certificates(Path::new(&test_data("server.pem"))).unwrap();
+        let key = load_private_key(Path::new(&test_data("server.key"))).unwrap();
+        let builder = ServerConfig::builder();
+        let builder = if require_client_cert {
+            let mut roots = rustls::RootCertStore::empty();
+            for certificate in load_certificates(Path::new(&test_data("ca.pem"))).unwrap() {
+                roots.add(certificate).unwrap();
+            }
+            let verifier = WebPkiClientVerifier::builder(Arc::new(roots))
+                .build()
+                .unwrap();
+            builder.with_client_cert_verifier(verifier)
+        } else {
+            builder.with_no_client_auth()
+        };
+        let config = builder.with_single_cert(certificates, key).unwrap();
+        let acceptor = TlsAcceptor::from(Arc::new(config));
+
+        let listener = runtime.block_on(TcpListener::bind("127.0.0.1:0")).unwrap();
+        let port = listener.local_addr().unwrap().port();
+        runtime.spawn(async move {
+            while let Ok((stream, _)) = listener.accept().await {
+                let acceptor = acceptor.clone();
+                tokio::spawn(async move {
+                    let Ok(stream) = acceptor.accept(stream).await else {
+                        return;
+                    };
+                    let (reader, mut writer) = tokio::io::split(stream);
+                    let mut lines = BufReader::new(reader).lines();
+                    while let Ok(Some(line)) = lines.next_line().await {
+                        if writer
+                            .write_all(format!("{line}\n").as_bytes())
+                            .await
+                            .is_err()
+                        {
+                            break;
+                        }
+                    }
+                });
+            }
+        });
+        port
+    }
+
+    /// Sends a line over TLS, returning the echoed line or the connection error.
+    fn echo(
+        runtime: &tokio::runtime::Runtime,
+        port: u16,
+        settings: Option<&ContextServerTlsSettings>,
+    ) -> Result<String, String> {
+        let config = client_config(settings).unwrap();
+        let transport = TcpTransport::new(
+            "127.0.0.1".into(),
+            port,
+            Some(config),
+            runtime.handle().clone(),
+        );
+        runtime.block_on(async {
+            let mut responses = transport.receive();
+            let mut errors = transport.receive_err();
+            transport.send("hello".into()).await.ok();
+            match responses.next().await {
+                Some(response) => Ok(response),
+                None => Err(errors.next().await.unwrap_or_default()),
+            }
+        })
+    }
+
+    #[test]
+    fn test_private_ca() {
+        let runtime = tokio::runtime::Runtime::new().unwrap();
+        let port = start_echo_server(&runtime, false);
+
+        assert_eq!(
+            echo(&runtime, port, Some(&trusting_test_ca())),
+            Ok("hello".into())
+        );
+
+        let error = echo(&runtime, port, None).unwrap_err();
+        assert!(error.contains("invalid peer certificate"), "{error}");
+
+        let accept_invalid_certs = ContextServerTlsSettings {
+            accept_invalid_certs: Some(true),
+            ..Default::default()
+        };
+        assert_eq!(
+            echo(&runtime, port, Some(&accept_invalid_certs)),
+            Ok("hello".into())
+        );
+    }
+
+    #[test]
+    fn test_client_certificate() {
+        let runtime = tokio::runtime::Runtime::new().unwrap();
+        let port = start_echo_server(&runtime, true);
+
+        let with_client_cert = ContextServerTlsSettings {
+            client_cert_path: Some(test_data("client.pem")),
+            client_key_path: Some(test_data("client.key")),
+            ..trusting_test_ca()
+        };
+        assert_eq!(
+            echo(&runtime, port, Some(&with_client_cert)),
+            Ok("hello".into())
+        );
+        assert!(echo(&runtime, port, Some(&trusting_test_ca())).is_err());
+    }
+
+    #[test]
+    fn test_loading_errors() {
+        let error = |settings: ContextServerTlsSettings| {
+            format!("{:#}", client_config(Some(&settings)).unwrap_err())
+        };
+
+        let missing = test_data("missing.pem");
+        let message = error(ContextServerTlsSettings {
+            ca_cert_path: Some(missing.clone()),
+            ..Default::default()
+        });
+        assert!(
+            message.starts_with(&format!(
+                "failed to load CA certificates: could not read {missing}"
+            )),
+            "{message}"
+        );
+
+        let key = test_data("client.key");
+        let message = error(ContextServerTlsSettings {
+            ca_cert_path: Some(key.clone()),
+            ..Default::default()
+        });
+        assert_eq!(
+            message,
+            format!("failed to load CA certificates: {key} contains no PEM certificates")
+        );
+
+        let message = error(ContextServerTlsSettings {
+            client_cert_path: Some(test_data("client.pem")),
+            ..Default::default()
+        });
+        assert_eq!(message, "client_cert_path is set without client_key_path");
+    }
+}
@@ -0,0 +1,114 @@
+//! Asking the user before calling tools that may change things, based on their annotations.
+
+use std::fmt;
+
+use anyhow::Result;
+use gpui::Task;
+
+use crate::ContextServerId;
+use crate::types::{CallToolParams, ToolAnnotations};
+
+/// Which tool calls need to be approved before they are sent to the server.
+///
+/// Annotations are hints the server gives about its tools, so they can't be trusted to keep
+/// an untrusted server from doing harm. They help deciding when to bother the user.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum ToolApprovalPolicy {
+    /// No calls need approval.
+    Never,
+    /// Calls to tools marked destructive need approval, unless they're also marked
+    /// read-only.
+    #[default]
+    Destructive,
+    /// Calls to tools that aren't marked read-only need approval, including tools without
+    /// annotations.
+    UnlessReadOnly,
+    /// All calls need approval.
+    Always,
+}
+
+impl ToolApprovalPolicy {
+    /// Whether calls to a tool with the given annotations need approval. Tools that weren't
+    /// listed have no annotations.
+    pub fn requires_approval(self, annotations: Option<&ToolAnnotations>) -> bool {
+        let read_only =
+            annotations.and_then(|annotations| annotations.read_only_hint) == Some(true);
+        match self {
+            Self::Never => false,
+            // The destructive hint is meaningless for read-only tools.
+            Self::Destructive => {
+                !read_only
+                    && annotations.and_then(|annotations| annotations.destructive_hint)
+                        == Some(true)
+            }
+            Self::UnlessReadOnly => !read_only,
+            Self::Always => true,
+        }
+    }
+}
+
+/// Decides whether tool calls that need approval may be sent to the server, typically by
+/// asking the user.
+pub trait ToolApprovalDelegate: Send + Sync {
+    /// Asks whether `server_id` may be sent the given call. `annotations` are those the tool
+    /// had when the tools were last listed.
+    fn approve(
+        &self,
+        server_id: ContextServerId,
+        params: &CallToolParams,
+        annotations: Option<&ToolAnnotations>,
+    ) -> Task<Result<bool>>;
+}
+
+/// A tool call that needed approval was rejected, so it wasn't sent to the server.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RejectedByUser {
+    pub tool: String,
+}
+
+impl std::error::Error for RejectedByUser {}
+
+impl fmt::Display for RejectedByUser {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "the user rejected the call to tool {:?}", self.tool)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_requires_approval() {
+        let annotations = |read_only_hint, destructive_hint| ToolAnnotations {
+            read_only_hint,
+            destructive_hint,
+            ..Default::default()
+        };
+        let unannotated = None;
+        let empty = Some(annotations(None, None));
+        let read_only = Some(annotations(Some(true), None));
+        let writes = Some(annotations(Some(false), Some(false)));
+        let destructive = Some(annotations(None, Some(true)));
+        let read_only_and_destructive = Some(annotations(Some(true), Some(true)));
+
+        use ToolApprovalPolicy::*;
+        for (policy, expected) in [
+            (Never, [false, false, false, false, false, false]),
+            (Destructive, [false, false, false, false, true, false]),
+            (UnlessReadOnly, [true, true, false, true, true, false]),
+            (Always, [true, true, true, true, true, true]),
+        ] {
+            let actual = [
+                &unannotated,
+                &empty,
+                &read_only,
+                &writes,
+                &destructive,
+                &read_only_and_destructive,
+            ]
+            .map(|annotations| policy.requires_approval(annotations.as_ref()));
+            assert_eq!(actual, expected, "{policy:?}");
+        }
+    }
+}
@@ -0,0 +1,133 @@
+//! Exposing only some of a server's tools, as configured in its settings.
+
+use std::fmt;
+
+use anyhow::{Context as _, Result};
+use globset::{Glob, GlobSet, GlobSetBuilder};
+
+/// Which of a server's tools are listed and may be called, by glob patterns matched against
+/// their names.
+///
+/// Tools are exposed if they match one of the `allow` patterns, or no `allow` patterns are
+/// given, and don't match any of the `deny` patterns. A tool matching both is denied.
+#[derive(Debug, Clone)]
+pub struct ToolFilter {
+    allow: Option<GlobSet>,
+    deny: GlobSet,
+}
+
+impl ToolFilter {
+    pub fn new(allow: Option<&[String]>, deny: &[String]) -> Result<Self> {
+        Ok(Self {
+            allow: allow.map(glob_set).transpose()?,
+            deny: glob_set(deny)?,
+        })
+    }
+
+    /// Whether the tool with the given name is exposed.
+    pub fn allows(&self, tool: &str) -> bool {
+        self.allow.as_ref().is_none_or(|allow| allow.is_match(tool)) && !self.deny.is_match(tool)
+    }
+}
+
+fn glob_set(patterns: &[String]) -> Result<GlobSet> {
+    let mut builder = GlobSetBuilder::new();
+    for pattern in patterns {
+        builder
+            .add(Glob::new(pattern).with_context(|| format!("invalid tool pattern {pattern:?}"))?);
+    }
+    Ok(builder.build()?)
+}
+
+impl TryFrom<&settings::ContextServerToolsSettings> for ToolFilter {
+    type Error = anyhow::Error;
+
+    fn try_from(settings: &settings::ContextServerToolsSettings) -> Result<Self> {
+        Self::new(
+            settings.allow.as_deref(),
+            settings.deny.as_deref().unwrap_or_default(),
+        )
+    }
+}
+
+/// A tool call was refused because the server's settings don't expose the tool, so it
+/// wasn't sent to the server.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PolicyDenied {
+    pub tool: String,
+}
+
+impl std::error::Error for PolicyDenied {}
+
+impl fmt::Display for PolicyDenied {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "tool {:?} is not allowed by the context server's settings",
+            self.tool
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn filter(allow: Option<&[&str]>, deny: &[&str]) -> ToolFilter {
+        let strings =
+            |patterns: &[&str]| patterns.iter().map(|p| p.to_string()).collect::<Vec<_>>();
+        ToolFilter::new(allow.map(strings).as_deref(), &strings(deny)).unwrap()
+    }
+
+    fn allowed(filter: &ToolFilter) -> Vec<&'static str> {
+        [
+            "search_issues",
+            "search_code",
+            "get_issue",
+            "delete_repo",
+            "delete_branch",
+        ]
+        .into_iter()
+        .filter(|tool| filter.allows(tool))
+        .collect()
+    }
+
+    #[test]
+    fn test_tool_filter() {
+        assert_eq!(
+            allowed(&filter(None, &[])),
+            [
+                "search_issues",
+                "search_code",
+                "get_issue",
+                "delete_repo",
+                "delete_branch"
+            ]
+        );
+
+        // Allow only.
+        assert_eq!(
+            allowed(&filter(Some(&["search_*", "get_issue"]), &[])),
+            ["search_issues", "search_code", "get_issue"]
+        );
+        assert!(allowed(&filter(Some(&[]), &[])).is_empty());
+
+        // Deny only.
+        assert_eq!(
+            allowed(&filter(None, &["delete_*"])),
+            ["search_issues", "search_code", "get_issue"]
+        );
+
+        // Denying takes precedence over allowing.
+        assert_eq!(
+            allowed(&filter(
+                Some(&["search_*", "delete_*"]),
+                &["delete_repo", "*_code"]
+            )),
+            ["search_issues", "delete_branch"]
+        );
+
+        let error = ToolFilter::new(Some(&["search_[".to_string()]), &[]).unwrap_err();
+        assert_eq!(error.to_string(), "invalid tool pattern \"search_[\"");
+    }
+}
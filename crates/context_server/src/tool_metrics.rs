@@ -0,0 +1,202 @@
+//! Statistics about the tool calls made to a server, for diagnosing slow or failing tools.
+
+use std::time::{Duration, SystemTime};
+
+use collections::{BTreeMap, HashMap};
+
+/// How many of the latest calls to a tool its latency percentiles are computed from.
+pub const LATENCY_WINDOW: usize = 100;
+
+/// Statistics about the calls to a tool since the server started.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ToolMetrics {
+    pub calls: u64,
+    /// How many calls failed, either with an error or a result marked as an error.
+    pub errors: u64,
+    /// How many calls were refused without being sent, because the server's settings don't
+    /// expose the tool. They don't count as calls.
+    pub denied: u64,
+    /// The median latency of the last [`LATENCY_WINDOW`] calls.
+    pub p50_latency: Option<Duration>,
+    /// The 95th percentile latency of the last [`LATENCY_WINDOW`] calls.
+    pub p95_latency: Option<Duration>,
+    pub last_error: Option<ToolError>,
+}
+
+/// The latest failed call to a tool.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ToolError {
+    pub at: SystemTime,
+    pub message: String,
+}
+
+/// Records the calls to each of a server's tools.
+#[derive(Default)]
+pub(crate) struct ToolMetricsRecorder {
+    tools: HashMap<String, ToolStats>,
+}
+
+#[derive(Default)]
+struct ToolStats {
+    calls: u64,
+    errors: u64,
+    denied: u64,
+    latencies: LatencyWindow,
+    last_error: Option<ToolError>,
+}
+
+/// The latencies of the latest calls, in a ring buffer so that recording doesn't allocate.
+struct LatencyWindow {
+    samples: [Duration; LATENCY_WINDOW],
+    len: usize,
+    next: usize,
+}
+
+impl ToolMetricsRecorder {
+    pub fn record(&mut self, tool: &str, latency: Duration, error: Option<String>) {
+        let stats = self.stats(tool);
+        stats.calls += 1;
+        stats.latencies.push(latency);
+        if let Some(message) = error {
+            stats.errors += 1;
+            stats.last_error = Some(ToolError {
+                at: SystemTime::now(),
+                message,
+            });
+        }
+    }
+
+    pub fn record_denied(&mut self, tool: &str) {
+        self.stats(tool).denied += 1;
+    }
+
+    fn stats(&mut self, tool: &str) -> &mut ToolStats {
+        // Look the tool up by reference, so that only its first call allocates.
+        if !self.tools.contains_key(tool) {
+            self.tools.insert(tool.to_string(), ToolStats::default());
+        }
+        self.tools.get_mut(tool).unwrap()
+    }
+
+    pub fn metrics(&self) -> BTreeMap<String, ToolMetrics> {
+        self.tools
+            .iter()
+            .map(|(tool, stats)| {
+                let mut latencies = stats.latencies.samples().to_vec();
+                latencies.sort_unstable();
+                let metrics = ToolMetrics {
+                    calls: stats.calls,
+                    errors: stats.errors,
+                    denied: stats.denied,
+                    p50_latency: percentile(&latencies, 50),
+                    p95_latency: percentile(&latencies, 95),
+                    last_error: stats.last_error.clone(),
+                };
+                (tool.clone(), metrics)
+            })
+            .collect()
+    }
+
+    pub fn clear(&mut self) {
+        self.tools.clear();
+    }
+}
+
+impl Default for LatencyWindow {
+    fn default() -> Self {
+        Self {
+            samples: [Duration::ZERO; LATENCY_WINDOW],
+            len: 0,
+            next: 0,
+        }
+    }
+}
+
+impl LatencyWindow {
+    fn push(&mut self, latency: Duration) {
+        self.samples[self.next] = latency;
+        self.next = (self.next + 1) % LATENCY_WINDOW;
+        self.len = (self.len + 1).min(LATENCY_WINDOW);
+    }
+
+    fn samples(&self) -> &[Duration] {
+        &self.samples[..self.len]
+    }
+}
+
+/// The nearest-rank percentile of sorted samples: the smallest sample that at least
+/// `percent`% of the samples are less than or equal to.
+fn percentile(sorted: &[Duration], percent: usize) -> Option<Duration> {
+    if sorted.is_empty() {
+        return None;
+    }
+    let rank = (sorted.len() * percent).div_ceil(100).max(1);
+    Some(sorted[rank - 1])
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn millis(values: impl IntoIterator<Item = u64>) -> Vec<Duration> {
+        values.into_iter().map(Duration::from_millis).collect()
+    }
+
+    #[test]
+    fn test_percentile() {
+        assert_eq!(percentile(&[], 50), None);
+
+        let one = millis([7]);
+        assert_eq!(percentile(&one, 50), Some(Duration::from_millis(7)));
+        assert_eq!(percentile(&one, 95), Some(Duration::from_millis(7)));
+
+        let hundred = millis(1..=100);
+        assert_eq!(percentile(&hundred, 50), Some(Duration::from_millis(50)));
+        assert_eq!(percentile(&hundred, 95), Some(Duration::from_millis(95)));
+
+        let ten = millis(1..=10);
+        assert_eq!(percentile(&ten, 50), Some(Duration::from_millis(5)));
+        assert_eq!(percentile(&ten, 95), Some(Duration::from_millis(10)));
+    }
+
+    #[test]
+    fn test_tool_metrics() {
+        let mut recorder = ToolMetricsRecorder::default();
+        // Recorded out of order, with more calls than fit in the window.
+        for ms in (1..=150).rev() {
+            recorder.record("search", Duration::from_millis(ms), None);
+        }
+        recorder.record("fetch", Duration::from_millis(10), None);
+        recorder.record("fetch", Duration::from_millis(30), Some("not found".into()));
+        recorder.record_denied("delete");
+
+        let metrics = recorder.metrics();
+        assert_eq!(
+            metrics.keys().collect::<Vec<_>>(),
+            vec!["delete", "fetch", "search"]
+        );
+
+        let search = &metrics["search"];
+        assert_eq!(search.calls, 150);
+        assert_eq!(search.errors, 0);
+        // Only the last 100 calls, which took 1 to 100ms, are in the window.
+        assert_eq!(search.p50_latency, Some(Duration::from_millis(50)));
+        assert_eq!(search.p95_latency, Some(Duration::from_millis(95)));
+        assert_eq!(search.last_error, None);
+
+        let fetch = &metrics["fetch"];
+        assert_eq!(fetch.calls, 2);
+        assert_eq!(fetch.errors, 1);
+        assert_eq!(fetch.p50_latency, Some(Duration::from_millis(10)));
+        assert_eq!(fetch.p95_latency, Some(Duration::from_millis(30)));
+        assert_eq!(fetch.last_error.as_ref().unwrap().message, "not found");
+
+        let delete = &metrics["delete"];
+        assert_eq!(delete.calls, 0);
+        assert_eq!(delete.denied, 1);
+        assert_eq!(delete.p50_latency, None);
+
+        recorder.clear();
+        assert!(recorder.metrics().is_empty());
+    }
+}
@@ -0,0 +1,254 @@
+//! The results of tool calls, converted from the protocol's response types.
+
+use url::Url;
+
+use crate::types::{self, ResourceContentsType, ToolResponseContent};
+
+/// What a tool returned.
+#[derive(Debug, Default, Clone, PartialEq)]
+pub struct ToolResult {
+    pub content: Vec<ToolContent>,
+    /// Whether the tool failed. The content then describes the failure.
+    pub is_error: bool,
+    /// JSON the tool returned alongside its content, typically matching its output schema.
+    pub structured_content: Option<serde_json::Value>,
+    /// The ways the structured content doesn't match the tool's output schema, which are
+    /// reported instead of failing the call.
+    pub schema_mismatches: Vec<String>,
+}
+
+impl ToolResult {
+    /// The text content of the result, concatenated.
+    pub fn text(&self) -> String {
+        let mut text = String::new();
+        for content in &self.content {
+            if let ToolContent::Text(chunk) = content {
+                text.push_str(chunk);
+            }
+        }
+        text
+    }
+}
+
+/// A piece of content a tool returned.
+#[derive(Debug, Clone, PartialEq)]
+pub enum ToolContent {
+    /// Text, including a description of content of a type that isn't supported.
+    Text(String),
+    /// A base64-encoded image.
+    Image { data: String, mime_type: String },
+    /// Base64-encoded audio.
+    Audio { data: String, mime_type: String },
+    /// A resource that can be read with `resources/read`.
+    ResourceLink { uri: Url, name: String },
+    /// A resource whose contents are part of the result.
+    EmbeddedResource {
+        uri: Url,
+        mime_type: Option<String>,
+        contents: EmbeddedContents,
+    },
+}
+
+/// The contents of an embedded resource.
+#[derive(Debug, Clone, PartialEq)]
+pub enum EmbeddedContents {
+    Text(String),
+    /// Base64-encoded binary data.
+    Blob(String),
+}
+
+impl From<types::CallToolResponse> for ToolResult {
+    fn from(response: types::CallToolResponse) -> Self {
+        Self {
+            content: response.content.into_iter().map(Into::into).collect(),
+            is_error: response.is_error.unwrap_or(false),
+            structured_content: response.structured_content,
+            schema_mismatches: Vec::new(),
+        }
+    }
+}
+
+impl From<ToolResponseContent> for ToolContent {
+    fn from(content: ToolResponseContent) -> Self {
+        match content {
+            ToolResponseContent::Text { text } => ToolContent::Text(text),
+            ToolResponseContent::Image { data, mime_type } => {
+                ToolContent::Image { data, mime_type }
+            }
+            ToolResponseContent::Audio { data, mime_type } => {
+                ToolContent::Audio { data, mime_type }
+            }
+            ToolResponseContent::ResourceLink { uri, name, .. } => {
+                ToolContent::ResourceLink { uri, name }
+            }
+            ToolResponseContent::Resource { resource } => match resource {
+                ResourceContentsType::Text(resource) => ToolContent::EmbeddedResource {
+                    uri: resource.uri,
+                    mime_type: resource.mime_type,
+                    contents: EmbeddedContents::Text(resource.text),
+                },
+                ResourceContentsType::Blob(resource) => ToolContent::EmbeddedResource {
+                    uri: resource.uri,
+                    mime_type: resource.mime_type,
+                    contents: EmbeddedContents::Blob(resource.blob),
+                },
+            },
+            ToolResponseContent::Unknown(content) => {
+                match content.get("type").and_then(|kind| kind.as_str()) {
+                    Some(kind) => {
+                        log::warn!("tool returned unsupported content: {content}");
+                        ToolContent::Text(format!("[unsupported {kind} content]"))
+                    }
+                    None => {
+                        log::warn!("tool returned content without a type: {content}");
+                        ToolContent::Text("[unsupported content]".to_string())
+                    }
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn convert(content: serde_json::Value) -> ToolContent {
+        let response: types::CallToolResponse =
+            serde_json::from_value(json!({ "content": [content] })).unwrap();
+        let mut result = ToolResult::from(response);
+        assert_eq!(result.content.len(), 1);
+        result.content.remove(0)
+    }
+
+    #[test]
+    fn test_text() {
+        assert_eq!(
+            convert(json!({ "type": "text", "text": "hello" })),
+            ToolContent::Text("hello".to_string())
+        );
+    }
+
+    #[test]
+    fn test_image_and_audio() {
+        assert_eq!(
+            convert(json!({ "type": "image", "data": "iVBO", "mimeType": "image/png" })),
+            ToolContent::Image {
+                data: "iVBO".to_string(),
+                mime_type: "image/png".to_string(),
+            }
+        );
+        assert_eq!(
+            convert(json!({ "type": "audio", "data": "UklG", "mimeType": "audio/wav" })),
+            ToolContent::Audio {
+                data: "UklG".to_string(),
+                mime_type: "audio/wav".to_string(),
+            }
+        );
+    }
+
+    #[test]
+    fn test_resource_link() {
+        assert_eq!(
+            convert(json!({
+                "type": "resource_link",
+                "uri": "file:///project/src/main.rs",
+                "name": "main.rs",
+                "mimeType": "text/x-rust",
+            })),
+            ToolContent::ResourceLink {
+                uri: Url::parse("file:///project/src/main.rs").unwrap(),
+                name: "main.rs".to_string(),
+            }
+        );
+    }
+
+    #[test]
+    fn test_embedded_resource() {
+        assert_eq!(
+            convert(json!({
+                "type": "resource",
+                "resource": {
+                    "uri": "file:///notes.md",
+                    "mimeType": "text/markdown",
+                    "text": "# Notes",
+                },
+            })),
+            ToolContent::EmbeddedResource {
+                uri: Url::parse("file:///notes.md").unwrap(),
+                mime_type: Some("text/markdown".to_string()),
+                contents: EmbeddedContents::Text("# Notes".to_string()),
+            }
+        );
+        assert_eq!(
+            convert(json!({
+                "type": "resource",
+                "resource": { "uri": "file:///logo.png", "blob": "iVBO" },
+            })),
+            ToolContent::EmbeddedResource {
+                uri: Url::parse("file:///logo.png").unwrap(),
+                mime_type: None,
+                contents: EmbeddedContents::Blob("iVBO".to_string()),
+            }
+        );
+    }
+
+    #[test]
+    fn test_unsupported_content() {
+        assert_eq!(
+            convert(json!({ "type": "video", "data": "AAAA" })),
+            ToolContent::Text("[unsupported video content]".to_string())
+        );
+        // Content that doesn't match its type is unsupported as well.
+        assert_eq!(
+            convert(json!({ "type": "image", "mimeType": "image/png" })),
+            ToolContent::Text("[unsupported image content]".to_string())
+        );
+        assert_eq!(
+            convert(json!({ "text": "untyped" })),
+            ToolContent::Text("[unsupported content]".to_string())
+        );
+    }
+
+    #[test]
+    fn test_tool_result() {
+        let response: types::CallToolResponse = serde_json::from_value(json!({
+            "content": [
+                { "type": "text", "text": "first " },
+                { "type": "image", "data": "iVBO", "mimeType": "image/png" },
+                { "type": "text", "text": "second" },
+            ],
+        }))
+        .unwrap();
+        let result = ToolResult::from(response);
+        assert!(!result.is_error);
+        assert_eq!(result.content.len(), 3);
+        assert_eq!(result.text(), "first second");
+        assert_eq!(result.structured_content, None);
+
+        let response: types::CallToolResponse = serde_json::from_value(json!({
+            "content": [{ "type": "text", "text": "{\"count\": 3}" }],
+            "structuredContent": { "count": 3 },
+        }))
+        .unwrap();
+        assert_eq!(
+            ToolResult::from(response).structured_content,
+            Some(json!({ "count": 3 }))
+        );
+
+        let response: types::CallToolResponse = serde_json::from_value(json!({
+            "content": [{ "type": "text", "text": "not found" }],
+            "isError": true,
+        }))
+        .unwrap();
+        assert_eq!(
+            ToolResult::from(response),
+            ToolResult {
+                content: vec![ToolContent::Text("not found".to_string())],
+                is_error: true,
+                ..Default::default()
+            }
+        );
+    }
+}
@@ -0,0 +1,150 @@
+//! Retrying tool calls that failed because of the connection to the server.
+
+use std::time::Duration;
+
+use crate::error::ContextServerError;
+use crate::types::ToolAnnotations;
+
+/// How long to wait between retries at most, however many retries were made.
+const MAX_BACKOFF: Duration = Duration::from_secs(30);
+
+/// Which failed tool calls are retried, how many times and how long to wait in between.
+///
+/// Only calls to tools the server marks as idempotent or read-only are retried, since the
+/// server may have run the tool before the connection broke.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RetryPolicy {
+    /// How many times a call is made at most, including the first attempt.
+    pub max_attempts: u32,
+    /// How long to wait before the first retry. The delay doubles with every retry, up to
+    /// 30 seconds.
+    pub initial_backoff: Duration,
+    /// Which failures are retried. Errors reported by the server or the tool never are.
+    pub retry_on: Vec<RetryableError>,
+}
+
+/// A kind of tool call failure that can be retried.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RetryableError {
+    /// [`ContextServerError::Transport`].
+    Transport,
+    /// [`ContextServerError::TimedOut`].
+    TimedOut,
+}
+
+impl Default for RetryPolicy {
+    fn default() -> Self {
+        Self {
+            max_attempts: 3,
+            initial_backoff: Duration::from_millis(500),
+            retry_on: vec![RetryableError::Transport],
+        }
+    }
+}
+
+impl RetryPolicy {
+    /// Whether calls to a tool with the given annotations may be retried. Tools that weren't
+    /// listed have no annotations.
+    pub fn applies_to(&self, annotations: Option<&ToolAnnotations>) -> bool {
+        annotations.is_some_and(|annotations| {
+            annotations.idempotent_hint == Some(true) || annotations.read_only_hint == Some(true)
+        })
+    }
+
+    /// Whether a call that failed with `error` is worth retrying.
+    pub fn is_retryable(&self, error: &ContextServerError) -> bool {
+        let kind = match error {
+            ContextServerError::Transport(_) => RetryableError::Transport,
+            ContextServerError::TimedOut { .. } => RetryableError::TimedOut,
+            _ => return false,
+        };
+        self.retry_on.contains(&kind)
+    }
+
+    /// How long to wait after the given attempt failed, counting from 1.
+    pub fn backoff(&self, attempt: u32) -> Duration {
+        let factor = 1u32 << attempt.saturating_sub(1).min(16);
+        self.initial_backoff.saturating_mul(factor).min(MAX_BACKOFF)
+    }
+}
+
+impl From<&settings::ContextServerRetrySettings> for RetryPolicy {
+    fn from(settings: &settings::ContextServerRetrySettings) -> Self {
+        let default = Self::default();
+        Self {
+            max_attempts: settings.max_attempts.unwrap_or(default.max_attempts),
+            initial_backoff: settings
+                .initial_backoff
+                .map_or(default.initial_backoff, Duration::from_millis),
+            retry_on: settings
+                .retry_on
+                .as_ref()
+                .map_or(default.retry_on, |kinds| {
+                    kinds
+                        .iter()
+                        .map(|kind| match kind {
+                            settings::ContextServerRetryableError::Transport => {
+                                RetryableError::Transport
+                            }
+                            settings::ContextServerRetryableError::Timeout => {
+                                RetryableError::TimedOut
+                            }
+                        })
+                        .collect()
+                }),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use anyhow::anyhow;
+
+    use super::*;
+    use crate::client::{ConnectionInterrupted, RpcError};
+
+    #[test]
+    fn test_retry_policy() {
+        let policy = RetryPolicy::default();
+
+        let annotations = |read_only_hint, idempotent_hint| ToolAnnotations {
+            read_only_hint,
+            idempotent_hint,
+            ..Default::default()
+        };
+        assert!(policy.applies_to(Some(&annotations(None, Some(true)))));
+        assert!(policy.applies_to(Some(&annotations(Some(true), None))));
+        assert!(!policy.applies_to(Some(&annotations(Some(false), Some(false)))));
+        assert!(!policy.applies_to(None));
+
+        let transport = ContextServerError::from(anyhow!(ConnectionInterrupted));
+        let timed_out = ContextServerError::TimedOut {
+            tool: None,
+            timeout: None,
+        };
+        let rpc = ContextServerError::from(anyhow!(RpcError {
+            code: -32603,
+            message: "internal error".into(),
+        }));
+        let tool_error = ContextServerError::ToolError {
+            tool: "search".into(),
+            content: Vec::new(),
+        };
+        assert!(policy.is_retryable(&transport));
+        assert!(!policy.is_retryable(&timed_out));
+        assert!(!policy.is_retryable(&rpc));
+        assert!(!policy.is_retryable(&tool_error));
+
+        let policy = RetryPolicy {
+            retry_on: vec![RetryableError::TimedOut],
+            ..RetryPolicy::default()
+        };
+        assert!(!policy.is_retryable(&transport));
+        assert!(policy.is_retryable(&timed_out));
+
+        assert_eq!(policy.backoff(1), Duration::from_millis(500));
+        assert_eq!(policy.backoff(2), Duration::from_secs(1));
+        assert_eq!(policy.backoff(3), Duration::from_secs(2));
+        assert_eq!(policy.backoff(100), MAX_BACKOFF);
+    }
+}
@@ -0,0 +1,224 @@
+//! Logging the JSON-RPC messages exchanged with a server to a file, for debugging servers
+//! that misbehave.
+
+use std::fs::{self, File};
+use std::io::Write as _;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+
+use anyhow::{Context as _, Result};
+use chrono::{DateTime, SecondsFormat, Utc};
+use parking_lot::Mutex;
+use serde_json::Value;
+
+/// How large a traffic log grows before it's moved to a `.old` file and a new one is started.
+pub const MAX_TRAFFIC_LOG_BYTES: u64 = 10 * 1024 * 1024;
+
+/// Fields whose names look like they hold credentials, but that are part of the protocol.
+const NOT_REDACTED: &[&str] = &["progressToken"];
+
+/// Whether a message was sent to the server or received from it.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Direction {
+    Sent,
+    Received,
+}
+
+/// Where the messages exchanged with a server are logged, if they are.
+///
+/// It's shared with the server's client, so that logging can be turned on and off while the
+/// server runs. The file is only created once a message is logged.
+#[derive(Clone, Default)]
+pub struct TrafficLog(Arc<Mutex<TrafficLogState>>);
+
+#[derive(Default)]
+struct TrafficLogState {
+    path: Option<PathBuf>,
+    enabled: bool,
+    file: Option<File>,
+    size: u64,
+}
+
+impl TrafficLog {
+    /// Sets the file messages are logged to while logging is enabled.
+    pub fn set_path(&self, path: PathBuf) {
+        let mut state = self.0.lock();
+        state.file = None;
+        state.path = Some(path);
+    }
+
+    pub fn set_enabled(&self, enabled: bool) {
+        let mut state = self.0.lock();
+        state.enabled = enabled;
+        if !enabled {
+            state.file = None;
+        }
+    }
+
+    /// The file messages are logged to, or `None` if they aren't logged.
+    pub fn path(&self) -> Option<PathBuf> {
+        let state = self.0.lock();
+        state.path.clone().filter(|_| state.enabled)
+    }
+
+    /// Logs a message if logging is enabled. Logging is disabled if the file can't be
+    /// written to.
+    pub(crate) fn record(&self, direction: Direction, message: &str) {
+        let mut state = self.0.lock();
+        if !state.enabled {
+            return;
+        }
+        let entry = format_entry(direction, message, Utc::now());
+        if let Err(error) = state.write(&entry) {
+            log::error!("failed to write context server traffic log: {error:#}");
+            state.enabled = false;
+            state.file = None;
+        }
+    }
+}
+
+impl TrafficLogState {
+    fn write(&mut self, entry: &str) -> Result<()> {
+        let path = self.path.clone().context("no traffic log file was set")?;
+        if self.file.is_none() {
+            self.open(&path)?;
+        }
+        let entry_size = entry.len() as u64;
+        if self.size > 0 && self.size + entry_size > MAX_TRAFFIC_LOG_BYTES {
+            self.file = None;
+            fs::rename(&path, rotated_path(&path))
+                .with_context(|| format!("failed to rotate {path:?}"))?;
+            self.open(&path)?;
+        }
+        let file = self.file.as_mut().context("traffic log isn't open")?;
+        file.write_all(entry.as_bytes())
+            .with_context(|| format!("failed to write to {path:?}"))?;
+        self.size += entry_size;
+        Ok(())
+    }
+
+    fn open(&mut self, path: &Path) -> Result<()> {
+        if let Some(directory) = path.parent() {
+            fs::create_dir_all(directory)
+                .with_context(|| format!("failed to create {directory:?}"))?;
+        }
+        let file = File::options()
+            .create(true)
+            .append(true)
+            .open(path)
+            .with_context(|| format!("failed to open {path:?}"))?;
+        self.size = file.metadata()?.len();
+        self.file = Some(file);
+        Ok(())
+    }
+}
+
+/// The file a full traffic log is moved to, like `Zed.log.old` for `Zed.log`.
+fn rotated_path(path: &Path) -> PathBuf {
+    let mut file_name = path.file_name().unwrap_or_default().to_os_string();
+    file_name.push(".old");
+    path.with_file_name(file_name)
+}
+
+/// Formats a message as pretty-printed JSON after a line with the time and direction, with
+/// the values of fields that look like credentials redacted.
+fn format_entry(direction: Direction, message: &str, time: DateTime<Utc>) -> String {
+    let direction = match direction {
+        Direction::Sent => "sent",
+        Direction::Received => "received",
+    };
+    let message = match serde_json::from_str::<Value>(message) {
+        Ok(mut message) => {
+            redact(&mut message);
+            serde_json::to_string_pretty(&message).unwrap_or_default()
+        }
+        // Messages that aren't JSON are logged as they are, since they hold no fields.
+        Err(_) => message.trim_end().to_string(),
+    };
+    format!(
+        "[{}] {direction}\n{message}\n\n",
+        time.to_rfc3339_opts(SecondsFormat::Millis, true)
+    )
+}
+
+fn redact(value: &mut Value) {
+    match value {
+        Value::Object(object) => {
+            for (key, value) in object.iter_mut() {
+                if value.is_string()
+                    && util::redact::should_redact(key)
+                    && !NOT_REDACTED.contains(&key.as_str())
+                {
+                    *value = Value::String("[REDACTED]".to_string());
+                } else {
+                    redact(value);
+                }
+            }
+        }
+        Value::Array(values) => values.iter_mut().for_each(redact),
+        _ => {}
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_format_entry() {
+        let time = DateTime::parse_from_rfc3339("2025-01-02T03:04:05.678Z")
+            .unwrap()
+            .with_timezone(&Utc);
+        let message = r#"{"jsonrpc":"2.0","id":1,"method":"tools/call","params":{"name":"fetch","arguments":{"headers":{"Authorization":"Bearer abc"},"api_key":"secret","count":2},"_meta":{"progressToken":"p1"}}}"#;
+        assert_eq!(
+            format_entry(Direction::Sent, message, time),
+            r#"[2025-01-02T03:04:05.678Z] sent
+{
+  "jsonrpc": "2.0",
+  "id": 1,
+  "method": "tools/call",
+  "params": {
+    "name": "fetch",
+    "arguments": {
+      "headers": {
+        "Authorization": "[REDACTED]"
+      },
+      "api_key": "[REDACTED]",
+      "count": 2
+    },
+    "_meta": {
+      "progressToken": "p1"
+    }
+  }
+}
+
+"#
+        );
+        assert_eq!(
+            format_entry(Direction::Received, "not json\n", time),
+            "[2025-01-02T03:04:05.678Z] received\nnot json\n\n"
+        );
+    }
+
+    #[test]
+    fn test_rotation() {
+        let directory = tempfile::tempdir().unwrap();
+        let path = directory.path().join("logs").join("server.log");
+        let log = TrafficLog::default();
+        log.set_path(path.clone());
+        log.record(Direction::Sent, "{}");
+        assert!(!path.exists());
+        assert_eq!(log.path(), None);
+
+        log.set_enabled(true);
+        assert_eq!(log.path(), Some(path.clone()));
+        let message = format!(r#"{{"text":"{}"}}"#, "x".repeat(1024 * 1024));
+        for _ in 0..9 {
+            log.record(Direction::Received, &message);
+        }
+        assert!(!rotated_path(&path).exists());
+        log.record(Direction::Received, &message);
+        assert!(rotated_path(&path).exists());
+        assert!(fs::metadata(&path).unwrap().len() < 2 * 1024 * 1024);
+    }
+}
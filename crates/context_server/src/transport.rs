@@ -1,17 +1,383 @@
+mod auto;
 pub mod http;
+mod sse;
 mod stdio_transport;
+mod tcp;
+mod websocket;
 
-use anyhow::Result;
+use anyhow::{Result, anyhow};
 use async_trait::async_trait;
+use collections::HashMap;
 use futures::Stream;
+use gpui::BackgroundExecutor;
+use http_client::{AsyncBody, HttpClient, Request, Response, http::request};
+use parking_lot::{Mutex, RwLock};
+use std::fmt;
 use std::pin::Pin;
+use std::process::ExitStatus;
+use std::sync::Arc;
+use std::time::Duration;
 
+use crate::client::RequestId;
+
+pub use auto::*;
 pub use http::*;
+pub use sse::*;
 pub use stdio_transport::*;
+pub use tcp::*;
+pub use websocket::*;
+
+/// How the server ended when its transport was shut down.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Shutdown {
+    /// The server exited on its own, possibly before the shutdown started.
+    Exited,
+    /// The server exited after being sent SIGTERM.
+    Terminated,
+    /// The server had to be killed.
+    Killed,
+}
+
+/// A change in the connection to a server that reconnects by itself.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ConnectionStatus {
+    /// The connection was lost. Requests waiting for a response fail, since their responses
+    /// may have been lost with it.
+    Interrupted,
+    /// The connection was reestablished after the given number of attempts.
+    Reconnected { attempts: u32 },
+}
+
+/// How large the messages exchanged with a server may be unless configured otherwise.
+pub const DEFAULT_MAX_MESSAGE_BYTES: usize = 16 * 1024 * 1024;
+/// How many bytes from each end of an oversized message are kept to find its ID.
+const REJECTED_MESSAGE_EDGE_BYTES: usize = 1024;
+
+/// A message was larger than the size the server's messages are limited to.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MessageTooLarge {
+    /// The size of the message in bytes.
+    pub size: usize,
+    pub limit: usize,
+}
+
+impl std::error::Error for MessageTooLarge {}
+
+impl fmt::Display for MessageTooLarge {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "Context server message of {} bytes exceeds the limit of {} bytes",
+            self.size, self.limit
+        )
+    }
+}
+
+/// A message from the server that was dropped for being too large.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RejectedMessage {
+    /// The ID of the request the message answered, if it was a response and its ID could be
+    /// found without parsing it.
+    pub response_to: Option<RequestId>,
+    pub error: MessageTooLarge,
+}
+
+impl RejectedMessage {
+    /// Describes a message that was received whole, but is larger than `limit`.
+    pub(crate) fn new(message: &str, limit: usize) -> Self {
+        let message = message.trim_end().as_bytes();
+        let head = &message[..message.len().min(REJECTED_MESSAGE_EDGE_BYTES)];
+        let tail = &message[message.len().saturating_sub(REJECTED_MESSAGE_EDGE_BYTES)..];
+        Self {
+            response_to: response_id(head, tail),
+            error: MessageTooLarge {
+                size: message.len(),
+                limit,
+            },
+        }
+    }
+}
+
+/// Finds the ID of the response a message is, given the bytes at the start and end of it.
+///
+/// The ID is looked for before the result, where most servers put it, or otherwise after
+/// it, skipping any `id` fields in the result itself.
+pub(crate) fn response_id(head: &[u8], tail: &[u8]) -> Option<RequestId> {
+    let head = String::from_utf8_lossy(head);
+    // Requests from the server have IDs the server chose.
+    if head.contains("\"method\"") {
+        return None;
+    }
+    let result_start = ["\"result\"", "\"error\""]
+        .iter()
+        .filter_map(|key| head.find(key))
+        .min()
+        .unwrap_or(head.len());
+    if let Some(id) = head[..result_start]
+        .match_indices("\"id\"")
+        .find_map(|(ix, _)| parse_id(&head[ix..]))
+    {
+        return Some(id);
+    }
+    let tail = String::from_utf8_lossy(tail);
+    tail.rmatch_indices("\"id\"")
+        .find_map(|(ix, _)| parse_id(&tail[ix..]))
+}
+
+/// Parses the value of the `"id"` key at the start of `text`.
+fn parse_id(text: &str) -> Option<RequestId> {
+    let value = text["\"id\"".len()..]
+        .trim_start()
+        .strip_prefix(':')?
+        .trim_start();
+    if let Some(value) = value.strip_prefix('"') {
+        let end = value.find('"')?;
+        return Some(RequestId::Str(value[..end].to_string()));
+    }
+    let end = value
+        .find(|c: char| !(c.is_ascii_digit() || c == '-'))
+        .unwrap_or(value.len());
+    value[..end].parse().ok().map(RequestId::Int)
+}
 
 #[async_trait]
 pub trait Transport: Send + Sync {
     async fn send(&self, message: String) -> Result<()>;
     fn receive(&self) -> Pin<Box<dyn Stream<Item = String> + Send>>;
     fn receive_err(&self) -> Pin<Box<dyn Stream<Item = String> + Send>>;
+
+    /// Closes the connection, giving the server up to `grace_period` to exit by itself
+    /// before it is stopped forcefully.
+    async fn shutdown(&self, _grace_period: Duration) -> Result<Shutdown> {
+        Ok(Shutdown::Exited)
+    }
+
+    /// Resolves once the server closed the connection by itself, like a process that exited.
+    /// Never resolves for transports whose servers can't go away on their own.
+    async fn closed(&self) {
+        futures::future::pending().await
+    }
+
+    /// Waits up to `timeout` for the server's process to exit, returning how it exited, or
+    /// `None` if it's still running or the transport doesn't run a process.
+    async fn exit_status(&self, _timeout: Duration) -> Option<ExitStatus> {
+        None
+    }
+
+    /// The ID of the session the server assigned, for transports that have sessions.
+    fn session_id(&self) -> Option<String> {
+        None
+    }
+
+    /// Ends the session, if there is one, so that the server starts a new one the next time
+    /// it's connected to. Sessions are otherwise kept when the connection is closed.
+    async fn end_session(&self) -> Result<()> {
+        Ok(())
+    }
+
+    /// The changes in the connection, for transports that reconnect when it's lost.
+    fn connection_status(&self) -> Pin<Box<dyn Stream<Item = ConnectionStatus> + Send>> {
+        Box::pin(futures::stream::pending())
+    }
+
+    /// The messages from the server that were dropped for being larger than allowed, for
+    /// transports that limit the size of messages while reading them. The connection stays
+    /// usable after a message was dropped.
+    fn rejected_messages(&self) -> Pin<Box<dyn Stream<Item = RejectedMessage> + Send>> {
+        Box::pin(futures::stream::pending())
+    }
+}
+
+/// The headers sent with every request of an HTTP transport.
+///
+/// They are shared with the [`ContextServer`](crate::ContextServer), which sets them when
+/// the server starts, since their values may come from the environment or the credential
+/// store. Values are left out of the `Debug` output, as they usually hold credentials.
+#[derive(Clone, Default)]
+pub struct HttpHeaders(Arc<RwLock<HashMap<String, String>>>);
+
+impl HttpHeaders {
+    pub fn new(headers: HashMap<String, String>) -> Self {
+        Self(Arc::new(RwLock::new(headers)))
+    }
+
+    /// Replaces the headers sent with subsequent requests.
+    pub fn set(&self, headers: HashMap<String, String>) {
+        *self.0.write() = headers;
+    }
+
+    pub(crate) fn apply(&self, mut builder: request::Builder) -> request::Builder {
+        for (key, value) in self.0.read().iter() {
+            builder = builder.header(key.as_str(), value.as_str());
+        }
+        builder
+    }
+}
+
+impl fmt::Debug for HttpHeaders {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_map()
+            .entries(self.0.read().keys().map(|key| (key, "<redacted>")))
+            .finish()
+    }
+}
+
+/// How long HTTP transports wait for the server to start answering a request unless
+/// configured otherwise.
+pub const DEFAULT_HTTP_REQUEST_TIMEOUT: Duration = Duration::from_secs(5 * 60);
+
+/// How long an HTTP transport waits for the server to start answering a request. Streamed
+/// responses may take longer to finish.
+///
+/// It's shared with the [`ContextServer`](crate::ContextServer), which can change it after
+/// the transport was created.
+#[derive(Debug, Clone)]
+pub struct HttpRequestTimeout(Arc<Mutex<Duration>>);
+
+impl Default for HttpRequestTimeout {
+    fn default() -> Self {
+        Self(Arc::new(Mutex::new(DEFAULT_HTTP_REQUEST_TIMEOUT)))
+    }
+}
+
+impl HttpRequestTimeout {
+    pub fn set(&self, timeout: Duration) {
+        *self.0.lock() = timeout;
+    }
+
+    pub fn get(&self) -> Duration {
+        *self.0.lock()
+    }
+}
+
+/// The server didn't start answering an HTTP request in time.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct HttpRequestTimedOut {
+    pub timeout: Duration,
+}
+
+impl std::error::Error for HttpRequestTimedOut {}
+
+impl fmt::Display for HttpRequestTimedOut {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "Context server didn't answer the HTTP request within {:?}",
+            self.timeout
+        )
+    }
+}
+
+/// Sends an HTTP request, failing if the server doesn't start answering it within `timeout`.
+///
+/// Errors name the proxy the request went through, since a proxy that can't reach the server
+/// looks like the server being down otherwise.
+pub(crate) async fn send_request(
+    http_client: &dyn HttpClient,
+    request: Request<AsyncBody>,
+    timeout: &HttpRequestTimeout,
+    executor: &BackgroundExecutor,
+) -> Result<Response<AsyncBody>> {
+    let proxy = http_client.proxy().cloned();
+    let timeout = timeout.get();
+    let timer = executor.timer(timeout);
+    smol::future::or(http_client.send(request), async move {
+        timer.await;
+        Err(anyhow!(HttpRequestTimedOut { timeout }))
+    })
+    .await
+    .map_err(|error| match proxy {
+        Some(proxy) => error.context(format!("request through proxy {proxy} failed")),
+        None => error,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use gpui::TestAppContext;
+    use http_client::{FakeHttpClient, HttpClientWithProxy, Url};
+
+    #[gpui::test]
+    async fn test_send_request_names_proxy(cx: &mut TestAppContext) {
+        let http_client = FakeHttpClient::create(|_| async { anyhow::bail!("connection refused") });
+        let proxy = Url::parse("http://proxy.example.com:8080").unwrap();
+        let http_client = HttpClientWithProxy::new_url(http_client, Some(proxy));
+
+        let request = Request::get("http://mcp.example.com")
+            .body(AsyncBody::empty())
+            .unwrap();
+        let error = send_request(
+            &http_client,
+            request,
+            &HttpRequestTimeout::default(),
+            &cx.executor(),
+        )
+        .await
+        .unwrap_err();
+        assert_eq!(
+            format!("{error:#}"),
+            "request through proxy http://proxy.example.com:8080/ failed: connection refused"
+        );
+    }
+
+    #[gpui::test]
+    async fn test_send_request_timeout(cx: &mut TestAppContext) {
+        let http_client = FakeHttpClient::create(|_| futures::future::pending());
+        let timeout = HttpRequestTimeout::default();
+        timeout.set(Duration::from_secs(5));
+
+        let request = Request::get("http://mcp.example.com")
+            .body(AsyncBody::empty())
+            .unwrap();
+        let executor = cx.executor();
+        let response = cx.background_spawn(async move {
+            send_request(http_client.as_ref(), request, &timeout, &executor).await
+        });
+        cx.run_until_parked();
+        cx.executor().advance_clock(Duration::from_secs(5));
+        let error = response.await.unwrap_err();
+        assert_eq!(
+            error.downcast_ref::<HttpRequestTimedOut>(),
+            Some(&HttpRequestTimedOut {
+                timeout: Duration::from_secs(5)
+            })
+        );
+        assert!(crate::client::is_transport_failure(&error));
+    }
+
+    #[test]
+    fn test_rejected_message_id() {
+        let rejected = RejectedMessage::new(r#"{"jsonrpc":"2.0","id":3,"result":{"id":9}}"#, 10);
+        assert_eq!(rejected.response_to, Some(RequestId::Int(3)));
+        assert_eq!(
+            rejected.error,
+            MessageTooLarge {
+                size: 42,
+                limit: 10
+            }
+        );
+
+        let message = format!(
+            r#"{{"jsonrpc":"2.0","result":{{"items":[{{"id": 7}}],"text":"{}"}},"id" : "abc"}}"#,
+            "x".repeat(4096)
+        );
+        let rejected = RejectedMessage::new(&message, 10);
+        assert_eq!(rejected.response_to, Some(RequestId::Str("abc".into())));
+
+        let message = format!(
+            r#"{{"jsonrpc":"2.0","id":1,"method":"sampling/createMessage","params":"{}"}}"#,
+            "x".repeat(4096)
+        );
+        assert_eq!(RejectedMessage::new(&message, 10).response_to, None);
+    }
+
+    #[test]
+    fn test_http_headers_debug_is_redacted() {
+        let headers = HttpHeaders::new(HashMap::from_iter([(
+            "Authorization".to_string(),
+            "Bearer secret".to_string(),
+        )]));
+        assert_eq!(format!("{headers:?}"), r#"{"Authorization": "<redacted>"}"#);
+    }
 }
@@ -0,0 +1,170 @@
+use std::pin::Pin;
+use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, Ordering::SeqCst};
+
+use anyhow::Result;
+use async_trait::async_trait;
+use futures::{Stream, StreamExt as _};
+use gpui::{BackgroundExecutor, Task};
+use http_client::HttpClient;
+use parking_lot::Mutex;
+use smol::channel;
+
+use crate::transport::{
+    ConnectionStatus, HttpHeaders, HttpRequestTimeout, HttpTransport, ReconnectTimeout,
+    SseTransport, Transport,
+};
+
+/// Talks streamable HTTP, unless the server rejects the first message with 404 or 405, in
+/// which case it falls back to the legacy HTTP with SSE transport.
+pub struct AutoTransport {
+    http: HttpTransport,
+    http_client: Arc<dyn HttpClient>,
+    endpoint: String,
+    headers: HttpHeaders,
+    reconnect_timeout: ReconnectTimeout,
+    request_timeout: HttpRequestTimeout,
+    executor: BackgroundExecutor,
+    /// Whether the server accepted a message over streamable HTTP.
+    http_supported: AtomicBool,
+    sse: Mutex<Option<(Arc<SseTransport>, Task<()>)>>,
+    sse_response_tx: channel::Sender<String>,
+    sse_response_rx: channel::Receiver<String>,
+    sse_error_tx: channel::Sender<String>,
+    sse_error_rx: channel::Receiver<String>,
+    sse_status_tx: channel::Sender<ConnectionStatus>,
+    sse_status_rx: channel::Receiver<ConnectionStatus>,
+}
+
+impl AutoTransport {
+    pub fn new(
+        http_client: Arc<dyn HttpClient>,
+        endpoint: String,
+        headers: HttpHeaders,
+        reconnect_timeout: ReconnectTimeout,
+        request_timeout: HttpRequestTimeout,
+        executor: BackgroundExecutor,
+    ) -> Self {
+        let (sse_response_tx, sse_response_rx) = channel::unbounded();
+        let (sse_error_tx, sse_error_rx) = channel::unbounded();
+        let (sse_status_tx, sse_status_rx) = channel::unbounded();
+        Self {
+            http: HttpTransport::new(
+                http_client.clone(),
+                endpoint.clone(),
+                headers.clone(),
+                request_timeout.clone(),
+                executor.clone(),
+            ),
+            http_client,
+            endpoint,
+            headers,
+            reconnect_timeout,
+            request_timeout,
+            executor,
+            http_supported: AtomicBool::new(false),
+            sse: Mutex::new(None),
+            sse_response_tx,
+            sse_response_rx,
+            sse_error_tx,
+            sse_error_rx,
+            sse_status_tx,
+            sse_status_rx,
+        }
+    }
+
+    fn connect_sse(&self) -> Arc<SseTransport> {
+        let sse = Arc::new(SseTransport::new(
+            self.http_client.clone(),
+            self.endpoint.clone(),
+            self.headers.clone(),
+            self.reconnect_timeout.clone(),
+            self.request_timeout.clone(),
+            self.executor.clone(),
+        ));
+        let forward_task = self.executor.spawn({
+            let mut responses = sse.receive();
+            let mut errors = sse.receive_err();
+            let mut statuses = sse.connection_status();
+            let response_tx = self.sse_response_tx.clone();
+            let error_tx = self.sse_error_tx.clone();
+            let status_tx = self.sse_status_tx.clone();
+            async move {
+                futures::join!(
+                    async {
+                        while let Some(response) = responses.next().await {
+                            response_tx.send(response).await.ok();
+                        }
+                    },
+                    async {
+                        while let Some(error) = errors.next().await {
+                            error_tx.send(error).await.ok();
+                        }
+                    },
+                    async {
+                        while let Some(status) = statuses.next().await {
+                            status_tx.send(status).await.ok();
+                        }
+                    }
+                );
+            }
+        });
+        *self.sse.lock() = Some((sse.clone(), forward_task));
+        sse
+    }
+}
+
+#[async_trait]
+impl Transport for AutoTransport {
+    async fn send(&self, message: String) -> Result<()> {
+        let sse = self.sse.lock().as_ref().map(|(sse, _)| sse.clone());
+        if let Some(sse) = sse {
+            return sse.send(message).await;
+        }
+        if self.http_supported.load(SeqCst) {
+            return self.http.send(message).await;
+        }
+
+        match self.http.post(message.clone()).await? {
+            None => {
+                self.http_supported.store(true, SeqCst);
+                Ok(())
+            }
+            Some(response) if matches!(response.status().as_u16(), 404 | 405) => {
+                log::info!(
+                    "{} doesn't support streamable HTTP ({}), falling back to SSE",
+                    self.endpoint,
+                    response.status()
+                );
+                self.connect_sse().send(message).await
+            }
+            Some(response) => self.http.report_error(response).await,
+        }
+    }
+
+    fn receive(&self) -> Pin<Box<dyn Stream<Item = String> + Send>> {
+        Box::pin(futures::stream::select(
+            self.http.receive(),
+            self.sse_response_rx.clone(),
+        ))
+    }
+
+    fn receive_err(&self) -> Pin<Box<dyn Stream<Item = String> + Send>> {
+        Box::pin(futures::stream::select(
+            self.http.receive_err(),
+            self.sse_error_rx.clone(),
+        ))
+    }
+
+    fn session_id(&self) -> Option<String> {
+        self.http.session_id()
+    }
+
+    async fn end_session(&self) -> Result<()> {
+        self.http.end_session().await
+    }
+
+    fn connection_status(&self) -> Pin<Box<dyn Stream<Item = ConnectionStatus> + Send>> {
+        Box::pin(self.sse_status_rx.clone())
+    }
+}
@@ -1,6 +1,5 @@
 use anyhow::{Result, anyhow};
 use async_trait::async_trait;
-use collections::HashMap;
 use futures::{Stream, StreamExt};
 use gpui::BackgroundExecutor;
 use http_client::{AsyncBody, HttpClient, Request, Response, http::Method};
@@ -8,7 +7,7 @@ use parking_lot::Mutex as SyncMutex;
 use smol::channel;
 use std::{pin::Pin, sync::Arc};
 
-use crate::transport::Transport;
+use crate::transport::{HttpHeaders, HttpRequestTimeout, Transport, send_request};
 
 // Constants from MCP spec
 const HEADER_SESSION_ID: &str = "Mcp-Session-Id";
@@ -26,14 +25,16 @@ pub struct HttpTransport {
     error_tx: channel::Sender<String>,
     error_rx: channel::Receiver<String>,
     // Authentication headers to include in requests
-    headers: HashMap<String, String>,
+    headers: HttpHeaders,
+    request_timeout: HttpRequestTimeout,
 }
 
 impl HttpTransport {
     pub fn new(
         http_client: Arc<dyn HttpClient>,
         endpoint: String,
-        headers: HashMap<String, String>,
+        headers: HttpHeaders,
+        request_timeout: HttpRequestTimeout,
         executor: BackgroundExecutor,
     ) -> Self {
         let (response_tx, response_rx) = channel::unbounded();
@@ -49,35 +50,47 @@ impl HttpTransport {
             error_tx,
             error_rx,
             headers,
+            request_timeout,
         }
     }
 
     /// Send a message and handle the response based on content type
     async fn send_message(&self, message: String) -> Result<()> {
+        if let Some(response) = self.post(message).await? {
+            self.report_error(response).await?;
+        }
+        Ok(())
+    }
+
+    /// Posts a message, handling the response if the server accepted it and returning it
+    /// otherwise.
+    ///
+    /// Messages are sent in the current session, if there is one. That includes the
+    /// initialize request of a restarted server, so that the server can keep the state of
+    /// the session. If the server no longer knows the session, the initialize request is
+    /// sent again to start a new one, while other messages fail.
+    pub(crate) async fn post(&self, message: String) -> Result<Option<Response<AsyncBody>>> {
         let is_notification =
             !message.contains("\"id\":") || message.contains("notifications/initialized");
+        let is_initialize = message.contains("\"method\":\"initialize\"");
 
-        let mut request_builder = Request::builder()
-            .method(Method::POST)
-            .uri(&self.endpoint)
-            .header("Content-Type", JSON_MIME_TYPE)
-            .header(
-                "Accept",
-                format!("{}, {}", JSON_MIME_TYPE, EVENT_STREAM_MIME_TYPE),
-            );
-
-        for (key, value) in &self.headers {
-            request_builder = request_builder.header(key.as_str(), value.as_str());
-        }
-
-        // Add session ID if we have one (except for initialize)
-        if let Some(ref session_id) = *self.session_id.lock() {
-            request_builder = request_builder.header(HEADER_SESSION_ID, session_id.as_str());
+        let session_id = self.session_id.lock().clone();
+        let mut response = self.send_post(&message, session_id.as_deref()).await?;
+        if let Some(session_id) = session_id
+            && response.status().as_u16() == 404
+        {
+            log::info!("{} ended session {session_id}", self.endpoint);
+            {
+                let mut current_session_id = self.session_id.lock();
+                if current_session_id.as_ref() == Some(&session_id) {
+                    *current_session_id = None;
+                }
+            }
+            if is_initialize {
+                response = self.send_post(&message, None).await?;
+            }
         }
 
-        let request = request_builder.body(AsyncBody::from(message.into_bytes()))?;
-        let mut response = self.http_client.send(request).await?;
-
         // Handle different response types based on status and content-type
         match response.status() {
             status if status.is_success() => {
@@ -130,17 +143,71 @@ impl HttpTransport {
                 // Accepted - notification acknowledged, no response needed
                 log::debug!("Notification accepted");
             }
-            _ => {
-                let mut error_body = String::new();
-                futures::AsyncReadExt::read_to_string(response.body_mut(), &mut error_body).await?;
+            _ => return Ok(Some(response)),
+        }
 
-                self.error_tx
-                    .send(format!("HTTP {}: {}", response.status(), error_body))
-                    .await
-                    .map_err(|_| anyhow!("Failed to send error"))?;
-            }
+        Ok(None)
+    }
+
+    async fn send_post(
+        &self,
+        message: &str,
+        session_id: Option<&str>,
+    ) -> Result<Response<AsyncBody>> {
+        let mut request_builder = self.headers.apply(
+            Request::builder()
+                .method(Method::POST)
+                .uri(&self.endpoint)
+                .header("Content-Type", JSON_MIME_TYPE)
+                .header(
+                    "Accept",
+                    format!("{}, {}", JSON_MIME_TYPE, EVENT_STREAM_MIME_TYPE),
+                ),
+        );
+        if let Some(session_id) = session_id {
+            request_builder = request_builder.header(HEADER_SESSION_ID, session_id);
         }
 
+        let request = request_builder.body(AsyncBody::from(message.as_bytes().to_vec()))?;
+        send_request(
+            self.http_client.as_ref(),
+            request,
+            &self.request_timeout,
+            &self.executor,
+        )
+        .await
+    }
+
+    /// The ID of the current session, if the server assigned one.
+    pub(crate) fn session_id(&self) -> Option<String> {
+        self.session_id.lock().clone()
+    }
+
+    /// Asks the server to end the current session, if there is one.
+    pub(crate) async fn end_session(&self) -> Result<()> {
+        let Some(session_id) = self.session_id.lock().take() else {
+            return Ok(());
+        };
+        delete_session(
+            self.http_client.as_ref(),
+            &self.endpoint,
+            &self.headers,
+            &session_id,
+            &self.request_timeout,
+            &self.executor,
+        )
+        .await
+    }
+
+    /// Forwards the body of a response rejecting a message to the error stream.
+    pub(crate) async fn report_error(&self, mut response: Response<AsyncBody>) -> Result<()> {
+        let mut error_body = String::new();
+        futures::AsyncReadExt::read_to_string(response.body_mut(), &mut error_body).await?;
+
+        self.error_tx
+            .send(format!("HTTP {}: {}", response.status(), error_body))
+            .await
+            .map_err(|_| anyhow!("Failed to send error"))?;
         Ok(())
     }
 
@@ -224,6 +291,37 @@ impl Transport for HttpTransport {
     fn receive_err(&self) -> Pin<Box<dyn Stream<Item = String> + Send>> {
         Box::pin(self.error_rx.clone())
     }
+
+    fn session_id(&self) -> Option<String> {
+        HttpTransport::session_id(self)
+    }
+
+    async fn end_session(&self) -> Result<()> {
+        HttpTransport::end_session(self).await
+    }
+}
+
+/// Ends a session. Servers that don't let clients end sessions respond with 405, which is
+/// fine, as they end them by themselves.
+async fn delete_session(
+    http_client: &dyn HttpClient,
+    endpoint: &str,
+    headers: &HttpHeaders,
+    session_id: &str,
+    request_timeout: &HttpRequestTimeout,
+    executor: &BackgroundExecutor,
+) -> Result<()> {
+    let request = headers
+        .apply(
+            Request::builder()
+                .method(Method::DELETE)
+                .uri(endpoint)
+                .header(HEADER_SESSION_ID, session_id),
+        )
+        .body(AsyncBody::empty())?;
+    let response = send_request(http_client, request, request_timeout, executor).await?;
+    log::debug!("ended session {session_id}: {}", response.status());
+    Ok(())
 }
 
 impl Drop for HttpTransport {
@@ -233,27 +331,202 @@ impl Drop for HttpTransport {
         let endpoint = self.endpoint.clone();
         let session_id = self.session_id.lock().clone();
         let headers = self.headers.clone();
+        let request_timeout = self.request_timeout.clone();
+        let executor = self.executor.clone();
 
         if let Some(session_id) = session_id {
             self.executor
                 .spawn(async move {
-                    let mut request_builder = Request::builder()
-                        .method(Method::DELETE)
-                        .uri(&endpoint)
-                        .header(HEADER_SESSION_ID, &session_id);
-
-                    // Add authentication headers if present
-                    for (key, value) in headers {
-                        request_builder = request_builder.header(key.as_str(), value.as_str());
-                    }
-
-                    let request = request_builder.body(AsyncBody::empty());
-
-                    if let Ok(request) = request {
-                        let _ = http_client.send(request).await;
-                    }
+                    delete_session(
+                        http_client.as_ref(),
+                        &endpoint,
+                        &headers,
+                        &session_id,
+                        &request_timeout,
+                        &executor,
+                    )
+                    .await
+                    .ok();
                 })
                 .detach();
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::error::ContextServerError;
+    use crate::{ContextServer, ContextServerHttpTransport, ContextServerId, types};
+    use collections::{HashMap, HashSet};
+    use futures::AsyncReadExt as _;
+    use gpui::TestAppContext;
+    use http_client::FakeHttpClient;
+    use serde_json::json;
+    use std::time::Duration;
+    use url::Url;
+
+    #[derive(Default)]
+    struct ServerState {
+        sessions: HashSet<String>,
+        next_session: usize,
+        /// The session ID sent with each initialize request.
+        initialize_sessions: Vec<Option<String>>,
+        ended_sessions: Vec<String>,
+        /// Whether requests are left unanswered.
+        unresponsive: bool,
+    }
+
+    /// A streamable HTTP server at `/mcp` that tracks sessions. Initialize requests in a
+    /// session it knows resume it, and requests in sessions it doesn't know fail with 404.
+    fn fake_http_server() -> (Arc<dyn HttpClient>, Arc<SyncMutex<ServerState>>) {
+        let state = Arc::new(SyncMutex::new(ServerState::default()));
+        let http_client = FakeHttpClient::create({
+            let state = state.clone();
+            move |mut request| {
+                let state = state.clone();
+                async move {
+                    let session_id = request
+                        .headers()
+                        .get(HEADER_SESSION_ID)
+                        .map(|value| value.to_str().unwrap().to_string());
+                    if *request.method() == Method::DELETE {
+                        let session_id = session_id.unwrap();
+                        let mut state = state.lock();
+                        state.sessions.remove(&session_id);
+                        state.ended_sessions.push(session_id);
+                        return Ok(Response::builder().status(200).body(AsyncBody::empty())?);
+                    }
+
+                    let mut body = String::new();
+                    request.body_mut().read_to_string(&mut body).await?;
+                    let message: serde_json::Value = serde_json::from_str(&body)?;
+                    if state.lock().unresponsive && message.get("id").is_some() {
+                        return futures::future::pending().await;
+                    }
+                    let mut state = state.lock();
+                    let is_initialize = message["method"] == "initialize";
+                    if is_initialize {
+                        state.initialize_sessions.push(session_id.clone());
+                    }
+                    let session_id = match session_id {
+                        Some(session_id) if state.sessions.contains(&session_id) => session_id,
+                        Some(_) => {
+                            return Ok(Response::builder().status(404).body(AsyncBody::empty())?);
+                        }
+                        None if is_initialize => {
+                            state.next_session += 1;
+                            let session_id = format!("session-{}", state.next_session);
+                            state.sessions.insert(session_id.clone());
+                            session_id
+                        }
+                        None => {
+                            return Ok(Response::builder().status(400).body(AsyncBody::empty())?);
+                        }
+                    };
+
+                    let Some(id) = message.get("id") else {
+                        return Ok(Response::builder().status(202).body(AsyncBody::empty())?);
+                    };
+                    let result = if is_initialize {
+                        json!({
+                            "protocolVersion": types::LATEST_PROTOCOL_VERSION,
+                            "capabilities": {},
+                            "serverInfo": { "name": "http-server", "version": "1.0.0" },
+                        })
+                    } else {
+                        json!({})
+                    };
+                    let response = json!({ "jsonrpc": "2.0", "id": id, "result": result });
+                    Ok(Response::builder()
+                        .status(200)
+                        .header("Content-Type", JSON_MIME_TYPE)
+                        .header(HEADER_SESSION_ID, session_id)
+                        .body(AsyncBody::from(response.to_string()))?)
+                }
+            }
+        });
+        (http_client, state)
+    }
+
+    #[gpui::test]
+    async fn test_session_resumption(cx: &mut TestAppContext) {
+        let (http_client, state) = fake_http_server();
+        let server = ContextServer::http(
+            ContextServerId("http".into()),
+            &Url::parse("http://test.example/mcp").unwrap(),
+            HashMap::default(),
+            ContextServerHttpTransport::StreamableHttp,
+            http_client,
+            cx.executor(),
+        )
+        .unwrap();
+        server.start(&cx.to_async()).await.unwrap();
+        assert_eq!(server.session_id().as_deref(), Some("session-1"));
+
+        // Restarts resume the session.
+        server.restart(&cx.to_async()).await.unwrap();
+        server.ping(Duration::from_secs(1)).await.unwrap();
+        assert_eq!(server.session_id().as_deref(), Some("session-1"));
+
+        // Unless the server no longer knows it, in which case a new one is started.
+        state.lock().sessions.clear();
+        server.restart(&cx.to_async()).await.unwrap();
+        server.ping(Duration::from_secs(1)).await.unwrap();
+        assert_eq!(server.session_id().as_deref(), Some("session-2"));
+        assert_eq!(
+            state.lock().initialize_sessions,
+            [
+                None,
+                Some("session-1".to_string()),
+                Some("session-1".to_string()),
+                None
+            ]
+        );
+
+        // Stopping the server ends its session.
+        server.stop().await.unwrap();
+        cx.run_until_parked();
+        assert_eq!(server.session_id(), None);
+        assert_eq!(state.lock().ended_sessions, ["session-2"]);
+        assert!(state.lock().sessions.is_empty());
+    }
+
+    #[gpui::test]
+    async fn test_request_timeout(cx: &mut TestAppContext) {
+        let (http_client, state) = fake_http_server();
+        let server = Arc::new(
+            ContextServer::http(
+                ContextServerId("http".into()),
+                &Url::parse("http://test.example/mcp").unwrap(),
+                HashMap::default(),
+                ContextServerHttpTransport::StreamableHttp,
+                http_client,
+                cx.executor(),
+            )
+            .unwrap()
+            .with_http_request_timeout(Duration::from_secs(5)),
+        );
+        server.start(&cx.to_async()).await.unwrap();
+
+        // Requests the server doesn't start answering in time fail like a broken connection,
+        // so that they can be retried.
+        state.lock().unresponsive = true;
+        let ping = cx.foreground_executor().spawn({
+            let server = server.clone();
+            async move { server.ping(Duration::from_secs(60)).await }
+        });
+        cx.run_until_parked();
+        cx.executor().advance_clock(Duration::from_secs(5));
+        let error = ContextServerError::from(ping.await.unwrap_err());
+        assert!(
+            matches!(error, ContextServerError::Transport(_)),
+            "{error:?}"
+        );
+        assert_eq!(
+            error.to_string(),
+            "ping request 1 failed: failed to send a message to the context server: \
+             Context server didn't answer the HTTP request within 5s"
+        );
+    }
+}
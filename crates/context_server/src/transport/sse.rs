@@ -0,0 +1,816 @@
+use anyhow::{Context as _, Result, anyhow};
+use async_trait::async_trait;
+use futures::{
+    AsyncBufReadExt as _, AsyncReadExt as _, Stream, StreamExt as _,
+    io::{BufReader, Lines},
+};
+use gpui::{BackgroundExecutor, Task};
+use http_client::{AsyncBody, HttpClient, Request, http::Method};
+use parking_lot::Mutex;
+use postage::{prelude::Stream as _, watch};
+use smol::channel;
+use std::{mem, pin::Pin, sync::Arc, time::Duration};
+use url::Url;
+
+use crate::transport::{
+    ConnectionStatus, HttpHeaders, HttpRequestTimeout, Transport, send_request,
+};
+use crate::types::{self, Notification as _, Request as _};
+
+const EVENT_STREAM_MIME_TYPE: &str = "text/event-stream";
+const JSON_MIME_TYPE: &str = "application/json";
+/// How long to wait before the first attempt to reconnect. The delay doubles after each
+/// failed attempt, up to [`MAX_RECONNECT_DELAY`].
+const INITIAL_RECONNECT_DELAY: Duration = Duration::from_millis(500);
+const MAX_RECONNECT_DELAY: Duration = Duration::from_secs(30);
+/// How long SSE transports keep trying to reconnect unless configured otherwise.
+pub const DEFAULT_RECONNECT_TIMEOUT: Duration = Duration::from_secs(5 * 60);
+
+type EventLines = Lines<BufReader<AsyncBody>>;
+
+/// How long an [`SseTransport`] keeps trying to reconnect after losing its event stream.
+///
+/// It's shared with the [`ContextServer`](crate::ContextServer), which can change it after
+/// the transport was created.
+#[derive(Debug, Clone)]
+pub struct ReconnectTimeout(Arc<Mutex<Duration>>);
+
+impl Default for ReconnectTimeout {
+    fn default() -> Self {
+        Self(Arc::new(Mutex::new(DEFAULT_RECONNECT_TIMEOUT)))
+    }
+}
+
+impl ReconnectTimeout {
+    pub fn set(&self, timeout: Duration) {
+        *self.0.lock() = timeout;
+    }
+
+    pub fn get(&self) -> Duration {
+        *self.0.lock()
+    }
+}
+
+/// The legacy HTTP with SSE transport, which predates streamable HTTP.
+///
+/// The server sends messages as events on a long-lived SSE stream. The first event on the
+/// stream is an `endpoint` event with the URL messages for the server are posted to.
+///
+/// When the stream is lost after it was established, the transport reconnects with
+/// exponential backoff, sending the ID of the last event it received as `Last-Event-ID` so
+/// that the server can resume the stream. Messages are held back until it has reconnected.
+pub struct SseTransport {
+    http_client: Arc<dyn HttpClient>,
+    headers: HttpHeaders,
+    /// The URL messages are posted to, which is unset while the stream is reconnecting.
+    message_endpoint: watch::Receiver<Option<Url>>,
+    /// The messages that initialized the session, which are sent again if the server starts a
+    /// new one when reconnecting.
+    handshake: Arc<Mutex<Vec<String>>>,
+    response_rx: channel::Receiver<String>,
+    error_tx: channel::Sender<String>,
+    error_rx: channel::Receiver<String>,
+    status_rx: channel::Receiver<ConnectionStatus>,
+    request_timeout: HttpRequestTimeout,
+    executor: BackgroundExecutor,
+    _stream_task: Task<()>,
+}
+
+/// The state of the event stream that carries over when reconnecting.
+struct EventStream {
+    http_client: Arc<dyn HttpClient>,
+    endpoint: Url,
+    headers: HttpHeaders,
+    /// The ID of the last event with one, which the server resumes the stream after.
+    last_event_id: Option<String>,
+    response_tx: channel::Sender<String>,
+    request_timeout: HttpRequestTimeout,
+    executor: BackgroundExecutor,
+}
+
+struct Event {
+    kind: String,
+    data: String,
+}
+
+impl SseTransport {
+    pub fn new(
+        http_client: Arc<dyn HttpClient>,
+        endpoint: String,
+        headers: HttpHeaders,
+        reconnect_timeout: ReconnectTimeout,
+        request_timeout: HttpRequestTimeout,
+        executor: BackgroundExecutor,
+    ) -> Self {
+        let (response_tx, response_rx) = channel::unbounded();
+        let (error_tx, error_rx) = channel::unbounded();
+        let (status_tx, status_rx) = channel::unbounded();
+        let (endpoint_tx, endpoint_rx) = watch::channel();
+        let handshake = Arc::new(Mutex::new(Vec::new()));
+
+        let stream_task = executor.spawn({
+            let http_client = http_client.clone();
+            let headers = headers.clone();
+            let handshake = handshake.clone();
+            let error_tx = error_tx.clone();
+            let request_timeout = request_timeout.clone();
+            let executor = executor.clone();
+            async move {
+                let endpoint = match Url::parse(&endpoint) {
+                    Ok(endpoint) => endpoint,
+                    Err(error) => {
+                        error_tx
+                            .send(format!("SSE stream error: {error:#}"))
+                            .await
+                            .ok();
+                        return;
+                    }
+                };
+                let stream = EventStream {
+                    http_client,
+                    endpoint,
+                    headers,
+                    last_event_id: None,
+                    response_tx,
+                    request_timeout,
+                    executor,
+                };
+                if let Err(error) = stream
+                    .run(endpoint_tx, handshake, status_tx, reconnect_timeout)
+                    .await
+                {
+                    error_tx
+                        .send(format!("SSE stream error: {error:#}"))
+                        .await
+                        .ok();
+                }
+            }
+        });
+
+        Self {
+            http_client,
+            headers,
+            message_endpoint: endpoint_rx,
+            handshake,
+            response_rx,
+            error_tx,
+            error_rx,
+            status_rx,
+            request_timeout,
+            executor,
+            _stream_task: stream_task,
+        }
+    }
+}
+
+impl EventStream {
+    /// Forwards the messages sent on the event stream, reconnecting whenever it's lost.
+    ///
+    /// The first connection isn't retried, so that servers that can't be reached fail to
+    /// start instead of hanging.
+    async fn run(
+        mut self,
+        mut endpoint_tx: watch::Sender<Option<Url>>,
+        handshake: Arc<Mutex<Vec<String>>>,
+        status_tx: channel::Sender<ConnectionStatus>,
+        reconnect_timeout: ReconnectTimeout,
+    ) -> Result<()> {
+        let executor = self.executor.clone();
+        let (mut lines, mut message_endpoint) = self.connect().await?;
+        *endpoint_tx.borrow_mut() = Some(message_endpoint.clone());
+
+        loop {
+            match self.forward(&mut lines).await {
+                Ok(()) => log::warn!("SSE stream ended, reconnecting"),
+                Err(error) => log::warn!("SSE stream failed, reconnecting: {error:#}"),
+            }
+            if self.response_tx.is_closed() {
+                return Ok(());
+            }
+            *endpoint_tx.borrow_mut() = None;
+            status_tx.send(ConnectionStatus::Interrupted).await.ok();
+
+            let interrupted_at = executor.now();
+            let timeout = reconnect_timeout.get();
+            let mut delay = INITIAL_RECONNECT_DELAY;
+            let mut attempts = 0;
+            let previous_endpoint = message_endpoint;
+            (lines, message_endpoint) = loop {
+                if executor.now() + delay - interrupted_at > timeout {
+                    anyhow::bail!("gave up reconnecting after {attempts} attempts");
+                }
+                executor.timer(delay).await;
+                attempts += 1;
+                match self.connect().await {
+                    Ok(connection) => break connection,
+                    Err(error) => {
+                        log::warn!("SSE reconnection attempt {attempts} failed: {error:#}")
+                    }
+                }
+                delay = (delay * 2).min(MAX_RECONNECT_DELAY);
+            };
+
+            // A new endpoint means the server started a new session, which has to be
+            // initialized like the first one. The responses are dropped by the client, as
+            // it's no longer waiting for them.
+            if message_endpoint != previous_endpoint {
+                log::info!("SSE server started a new session, initializing it again");
+                let handshake = handshake.lock().clone();
+                for message in handshake {
+                    match post(
+                        self.http_client.as_ref(),
+                        &self.headers,
+                        &message_endpoint,
+                        message,
+                        &self.request_timeout,
+                        &executor,
+                    )
+                    .await
+                    {
+                        Ok(None) => {}
+                        Ok(Some(error)) => {
+                            log::warn!("failed to initialize new SSE session: {error}")
+                        }
+                        Err(error) => {
+                            log::warn!("failed to initialize new SSE session: {error:#}")
+                        }
+                    }
+                }
+            }
+            *endpoint_tx.borrow_mut() = Some(message_endpoint.clone());
+            status_tx
+                .send(ConnectionStatus::Reconnected { attempts })
+                .await
+                .ok();
+        }
+    }
+
+    /// Opens the event stream and waits for the endpoint event, forwarding the messages sent
+    /// before it.
+    async fn connect(&mut self) -> Result<(EventLines, Url)> {
+        let mut request = Request::builder()
+            .method(Method::GET)
+            .uri(self.endpoint.as_str())
+            .header("Accept", EVENT_STREAM_MIME_TYPE);
+        if let Some(last_event_id) = &self.last_event_id {
+            request = request.header("Last-Event-ID", last_event_id.as_str());
+        }
+        let request = self.headers.apply(request).body(AsyncBody::empty())?;
+        let mut response = send_request(
+            self.http_client.as_ref(),
+            request,
+            &self.request_timeout,
+            &self.executor,
+        )
+        .await?;
+        if !response.status().is_success() {
+            let mut body = String::new();
+            response.body_mut().read_to_string(&mut body).await?;
+            anyhow::bail!("HTTP {}: {}", response.status(), body);
+        }
+
+        let mut lines = BufReader::new(response.into_body()).lines();
+        while let Some(event) = self.next_event(&mut lines).await? {
+            if event.kind == "endpoint" {
+                let message_endpoint = self
+                    .endpoint
+                    .join(event.data.trim())
+                    .with_context(|| format!("invalid message endpoint {:?}", event.data))?;
+                log::debug!("SSE message endpoint: {message_endpoint}");
+                return Ok((lines, message_endpoint));
+            }
+            self.dispatch(event).await;
+        }
+        anyhow::bail!("SSE stream closed before the server sent its endpoint")
+    }
+
+    /// Forwards the messages sent on the event stream until it ends.
+    async fn forward(&mut self, lines: &mut EventLines) -> Result<()> {
+        while let Some(event) = self.next_event(lines).await? {
+            if !self.dispatch(event).await {
+                break;
+            }
+        }
+        Ok(())
+    }
+
+    /// Forwards the event if it's a message, returning false if the transport was dropped.
+    async fn dispatch(&self, event: Event) -> bool {
+        match event.kind.as_str() {
+            "" | "message" if !event.data.trim().is_empty() => {
+                self.response_tx.send(event.data).await.is_ok()
+            }
+            "" | "message" => true,
+            "endpoint" => {
+                log::debug!("ignoring SSE endpoint event after the first");
+                true
+            }
+            kind => {
+                log::debug!("ignoring SSE event {kind:?}");
+                true
+            }
+        }
+    }
+
+    /// Reads the next event, remembering its ID. Returns `None` once the stream ends.
+    async fn next_event(&mut self, lines: &mut EventLines) -> Result<Option<Event>> {
+        let mut kind = String::new();
+        let mut data = Vec::new();
+        while let Some(line) = lines.next().await {
+            let line = line?;
+            if !line.is_empty() {
+                // Lines starting with a colon are comments, which have an empty field name.
+                let (field, value) = line.split_once(':').unwrap_or((line.as_str(), ""));
+                let value = value.strip_prefix(' ').unwrap_or(value);
+                match field {
+                    "event" => kind = value.to_string(),
+                    "data" => data.push(value.to_string()),
+                    // IDs containing null can't be sent back in a header, so they are ignored.
+                    "id" if !value.contains('\0') => self.last_event_id = Some(value.to_string()),
+                    _ => {}
+                }
+                continue;
+            }
+
+            // An empty line dispatches the event.
+            return Ok(Some(Event {
+                kind: mem::take(&mut kind),
+                data: mem::take(&mut data).join("\n"),
+            }));
+        }
+        Ok(None)
+    }
+}
+
+/// Posts a message to the server, returning the error it responded with, if any.
+async fn post(
+    http_client: &dyn HttpClient,
+    headers: &HttpHeaders,
+    endpoint: &Url,
+    message: String,
+    request_timeout: &HttpRequestTimeout,
+    executor: &BackgroundExecutor,
+) -> Result<Option<String>> {
+    let request = headers
+        .apply(
+            Request::builder()
+                .method(Method::POST)
+                .uri(endpoint.as_str())
+                .header("Content-Type", JSON_MIME_TYPE),
+        )
+        .body(AsyncBody::from(message.into_bytes()))?;
+    let mut response = send_request(http_client, request, request_timeout, executor).await?;
+
+    // Responses arrive on the event stream, the POST just acknowledges the message.
+    if !response.status().is_success() {
+        let mut body = String::new();
+        response.body_mut().read_to_string(&mut body).await?;
+        return Ok(Some(format!("HTTP {}: {}", response.status(), body)));
+    }
+    Ok(None)
+}
+
+/// The method of the message, if it's a request or notification.
+fn method(message: &str) -> Option<String> {
+    #[derive(serde::Deserialize)]
+    struct Message {
+        method: Option<String>,
+    }
+    serde_json::from_str::<Message>(message).ok()?.method
+}
+
+#[async_trait]
+impl Transport for SseTransport {
+    async fn send(&self, message: String) -> Result<()> {
+        let mut message_endpoint = self.message_endpoint.clone();
+        let endpoint = loop {
+            if let Some(endpoint) = message_endpoint.borrow().clone() {
+                break endpoint;
+            }
+            if message_endpoint.recv().await.is_none() {
+                anyhow::bail!("SSE stream closed");
+            }
+        };
+
+        match method(&message).as_deref() {
+            // A restart initializes the session again, replacing the previous handshake.
+            Some(types::requests::Initialize::METHOD) => {
+                *self.handshake.lock() = vec![message.clone()]
+            }
+            Some(types::notifications::Initialized::METHOD) => {
+                self.handshake.lock().push(message.clone())
+            }
+            _ => {}
+        }
+        if let Some(error) = post(
+            self.http_client.as_ref(),
+            &self.headers,
+            &endpoint,
+            message,
+            &self.request_timeout,
+            &self.executor,
+        )
+        .await?
+        {
+            self.error_tx
+                .send(error)
+                .await
+                .map_err(|_| anyhow!("Failed to send error"))?;
+        }
+        Ok(())
+    }
+
+    fn receive(&self) -> Pin<Box<dyn Stream<Item = String> + Send>> {
+        Box::pin(self.response_rx.clone())
+    }
+
+    fn receive_err(&self) -> Pin<Box<dyn Stream<Item = String> + Send>> {
+        Box::pin(self.error_rx.clone())
+    }
+
+    fn connection_status(&self) -> Pin<Box<dyn Stream<Item = ConnectionStatus> + Send>> {
+        Box::pin(self.status_rx.clone())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::client::ConnectionInterrupted;
+    use crate::header_provider::HeaderProvider;
+    use crate::{ContextServer, ContextServerHttpTransport, ContextServerId, types};
+    use collections::HashMap;
+    use futures::{TryStreamExt as _, channel::mpsc};
+    use gpui::TestAppContext;
+    use http_client::{FakeHttpClient, Response};
+    use parking_lot::Mutex;
+    use serde_json::json;
+    use std::sync::atomic::{AtomicUsize, Ordering::SeqCst};
+    use std::time::Duration;
+
+    type EventSender = mpsc::UnboundedSender<std::io::Result<Vec<u8>>>;
+
+    #[derive(Default)]
+    struct SseServerState {
+        events_tx: Option<EventSender>,
+        /// How many times the event stream was opened, which is also the current session.
+        connections: usize,
+        /// The `Last-Event-ID` header of each request opening the event stream.
+        last_event_ids: Vec<Option<String>>,
+        last_event_id: usize,
+        /// The methods of the messages posted to the server.
+        methods: Vec<String>,
+        /// Whether opening the event stream fails.
+        unavailable: bool,
+        /// Whether requests other than initialize are left unanswered.
+        unresponsive: bool,
+    }
+
+    /// A minimal legacy SSE server, serving its event stream at `/sse` and accepting messages
+    /// at `/messages`. Posting to `/sse`, as streamable HTTP clients do, fails with 405.
+    fn fake_sse_server() -> Arc<dyn HttpClient> {
+        fake_sse_server_with_state().0
+    }
+
+    /// A [`fake_sse_server`] that starts a new session whenever the event stream is opened,
+    /// along with its state.
+    fn fake_sse_server_with_state() -> (Arc<dyn HttpClient>, Arc<Mutex<SseServerState>>) {
+        let state = Arc::new(Mutex::new(SseServerState::default()));
+        let http_client = FakeHttpClient::create({
+            let state = state.clone();
+            move |mut request| {
+                let state = state.clone();
+                async move {
+                    let method = request.method().to_string();
+                    let path = request.uri().path().to_string();
+                    match (method.as_str(), path.as_str()) {
+                        ("GET", "/sse") => {
+                            let mut state = state.lock();
+                            state.last_event_ids.push(
+                                request
+                                    .headers()
+                                    .get("Last-Event-ID")
+                                    .map(|value| value.to_str().unwrap().to_string()),
+                            );
+                            if state.unavailable {
+                                return Ok(Response::builder()
+                                    .status(503)
+                                    .body(AsyncBody::from("unavailable"))?);
+                            }
+                            state.connections += 1;
+                            let (tx, rx) = mpsc::unbounded();
+                            let endpoint = format!(
+                                ": connected\n\nevent: endpoint\ndata: /messages?session={}\n\n",
+                                state.connections
+                            );
+                            tx.unbounded_send(Ok(endpoint.into_bytes()))?;
+                            state.events_tx = Some(tx);
+                            Ok(Response::builder()
+                                .status(200)
+                                .header("Content-Type", EVENT_STREAM_MIME_TYPE)
+                                .body(AsyncBody::from_reader(rx.into_async_read()))?)
+                        }
+                        ("POST", "/messages") => {
+                            let mut body = String::new();
+                            request.body_mut().read_to_string(&mut body).await?;
+                            let message: serde_json::Value = serde_json::from_str(&body)?;
+                            let mut state = state.lock();
+                            let method = message["method"].as_str().unwrap_or_default();
+                            state.methods.push(method.to_string());
+                            if let Some(id) = message.get("id") {
+                                let result = match method {
+                                    "initialize" => json!({
+                                        "protocolVersion": types::LATEST_PROTOCOL_VERSION,
+                                        "capabilities": {},
+                                        "serverInfo": { "name": "sse-server", "version": "1.0.0" },
+                                    }),
+                                    _ if state.unresponsive => {
+                                        return Ok(Response::builder()
+                                            .status(202)
+                                            .body(AsyncBody::empty())?);
+                                    }
+                                    _ => json!({}),
+                                };
+                                let response =
+                                    json!({ "jsonrpc": "2.0", "id": id, "result": result });
+                                state.last_event_id += 1;
+                                let event = format!(
+                                    "event: message\nid: {}\ndata: {response}\n\n",
+                                    state.last_event_id
+                                );
+                                state
+                                    .events_tx
+                                    .as_ref()
+                                    .context("event stream not open")?
+                                    .unbounded_send(Ok(event.into_bytes()))?;
+                            }
+                            Ok(Response::builder().status(202).body(AsyncBody::empty())?)
+                        }
+                        _ => Ok(Response::builder().status(405).body(AsyncBody::empty())?),
+                    }
+                }
+            }
+        });
+        (http_client, state)
+    }
+
+    async fn start_server(
+        transport: ContextServerHttpTransport,
+        cx: &mut TestAppContext,
+    ) -> ContextServer {
+        let server = ContextServer::http(
+            ContextServerId("sse".into()),
+            &Url::parse("http://test.example/sse").unwrap(),
+            HashMap::default(),
+            transport,
+            fake_sse_server(),
+            cx.executor(),
+        )
+        .unwrap();
+        server.start(&cx.to_async()).await.unwrap();
+        server
+    }
+
+    #[gpui::test]
+    async fn test_sse_transport(cx: &mut TestAppContext) {
+        let server = start_server(ContextServerHttpTransport::Sse, cx).await;
+        let client = server.client().unwrap();
+        assert_eq!(client.initialize.server_info.name, "sse-server");
+        server.ping(Duration::from_secs(1)).await.unwrap();
+    }
+
+    #[gpui::test]
+    async fn test_auto_transport_falls_back_to_sse(cx: &mut TestAppContext) {
+        let server = start_server(ContextServerHttpTransport::Auto, cx).await;
+        let client = server.client().unwrap();
+        assert_eq!(client.initialize.server_info.name, "sse-server");
+        server.ping(Duration::from_secs(1)).await.unwrap();
+    }
+
+    /// Wraps [`fake_sse_server`], recording the `Authorization` header of every request.
+    fn recording_sse_server() -> (Arc<dyn HttpClient>, Arc<Mutex<Vec<Option<String>>>>) {
+        let sse_server = fake_sse_server();
+        let authorizations = Arc::new(Mutex::new(Vec::new()));
+        let http_client = FakeHttpClient::create({
+            let authorizations = authorizations.clone();
+            move |request| {
+                let authorization = request
+                    .headers()
+                    .get("Authorization")
+                    .map(|value| value.to_str().unwrap().to_string());
+                authorizations.lock().push(authorization);
+                let sse_server = sse_server.clone();
+                async move { sse_server.send(request).await }
+            }
+        });
+        (http_client, authorizations)
+    }
+
+    #[gpui::test]
+    async fn test_header_placeholders(cx: &mut TestAppContext) {
+        let (http_client, authorizations) = recording_sse_server();
+        let create_server = |authorization: &str| {
+            ContextServer::http(
+                ContextServerId("sse".into()),
+                &Url::parse("http://test.example/sse").unwrap(),
+                HashMap::from_iter([("Authorization".into(), authorization.into())]),
+                ContextServerHttpTransport::Sse,
+                http_client.clone(),
+                cx.executor(),
+            )
+        };
+
+        let server = create_server("Bearer ${env:CARGO_PKG_NAME} $${env:CARGO_PKG_NAME}").unwrap();
+        server.start(&cx.to_async()).await.unwrap();
+        let expected = format!("Bearer {} ${{env:CARGO_PKG_NAME}}", env!("CARGO_PKG_NAME"));
+        let authorizations = mem::take(&mut *authorizations.lock());
+        assert!(!authorizations.is_empty());
+        for authorization in authorizations {
+            assert_eq!(authorization, Some(expected.clone()));
+        }
+
+        let server = create_server("Bearer ${secret:zed-test-missing}").unwrap();
+        let error = server.start(&cx.to_async()).await.unwrap_err().to_string();
+        assert_eq!(
+            error,
+            "invalid header Authorization for context server sse: \
+             secret zed-test-missing is not in the credential store"
+        );
+
+        let error = create_server("Bearer ${env:TOKEN")
+            .err()
+            .unwrap()
+            .to_string();
+        assert_eq!(error, "invalid value for header Authorization");
+    }
+
+    struct FakeHeaderProvider {
+        requests: AtomicUsize,
+        fail: bool,
+    }
+
+    #[async_trait]
+    impl HeaderProvider for FakeHeaderProvider {
+        fn name(&self) -> String {
+            "fake provider".into()
+        }
+
+        async fn headers(&self) -> Result<HashMap<String, String>> {
+            if self.fail {
+                anyhow::bail!("token expired");
+            }
+            let request = self.requests.fetch_add(1, SeqCst) + 1;
+            Ok(HashMap::from_iter([(
+                "Authorization".to_string(),
+                format!("Bearer {request}"),
+            )]))
+        }
+    }
+
+    #[gpui::test]
+    async fn test_header_provider(cx: &mut TestAppContext) {
+        let (http_client, authorizations) = recording_sse_server();
+        let create_server = |fail: bool| {
+            ContextServer::http(
+                ContextServerId("sse".into()),
+                &Url::parse("http://test.example/sse").unwrap(),
+                HashMap::from_iter([("authorization".into(), "Bearer static".into())]),
+                ContextServerHttpTransport::Sse,
+                http_client.clone(),
+                cx.executor(),
+            )
+            .unwrap()
+            .with_header_provider(Arc::new(FakeHeaderProvider {
+                requests: AtomicUsize::new(0),
+                fail,
+            }))
+        };
+
+        let server = create_server(false);
+        server.start(&cx.to_async()).await.unwrap();
+        server.ping(Duration::from_secs(1)).await.unwrap();
+        let sent = mem::take(&mut *authorizations.lock());
+        assert!(!sent.is_empty());
+        assert!(
+            sent.iter()
+                .all(|authorization| authorization.as_deref() == Some("Bearer 1")),
+            "{sent:?}"
+        );
+
+        cx.executor().advance_clock(crate::HEADER_REFRESH_INTERVAL);
+        cx.run_until_parked();
+        server.ping(Duration::from_secs(1)).await.unwrap();
+        assert_eq!(
+            mem::take(&mut *authorizations.lock()),
+            vec![Some("Bearer 2".to_string())]
+        );
+
+        let server = create_server(true);
+        let error = server.start(&cx.to_async()).await.unwrap_err().to_string();
+        assert_eq!(
+            error,
+            "fake provider failed for context server sse: token expired"
+        );
+        assert_eq!(server.status(), crate::ServerStatus::AuthRequired);
+        assert!(authorizations.lock().is_empty());
+    }
+
+    #[gpui::test]
+    async fn test_sse_reconnection(cx: &mut TestAppContext) {
+        let (http_client, state) = fake_sse_server_with_state();
+        let server = Arc::new(
+            ContextServer::http(
+                ContextServerId("sse".into()),
+                &Url::parse("http://test.example/sse").unwrap(),
+                HashMap::default(),
+                ContextServerHttpTransport::Sse,
+                http_client,
+                cx.executor(),
+            )
+            .unwrap()
+            .with_reconnect_timeout(Duration::from_secs(10)),
+        );
+        server.start(&cx.to_async()).await.unwrap();
+        server.ping(Duration::from_secs(1)).await.unwrap();
+
+        // Requests in flight when the stream is lost fail, since their responses may be lost.
+        state.lock().unresponsive = true;
+        let ping = cx.foreground_executor().spawn({
+            let server = server.clone();
+            async move { server.ping(Duration::from_secs(60)).await }
+        });
+        cx.run_until_parked();
+        let last_event_id = Some(state.lock().last_event_id.to_string());
+        state.lock().events_tx.take();
+        cx.run_until_parked();
+        let error = ping.await.unwrap_err();
+        assert!(error.is::<ConnectionInterrupted>(), "{error:#}");
+
+        // Requests made while reconnecting wait for the stream to be reestablished.
+        {
+            let mut state = state.lock();
+            state.unavailable = true;
+            state.unresponsive = false;
+        }
+        let ping = cx.foreground_executor().spawn({
+            let server = server.clone();
+            async move { server.ping(Duration::from_secs(60)).await }
+        });
+        cx.executor().advance_clock(INITIAL_RECONNECT_DELAY);
+        cx.run_until_parked();
+        state.lock().unavailable = false;
+        cx.executor().advance_clock(INITIAL_RECONNECT_DELAY * 2);
+        cx.run_until_parked();
+        ping.await.unwrap();
+
+        {
+            let state = state.lock();
+            assert_eq!(
+                state.last_event_ids,
+                vec![None, last_event_id.clone(), last_event_id]
+            );
+            // The server started a new session, which was initialized again before the ping.
+            assert_eq!(
+                state.methods[state.methods.len() - 3..],
+                ["initialize", "notifications/initialized", "ping"]
+            );
+        }
+        let connection_logs = server
+            .recent_logs()
+            .into_iter()
+            .filter(|entry| entry.logger.as_deref() == Some(crate::CONNECTION_LOGGER))
+            .map(|entry| (entry.level, entry.data))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            connection_logs,
+            vec![
+                (
+                    types::LoggingLevel::Warning,
+                    json!("connection interrupted, reconnecting")
+                ),
+                (
+                    types::LoggingLevel::Info,
+                    json!("reconnected after 2 attempts")
+                ),
+            ]
+        );
+
+        // The transport gives up once it failed to reconnect for the configured timeout.
+        state.lock().unavailable = true;
+        state.lock().events_tx.take();
+        cx.run_until_parked();
+        for _ in 0..200 {
+            cx.executor().advance_clock(Duration::from_millis(100));
+            cx.run_until_parked();
+        }
+        assert!(server.ping(Duration::from_secs(1)).await.is_err());
+        assert!(
+            server.recent_logs().iter().any(|entry| entry
+                .data
+                .as_str()
+                .is_some_and(|data| data.contains("gave up reconnecting after 4 attempts"))),
+            "{:?}",
+            server.recent_logs()
+        );
+    }
+}
@@ -1,34 +1,76 @@
 use std::path::PathBuf;
 use std::pin::Pin;
+use std::process::ExitStatus;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::time::Duration;
 
 use anyhow::{Context as _, Result};
 use async_trait::async_trait;
 use futures::io::{BufReader, BufWriter};
 use futures::{
-    AsyncBufReadExt as _, AsyncRead, AsyncWrite, AsyncWriteExt as _, Stream, StreamExt as _,
+    AsyncBufRead, AsyncBufReadExt as _, AsyncRead, AsyncWrite, AsyncWriteExt as _, Stream,
+    StreamExt as _,
 };
-use gpui::AsyncApp;
+use gpui::{AsyncApp, Task};
+use parking_lot::Mutex;
 use smol::channel;
 use smol::process::Child;
 use util::TryFutureExt as _;
 
 use crate::client::ModelContextServerBinary;
-use crate::transport::Transport;
+#[cfg(windows)]
+use crate::resource_limits::ResourceLimits;
+use crate::transport::{MessageTooLarge, RejectedMessage, Shutdown, Transport, response_id};
+
+/// How often to check whether the server exited while shutting it down.
+const EXIT_POLL_INTERVAL: Duration = Duration::from_millis(20);
+/// How many bytes from each end of an oversized line are kept to find the message's ID.
+const REJECTED_LINE_EDGE_BYTES: usize = 1024;
+
+/// A line read from the server's stdout by [`read_line`].
+#[derive(Debug, PartialEq)]
+enum Line {
+    Message(String),
+    TooLarge(RejectedMessage),
+}
 
 pub struct StdioTransport {
     stdout_sender: channel::Sender<String>,
     stdin_receiver: channel::Receiver<String>,
     stderr_receiver: channel::Receiver<String>,
-    server: Child,
+    rejected_receiver: channel::Receiver<RejectedMessage>,
+    /// Closed once the server closes its stdout, which it does when it exits.
+    stdout_closed: channel::Receiver<()>,
+    server: Mutex<Child>,
+    pid: u32,
+    /// Whether the processes the server started were killed after it exited.
+    descendants_killed: AtomicBool,
+    /// The job the server's process is assigned to, which is how the processes it starts are
+    /// stopped along with it on Windows. Elsewhere, the server leads its own process group.
+    #[cfg(windows)]
+    job: Option<Job>,
+    /// Reads stderr until the server closes it. Kept here rather than detached so that a
+    /// descendant process holding the pipe open can't outlive the transport.
+    _stderr_task: Task<()>,
 }
 
 impl StdioTransport {
+    /// Starts the server, dropping the messages it sends that are larger than
+    /// `max_message_bytes` without reading them into memory.
     pub fn new(
         binary: ModelContextServerBinary,
         working_directory: &Option<PathBuf>,
+        max_message_bytes: usize,
         cx: &AsyncApp,
     ) -> Result<Self> {
-        let mut command = util::command::new_smol_command(&binary.executable);
+        let mut command = util::command::new_std_command(&binary.executable);
+        // Servers are often started through wrappers like `npx` or `uv run`, which run the
+        // actual server as a child of their own. Starting a new session makes the server the
+        // leader of a process group that those children belong to as well.
+        util::set_pre_exec_to_start_new_session(&mut command);
+        #[cfg(unix)]
+        binary.resource_limits.apply_to_command(&mut command);
+        let mut command = smol::process::Command::from(command);
         command
             .args(&binary.args)
             .envs(binary.env.unwrap_or_default())
@@ -43,7 +85,11 @@ impl StdioTransport {
 
         let mut server = command
             .spawn()
-            .with_context(|| format!("failed to spawn command {command:?})",))?;
+            .with_context(|| format!("failed to spawn command {:?}", binary.executable))?;
+        #[cfg(windows)]
+        let job = Job::new(&server, &binary.resource_limits)
+            .context("failed to create a job for the context server")
+            .log_err();
 
         let stdin = server.stdin.take().unwrap();
         let stdout = server.stdout.take().unwrap();
@@ -52,38 +98,52 @@ impl StdioTransport {
         let (stdin_sender, stdin_receiver) = channel::unbounded::<String>();
         let (stdout_sender, stdout_receiver) = channel::unbounded::<String>();
         let (stderr_sender, stderr_receiver) = channel::unbounded::<String>();
+        let (rejected_sender, rejected_receiver) = channel::unbounded::<RejectedMessage>();
+        let (stdout_closed_tx, stdout_closed) = channel::bounded::<()>(1);
 
         cx.spawn(async move |_| Self::handle_output(stdin, stdout_receiver).log_err().await)
             .detach();
 
-        cx.spawn(async move |_| Self::handle_input(stdout, stdin_sender).await)
-            .detach();
+        cx.spawn(async move |_| {
+            Self::handle_input(stdout, stdin_sender, rejected_sender, max_message_bytes).await;
+            drop(stdout_closed_tx);
+        })
+        .detach();
 
-        cx.spawn(async move |_| Self::handle_err(stderr, stderr_sender).await)
-            .detach();
+        let stderr_task = cx.spawn(async move |_| Self::handle_err(stderr, stderr_sender).await);
 
         Ok(Self {
             stdout_sender,
             stdin_receiver,
             stderr_receiver,
-            server,
+            rejected_receiver,
+            stdout_closed,
+            pid: server.id(),
+            server: Mutex::new(server),
+            descendants_killed: AtomicBool::new(false),
+            #[cfg(windows)]
+            job,
+            _stderr_task: stderr_task,
         })
     }
 
-    async fn handle_input<Stdout>(stdin: Stdout, inbound_rx: channel::Sender<String>)
-    where
+    async fn handle_input<Stdout>(
+        stdin: Stdout,
+        inbound_rx: channel::Sender<String>,
+        rejected_tx: channel::Sender<RejectedMessage>,
+        max_message_bytes: usize,
+    ) where
         Stdout: AsyncRead + Unpin + Send + 'static,
     {
         let mut stdin = BufReader::new(stdin);
-        let mut line = String::new();
-        while let Ok(n) = stdin.read_line(&mut line).await {
-            if n == 0 {
-                break;
-            }
-            if inbound_rx.send(line.clone()).await.is_err() {
+        while let Ok(Some(line)) = read_line(&mut stdin, max_message_bytes).await {
+            let sent = match line {
+                Line::Message(message) => inbound_rx.send(message).await.is_ok(),
+                Line::TooLarge(rejected) => rejected_tx.send(rejected).await.is_ok(),
+            };
+            if !sent {
                 break;
             }
-            line.clear();
         }
     }
 
@@ -124,6 +184,177 @@ impl StdioTransport {
     }
 }
 
+/// Reads the next line, or `None` at the end of the input. Of lines longer than `limit`,
+/// only the bytes needed to find the message's ID are kept.
+async fn read_line<R: AsyncBufRead + Unpin>(
+    reader: &mut R,
+    limit: usize,
+) -> std::io::Result<Option<Line>> {
+    // Lines are kept whole while they may fit, leaving room for a trailing "\r\n".
+    let max_line_bytes = limit.saturating_add(2);
+    let mut line = Vec::new();
+    let mut tail = Vec::new();
+    let mut size = 0;
+    loop {
+        let available = reader.fill_buf().await?;
+        if available.is_empty() {
+            if size == 0 {
+                return Ok(None);
+            }
+            break;
+        }
+        let (chunk, done) = match available.iter().position(|byte| *byte == b'\n') {
+            Some(ix) => (&available[..=ix], true),
+            None => (available, false),
+        };
+        let chunk_len = chunk.len();
+        size += chunk_len;
+        if size <= max_line_bytes {
+            line.extend_from_slice(chunk);
+        } else {
+            line.truncate(REJECTED_LINE_EDGE_BYTES);
+            tail.extend_from_slice(chunk);
+            if tail.len() > 2 * REJECTED_LINE_EDGE_BYTES {
+                tail.drain(..tail.len() - REJECTED_LINE_EDGE_BYTES);
+            }
+        }
+        reader.consume_unpin(chunk_len);
+        if done {
+            break;
+        }
+    }
+
+    let message_size = if size <= max_line_bytes {
+        line.trim_ascii_end().len()
+    } else {
+        size - (tail.len() - tail.trim_ascii_end().len())
+    };
+    if message_size <= limit {
+        let line = String::from_utf8(line)
+            .map_err(|error| std::io::Error::new(std::io::ErrorKind::InvalidData, error))?;
+        return Ok(Some(Line::Message(line)));
+    }
+    let head = &line[..line.len().min(REJECTED_LINE_EDGE_BYTES)];
+    let tail = if tail.is_empty() {
+        &line[line.len().saturating_sub(REJECTED_LINE_EDGE_BYTES)..]
+    } else {
+        &tail
+    };
+    Ok(Some(Line::TooLarge(RejectedMessage {
+        response_to: response_id(head, tail),
+        error: MessageTooLarge {
+            size: message_size,
+            limit,
+        },
+    })))
+}
+
+impl StdioTransport {
+    /// Waits up to `timeout` for the server to exit, returning whether it did.
+    ///
+    /// This polls with a real timer, since it waits on an actual process.
+    async fn wait_for_exit(&self, timeout: Duration) -> Result<bool> {
+        let mut waited = Duration::ZERO;
+        loop {
+            if self.has_exited()? {
+                return Ok(true);
+            }
+            if waited >= timeout {
+                return Ok(false);
+            }
+            smol::Timer::after(EXIT_POLL_INTERVAL).await;
+            waited += EXIT_POLL_INTERVAL;
+        }
+    }
+
+    /// Whether the server exited. The processes it started are killed once it's found to
+    /// have exited, since a wrapper may exit without stopping the server it started, which
+    /// would otherwise keep running, holding on to its ports and files.
+    fn has_exited(&self) -> Result<bool> {
+        let exited = self.server.lock().try_status()?.is_some();
+        // The process group ID may be reused by now, but only once the group is empty.
+        if exited && !self.descendants_killed.swap(true, Ordering::SeqCst) {
+            self.kill_descendants();
+        }
+        Ok(exited)
+    }
+
+    /// Sends SIGTERM to the server and the processes it started.
+    #[cfg(not(windows))]
+    fn terminate(&self) {
+        unsafe {
+            libc::killpg(self.pid as i32, libc::SIGTERM);
+        }
+    }
+
+    /// Kills the server along with the processes it started.
+    fn kill_descendants(&self) {
+        #[cfg(not(windows))]
+        unsafe {
+            libc::killpg(self.pid as i32, libc::SIGKILL);
+        }
+        #[cfg(windows)]
+        if let Some(job) = &self.job {
+            job.terminate().log_err();
+        }
+    }
+}
+
+/// A Job Object that kills the processes assigned to it once it's closed.
+#[cfg(windows)]
+struct Job(windows::Win32::Foundation::HANDLE);
+
+// The handle is only used to terminate and close the job, which may happen on any thread.
+#[cfg(windows)]
+unsafe impl Send for Job {}
+#[cfg(windows)]
+unsafe impl Sync for Job {}
+
+#[cfg(windows)]
+impl Job {
+    /// Creates a job for the process, with the given limits. The processes it starts from
+    /// then on belong to the job as well.
+    fn new(process: &Child, limits: &ResourceLimits) -> Result<Self> {
+        use std::os::windows::io::AsRawHandle as _;
+        use windows::Win32::Foundation::HANDLE;
+        use windows::Win32::System::JobObjects::{
+            AssignProcessToJobObject, CreateJobObjectW, JOB_OBJECT_LIMIT_KILL_ON_JOB_CLOSE,
+            JOBOBJECT_EXTENDED_LIMIT_INFORMATION, JobObjectExtendedLimitInformation,
+            SetInformationJobObject,
+        };
+        use windows::core::PCWSTR;
+
+        unsafe {
+            let job = Self(CreateJobObjectW(None, PCWSTR::null())?);
+            let mut info = JOBOBJECT_EXTENDED_LIMIT_INFORMATION::default();
+            info.BasicLimitInformation.LimitFlags = JOB_OBJECT_LIMIT_KILL_ON_JOB_CLOSE;
+            limits.apply_to_job(&mut info);
+            SetInformationJobObject(
+                job.0,
+                JobObjectExtendedLimitInformation,
+                &info as *const _ as *const std::ffi::c_void,
+                std::mem::size_of_val(&info) as u32,
+            )?;
+            AssignProcessToJobObject(job.0, HANDLE(process.as_raw_handle() as _))?;
+            Ok(job)
+        }
+    }
+
+    fn terminate(&self) -> Result<()> {
+        unsafe { windows::Win32::System::JobObjects::TerminateJobObject(self.0, 1)? };
+        Ok(())
+    }
+}
+
+#[cfg(windows)]
+impl Drop for Job {
+    fn drop(&mut self) {
+        unsafe {
+            windows::Win32::Foundation::CloseHandle(self.0).ok();
+        }
+    }
+}
+
 #[async_trait]
 impl Transport for StdioTransport {
     async fn send(&self, message: String) -> Result<()> {
@@ -137,10 +368,165 @@ impl Transport for StdioTransport {
     fn receive_err(&self) -> Pin<Box<dyn Stream<Item = String> + Send>> {
         Box::pin(self.stderr_receiver.clone())
     }
+
+    fn rejected_messages(&self) -> Pin<Box<dyn Stream<Item = RejectedMessage> + Send>> {
+        Box::pin(self.rejected_receiver.clone())
+    }
+
+    async fn shutdown(&self, grace_period: Duration) -> Result<Shutdown> {
+        // Closing the channel ends the task writing to the server's stdin, which closes it.
+        self.stdout_sender.close();
+        if self.wait_for_exit(grace_period).await? {
+            return Ok(Shutdown::Exited);
+        }
+
+        #[cfg(not(windows))]
+        {
+            self.terminate();
+            if self.wait_for_exit(grace_period).await? {
+                return Ok(Shutdown::Terminated);
+            }
+        }
+
+        self.kill_descendants();
+        self.server
+            .lock()
+            .kill()
+            .context("failed to kill context server")?;
+        // Reap the process so it doesn't linger as a zombie.
+        self.wait_for_exit(grace_period).await?;
+        Ok(Shutdown::Killed)
+    }
+
+    async fn closed(&self) {
+        // Nothing is ever sent, so this only returns once the sender is dropped.
+        self.stdout_closed.recv().await.ok();
+    }
+
+    async fn exit_status(&self, timeout: Duration) -> Option<ExitStatus> {
+        self.wait_for_exit(timeout).await.ok()?;
+        self.server.lock().try_status().ok().flatten()
+    }
 }
 
 impl Drop for StdioTransport {
     fn drop(&mut self) {
-        let _ = self.server.kill();
+        if !matches!(self.has_exited(), Ok(true)) {
+            self.kill_descendants();
+        }
+        let _ = self.server.get_mut().kill();
+    }
+}
+
+#[cfg(all(test, not(windows)))]
+mod tests {
+    use super::*;
+    use crate::client::RequestId;
+    use crate::transport::DEFAULT_MAX_MESSAGE_BYTES;
+    use gpui::TestAppContext;
+
+    const GRACE_PERIOD: Duration = Duration::from_millis(200);
+
+    fn binary(executable: &str, args: &[&str]) -> ModelContextServerBinary {
+        ModelContextServerBinary {
+            executable: executable.into(),
+            args: args.iter().map(|arg| arg.to_string()).collect(),
+            env: None,
+            timeout: None,
+            resource_limits: Default::default(),
+        }
+    }
+
+    fn ignore_stdin_eof(args: &[&str]) -> ModelContextServerBinary {
+        let mut script = PathBuf::from(env!("CARGO_MANIFEST_DIR"));
+        script.push("test_data");
+        script.push("ignore_stdin_eof.sh");
+        let script = script.to_string_lossy();
+        let mut args = args.to_vec();
+        args.insert(0, &script);
+        binary("sh", &args)
+    }
+
+    #[gpui::test]
+    async fn test_shutdown(cx: &mut TestAppContext) {
+        cx.executor().allow_parking();
+
+        let transport = StdioTransport::new(
+            binary("cat", &[]),
+            &None,
+            DEFAULT_MAX_MESSAGE_BYTES,
+            &cx.to_async(),
+        )
+        .unwrap();
+        assert_eq!(
+            transport.shutdown(GRACE_PERIOD).await.unwrap(),
+            Shutdown::Exited
+        );
+
+        let transport = StdioTransport::new(
+            binary("true", &[]),
+            &None,
+            DEFAULT_MAX_MESSAGE_BYTES,
+            &cx.to_async(),
+        )
+        .unwrap();
+        assert!(transport.wait_for_exit(GRACE_PERIOD).await.unwrap());
+        assert_eq!(
+            transport.shutdown(Duration::ZERO).await.unwrap(),
+            Shutdown::Exited
+        );
+
+        let transport = StdioTransport::new(
+            ignore_stdin_eof(&[]),
+            &None,
+            DEFAULT_MAX_MESSAGE_BYTES,
+            &cx.to_async(),
+        )
+        .unwrap();
+        assert_eq!(
+            transport.shutdown(GRACE_PERIOD).await.unwrap(),
+            Shutdown::Terminated
+        );
+        assert!(transport.server.lock().try_status().unwrap().is_some());
+
+        let transport = StdioTransport::new(
+            ignore_stdin_eof(&["--ignore-term"]),
+            &None,
+            DEFAULT_MAX_MESSAGE_BYTES,
+            &cx.to_async(),
+        )
+        .unwrap();
+        assert_eq!(
+            transport.shutdown(GRACE_PERIOD).await.unwrap(),
+            Shutdown::Killed
+        );
+        assert!(transport.server.lock().try_status().unwrap().is_some());
+    }
+
+    #[test]
+    fn test_read_line() {
+        let large = format!(
+            "{{\"jsonrpc\":\"2.0\",\"id\":2,\"result\":\"{}\"}}\r\n",
+            "x".repeat(10_000)
+        );
+        let input = format!("{{\"id\":1}}\n{large}{{\"id\":3}}\npartial");
+        // A small buffer makes the lines span many reads.
+        let mut reader = BufReader::with_capacity(16, futures::io::Cursor::new(input));
+        let mut next_line = || smol::block_on(read_line(&mut reader, 100)).unwrap();
+
+        assert_eq!(next_line(), Some(Line::Message("{\"id\":1}\n".to_string())));
+        assert_eq!(
+            next_line(),
+            Some(Line::TooLarge(RejectedMessage {
+                response_to: Some(RequestId::Int(2)),
+                error: MessageTooLarge {
+                    size: large.trim_end().len(),
+                    limit: 100,
+                },
+            }))
+        );
+        assert_eq!(next_line(), Some(Line::Message("{\"id\":3}\n".to_string())));
+        assert_eq!(next_line(), Some(Line::Message("partial".to_string())));
+        assert_eq!(next_line(), None);
     }
 }
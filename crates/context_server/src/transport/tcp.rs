@@ -0,0 +1,347 @@
+use std::net::SocketAddr;
+use std::pin::Pin;
+use std::sync::Arc;
+use std::time::Duration;
+
+use anyhow::{Context as _, Result, anyhow};
+use async_trait::async_trait;
+use futures::{
+    FutureExt as _, Stream,
+    channel::oneshot,
+    future::{self, Either, Shared},
+};
+use smol::channel;
+use tokio::io::{AsyncBufReadExt as _, AsyncRead, AsyncWrite, AsyncWriteExt as _, BufReader};
+use tokio::net::TcpSocket;
+use tokio_rustls::TlsConnector;
+
+use crate::transport::{Shutdown, Transport};
+
+const CONNECT_TIMEOUT: Duration = Duration::from_secs(10);
+
+enum Outgoing {
+    Message(String),
+    Close,
+}
+
+/// Sends newline-delimited JSON-RPC messages over a TCP connection, optionally wrapped in
+/// TLS.
+///
+/// Like [`WebSocketTransport`](crate::transport::WebSocketTransport), the connection runs
+/// on Tokio. TCP keepalive is enabled so that a server that silently went away is noticed.
+pub struct TcpTransport {
+    outgoing_tx: channel::Sender<Outgoing>,
+    response_rx: channel::Receiver<String>,
+    error_rx: channel::Receiver<String>,
+    closed: Shared<oneshot::Receiver<()>>,
+    connection: tokio::task::AbortHandle,
+}
+
+impl TcpTransport {
+    /// Connects to `host` and `port`, using TLS if a `tls_config` is given.
+    pub fn new(
+        host: String,
+        port: u16,
+        tls_config: Option<Arc<rustls::ClientConfig>>,
+        tokio: tokio::runtime::Handle,
+    ) -> Self {
+        let (outgoing_tx, outgoing_rx) = channel::unbounded();
+        let (response_tx, response_rx) = channel::unbounded();
+        let (error_tx, error_rx) = channel::unbounded();
+        let (closed_tx, closed_rx) = oneshot::channel();
+
+        let connection = tokio.spawn(async move {
+            let result = async {
+                let stream = connect(&host, port, tls_config.is_some()).await?;
+                if let Some(tls_config) = tls_config {
+                    let server_name = rustls_pki_types::ServerName::try_from(host.clone())
+                        .with_context(|| format!("invalid TLS server name {host:?}"))?;
+                    let connector = TlsConnector::from(tls_config);
+                    let stream = connector.connect(server_name, stream).await?;
+                    run(stream, outgoing_rx, response_tx).await
+                } else {
+                    run(stream, outgoing_rx, response_tx).await
+                }
+            };
+            if let Err(error) = result.await {
+                log::error!("TCP error: {error:#}");
+                error_tx.send(format!("TCP error: {error:#}")).await.ok();
+            }
+            closed_tx.send(()).ok();
+        });
+
+        Self {
+            outgoing_tx,
+            response_rx,
+            error_rx,
+            closed: closed_rx.shared(),
+            connection: connection.abort_handle(),
+        }
+    }
+}
+
+async fn connect(host: &str, port: u16, tls: bool) -> Result<tokio::net::TcpStream> {
+    let addresses = tokio::net::lookup_host((host, port))
+        .await
+        .with_context(|| format!("failed to resolve {host}:{port}"))?
+        .collect::<Vec<SocketAddr>>();
+    let address = *addresses
+        .first()
+        .with_context(|| format!("{host}:{port} did not resolve to any address"))?;
+    if !tls && !address.ip().is_loopback() {
+        log::warn!(
+            "context server at {host}:{port} isn't on this machine, but its traffic isn't \
+            encrypted. Use a tls:// url to connect to it over TLS."
+        );
+    }
+
+    let socket = match address {
+        SocketAddr::V4(_) => TcpSocket::new_v4()?,
+        SocketAddr::V6(_) => TcpSocket::new_v6()?,
+    };
+    socket.set_keepalive(true)?;
+    let timeout = smol::Timer::after(CONNECT_TIMEOUT);
+    match future::select(Box::pin(socket.connect(address)), timeout).await {
+        Either::Left((stream, _)) => {
+            let stream = stream.with_context(|| format!("failed to connect to {host}:{port}"))?;
+            stream.set_nodelay(true)?;
+            Ok(stream)
+        }
+        Either::Right(_) => Err(anyhow!(
+            "timed out connecting to {host}:{port} after {CONNECT_TIMEOUT:?}"
+        )),
+    }
+}
+
+async fn run<S>(
+    stream: S,
+    outgoing_rx: channel::Receiver<Outgoing>,
+    response_tx: channel::Sender<String>,
+) -> Result<()>
+where
+    S: AsyncRead + AsyncWrite + Unpin,
+{
+    let (reader, mut writer) = tokio::io::split(stream);
+    let mut lines = BufReader::new(reader).lines();
+    loop {
+        futures::select_biased! {
+            line = lines.next_line().fuse() => {
+                let Some(line) = line? else {
+                    return Ok(());
+                };
+                if !line.trim().is_empty() {
+                    response_tx.send(line).await.ok();
+                }
+            }
+            outgoing = outgoing_rx.recv().fuse() => match outgoing {
+                Ok(Outgoing::Message(message)) => {
+                    log::trace!("outgoing message: {}", message);
+                    writer.write_all(message.as_bytes()).await?;
+                    writer.write_all(b"\n").await?;
+                    writer.flush().await?;
+                }
+                Ok(Outgoing::Close) | Err(_) => {
+                    writer.shutdown().await?;
+                    // Wait for the server to close its end of the connection.
+                    while lines.next_line().await?.is_some() {}
+                    return Ok(());
+                }
+            },
+        }
+    }
+}
+
+#[async_trait]
+impl Transport for TcpTransport {
+    async fn send(&self, message: String) -> Result<()> {
+        self.outgoing_tx
+            .send(Outgoing::Message(message))
+            .await
+            .map_err(|_| anyhow!("TCP connection closed"))
+    }
+
+    fn receive(&self) -> Pin<Box<dyn Stream<Item = String> + Send>> {
+        Box::pin(self.response_rx.clone())
+    }
+
+    fn receive_err(&self) -> Pin<Box<dyn Stream<Item = String> + Send>> {
+        Box::pin(self.error_rx.clone())
+    }
+
+    async fn shutdown(&self, grace_period: Duration) -> Result<Shutdown> {
+        self.outgoing_tx.send(Outgoing::Close).await.ok();
+        let timeout = smol::Timer::after(grace_period);
+        match future::select(self.closed.clone(), timeout).await {
+            Either::Left(_) => Ok(Shutdown::Exited),
+            Either::Right(_) => {
+                self.connection.abort();
+                Ok(Shutdown::Killed)
+            }
+        }
+    }
+}
+
+impl Drop for TcpTransport {
+    fn drop(&mut self) {
+        self.connection.abort();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{ContextServer, ContextServerId, types};
+    use futures::StreamExt as _;
+    use gpui::TestAppContext;
+    use serde_json::{Value, json};
+    use tokio::net::TcpListener;
+    use url::Url;
+
+    /// Accepts a single connection and answers the JSON-RPC requests sent on it, one per
+    /// line.
+    async fn serve(listener: TcpListener) {
+        let (stream, _) = listener.accept().await.unwrap();
+        let (reader, mut writer) = tokio::io::split(stream);
+        let mut lines = BufReader::new(reader).lines();
+        while let Ok(Some(line)) = lines.next_line().await {
+            let message: Value = serde_json::from_str(&line).unwrap();
+            let Some(id) = message.get("id") else {
+                continue;
+            };
+            let result = match message["method"].as_str() {
+                Some("initialize") => json!({
+                    "protocolVersion": types::LATEST_PROTOCOL_VERSION,
+                    "capabilities": { "tools": {}, "logging": {} },
+                    "serverInfo": { "name": "tcp-server", "version": "1.0.0" },
+                }),
+                Some("tools/call") => {
+                    let notification = json!({
+                        "jsonrpc": "2.0",
+                        "method": "notifications/message",
+                        "params": { "level": "info", "data": "calling a tool" },
+                    });
+                    writer
+                        .write_all(format!("{notification}\n").as_bytes())
+                        .await
+                        .unwrap();
+                    json!({ "content": [{ "type": "text", "text": "done" }] })
+                }
+                _ => json!({}),
+            };
+            let response = json!({ "jsonrpc": "2.0", "id": id, "result": result });
+            writer
+                .write_all(format!("{response}\n").as_bytes())
+                .await
+                .unwrap();
+        }
+    }
+
+    fn start_fixture(runtime: &tokio::runtime::Runtime) -> u16 {
+        let listener = runtime.block_on(TcpListener::bind("127.0.0.1:0")).unwrap();
+        let port = listener.local_addr().unwrap().port();
+        runtime.spawn(serve(listener));
+        port
+    }
+
+    #[test]
+    fn test_tcp_transport() {
+        let runtime = tokio::runtime::Runtime::new().unwrap();
+        let port = start_fixture(&runtime);
+        let transport = TcpTransport::new("127.0.0.1".into(), port, None, runtime.handle().clone());
+
+        runtime.block_on(async {
+            let mut responses = transport.receive();
+            for id in 1..=2 {
+                let request = json!({ "jsonrpc": "2.0", "id": id, "method": "ping" });
+                transport.send(request.to_string()).await.unwrap();
+                let response: Value =
+                    serde_json::from_str(&responses.next().await.unwrap()).unwrap();
+                assert_eq!(
+                    response,
+                    json!({ "jsonrpc": "2.0", "id": id, "result": {} })
+                );
+            }
+
+            assert_eq!(
+                transport.shutdown(Duration::from_secs(1)).await.unwrap(),
+                Shutdown::Exited
+            );
+            assert_eq!(responses.next().await, None);
+        });
+    }
+
+    #[test]
+    fn test_tcp_transport_without_entered_runtime() {
+        let runtime = tokio::runtime::Runtime::new().unwrap();
+        let port = start_fixture(&runtime);
+
+        smol::block_on(async {
+            assert!(tokio::runtime::Handle::try_current().is_err());
+            let transport =
+                TcpTransport::new("127.0.0.1".into(), port, None, runtime.handle().clone());
+            let mut responses = transport.receive();
+            let request = json!({ "jsonrpc": "2.0", "id": 1, "method": "ping" });
+            transport.send(request.to_string()).await.unwrap();
+            let response: Value = serde_json::from_str(&responses.next().await.unwrap()).unwrap();
+            assert_eq!(response, json!({ "jsonrpc": "2.0", "id": 1, "result": {} }));
+            assert_eq!(
+                transport.shutdown(Duration::from_secs(1)).await.unwrap(),
+                Shutdown::Exited
+            );
+        });
+    }
+
+    #[test]
+    fn test_tcp_transport_connection_refused() {
+        let runtime = tokio::runtime::Runtime::new().unwrap();
+        // Bind and drop a listener to find a port nothing is listening on.
+        let port = runtime
+            .block_on(TcpListener::bind("127.0.0.1:0"))
+            .unwrap()
+            .local_addr()
+            .unwrap()
+            .port();
+        let transport = TcpTransport::new("127.0.0.1".into(), port, None, runtime.handle().clone());
+
+        runtime.block_on(async {
+            let error = transport.receive_err().next().await.unwrap();
+            assert!(
+                error.starts_with(&format!("TCP error: failed to connect to 127.0.0.1:{port}")),
+                "{error}"
+            );
+            assert!(transport.send("{}".into()).await.is_err());
+        });
+    }
+
+    #[gpui::test]
+    async fn test_tcp_context_server(cx: &mut TestAppContext) {
+        cx.executor().allow_parking();
+        let runtime = tokio::runtime::Runtime::new().unwrap();
+        let port = start_fixture(&runtime);
+
+        let url = Url::parse(&format!("tcp://127.0.0.1:{port}")).unwrap();
+        let server = ContextServer::tcp(
+            ContextServerId("tcp".into()),
+            &url,
+            crate::tls::client_config(None).unwrap(),
+            runtime.handle().clone(),
+        )
+        .unwrap();
+        // Nothing is run on the runtime the test created but the connection.
+        assert!(tokio::runtime::Handle::try_current().is_err());
+        let mut logs = server.log_entries();
+        server.start(&cx.to_async()).await.unwrap();
+        let client = server.client().unwrap();
+        assert_eq!(client.initialize.server_info.name, "tcp-server");
+
+        let params = types::CallToolParams {
+            name: "search".to_string(),
+            arguments: None,
+            meta: None,
+        };
+        let result = server.call_tool(params, None, None).await.unwrap();
+        assert_eq!(result.text(), "done");
+        assert_eq!(logs.next().await.unwrap().data, json!("calling a tool"));
+        assert_eq!(server.stop().await.unwrap(), Shutdown::Exited);
+    }
+}
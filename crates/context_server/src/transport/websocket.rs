@@ -0,0 +1,359 @@
+use std::pin::Pin;
+use std::sync::Arc;
+use std::time::Duration;
+
+use anyhow::{Context as _, Result, anyhow};
+use async_trait::async_trait;
+use async_tungstenite::tungstenite::{
+    Message,
+    client::IntoClientRequest as _,
+    http::{HeaderName, HeaderValue},
+    protocol::{CloseFrame, frame::coding::CloseCode},
+};
+use collections::HashMap;
+use futures::{
+    FutureExt as _, SinkExt as _, Stream, StreamExt as _,
+    channel::oneshot,
+    future::{self, Either, Shared},
+};
+use smol::channel;
+use tokio::net::TcpStream;
+use url::Url;
+
+use crate::transport::{Shutdown, Transport};
+
+const PING_INTERVAL: Duration = Duration::from_secs(30);
+/// How many pings in a row may go by without the server sending anything before the
+/// connection is considered dead.
+const MAX_MISSED_PINGS: u32 = 2;
+
+enum Outgoing {
+    Message(String),
+    Close,
+}
+
+/// Sends each JSON-RPC message as a text frame over a WebSocket.
+///
+/// The connection runs on Tokio, like the connection to Zed's collaboration server. It is
+/// kept alive with pings, and is dropped when the server stops answering them.
+///
+/// Only the task running the connection uses Tokio, on the runtime the transport is given.
+/// Messages are passed to and from it over channels, so the transport can be used from any
+/// executor, without a Tokio runtime being entered.
+pub struct WebSocketTransport {
+    outgoing_tx: channel::Sender<Outgoing>,
+    response_rx: channel::Receiver<String>,
+    error_rx: channel::Receiver<String>,
+    closed: Shared<oneshot::Receiver<()>>,
+    connection: tokio::task::AbortHandle,
+}
+
+impl WebSocketTransport {
+    pub fn new(
+        url: Url,
+        headers: HashMap<String, String>,
+        tls_config: Arc<rustls::ClientConfig>,
+        tokio: tokio::runtime::Handle,
+    ) -> Self {
+        let (outgoing_tx, outgoing_rx) = channel::unbounded();
+        let (response_tx, response_rx) = channel::unbounded();
+        let (error_tx, error_rx) = channel::unbounded();
+        let (closed_tx, closed_rx) = oneshot::channel();
+
+        let connection = tokio.spawn(async move {
+            if let Err(error) = Self::run(
+                url,
+                headers,
+                tls_config,
+                outgoing_rx,
+                response_tx,
+                error_tx.clone(),
+            )
+            .await
+            {
+                log::error!("WebSocket error: {error:#}");
+                error_tx
+                    .send(format!("WebSocket error: {error:#}"))
+                    .await
+                    .ok();
+            }
+            closed_tx.send(()).ok();
+        });
+
+        Self {
+            outgoing_tx,
+            response_rx,
+            error_rx,
+            closed: closed_rx.shared(),
+            connection: connection.abort_handle(),
+        }
+    }
+
+    async fn run(
+        url: Url,
+        headers: HashMap<String, String>,
+        tls_config: Arc<rustls::ClientConfig>,
+        outgoing_rx: channel::Receiver<Outgoing>,
+        response_tx: channel::Sender<String>,
+        error_tx: channel::Sender<String>,
+    ) -> Result<()> {
+        let mut request = url.as_str().into_client_request()?;
+        for (name, value) in &headers {
+            request.headers_mut().insert(
+                HeaderName::from_bytes(name.as_bytes())?,
+                HeaderValue::from_str(value)?,
+            );
+        }
+        let host = url.host_str().context("missing host in WebSocket url")?;
+        let port = url
+            .port_or_known_default()
+            .context("missing port in WebSocket url")?;
+        let stream = TcpStream::connect((host, port)).await?;
+        let (socket, _) = async_tungstenite::tokio::client_async_tls_with_connector_and_config(
+            request,
+            stream,
+            Some(tls_config.into()),
+            None,
+        )
+        .await?;
+        log::debug!("connected to WebSocket {url}");
+
+        let (mut sink, mut stream) = socket.split();
+        let mut pings = smol::Timer::interval(PING_INTERVAL);
+        let mut missed_pings = 0;
+        loop {
+            futures::select_biased! {
+                message = stream.next().fuse() => {
+                    let Some(message) = message else {
+                        return Ok(());
+                    };
+                    missed_pings = 0;
+                    match message? {
+                        Message::Text(text) => {
+                            response_tx.send(text.as_str().to_owned()).await.ok();
+                        }
+                        Message::Binary(bytes) => match String::from_utf8(bytes.to_vec()) {
+                            Ok(text) => {
+                                response_tx.send(text).await.ok();
+                            }
+                            Err(_) => log::warn!("ignoring binary WebSocket message that isn't UTF-8"),
+                        },
+                        Message::Close(frame) => {
+                            if let Some(error) = close_error(frame) {
+                                error_tx.send(error).await.ok();
+                            }
+                            return Ok(());
+                        }
+                        // Pings are answered by tungstenite, and any frame counts as a pong.
+                        Message::Ping(_) | Message::Pong(_) | Message::Frame(_) => {}
+                    }
+                }
+                outgoing = outgoing_rx.recv().fuse() => match outgoing {
+                    Ok(Outgoing::Message(message)) => sink.send(Message::text(message)).await?,
+                    Ok(Outgoing::Close) | Err(_) => {
+                        sink.send(Message::Close(Some(CloseFrame {
+                            code: CloseCode::Normal,
+                            reason: "".into(),
+                        })))
+                        .await?;
+                        // Wait for the server to acknowledge the close.
+                        while let Some(message) = stream.next().await {
+                            if let Message::Close(_) = message? {
+                                break;
+                            }
+                        }
+                        return Ok(());
+                    }
+                },
+                _ = pings.next().fuse() => {
+                    if missed_pings >= MAX_MISSED_PINGS {
+                        anyhow::bail!("server stopped answering pings");
+                    }
+                    missed_pings += 1;
+                    sink.send(Message::Ping(Default::default())).await?;
+                }
+            }
+        }
+    }
+}
+
+/// Describes why the server closed the connection, unless it closed it normally.
+fn close_error(frame: Option<CloseFrame>) -> Option<String> {
+    let frame = frame?;
+    match frame.code {
+        CloseCode::Normal | CloseCode::Away => None,
+        code if frame.reason.is_empty() => {
+            Some(format!("WebSocket closed with code {}", u16::from(code)))
+        }
+        code => Some(format!(
+            "WebSocket closed with code {}: {}",
+            u16::from(code),
+            frame.reason.as_str()
+        )),
+    }
+}
+
+#[async_trait]
+impl Transport for WebSocketTransport {
+    async fn send(&self, message: String) -> Result<()> {
+        self.outgoing_tx
+            .send(Outgoing::Message(message))
+            .await
+            .map_err(|_| anyhow!("WebSocket connection closed"))
+    }
+
+    fn receive(&self) -> Pin<Box<dyn Stream<Item = String> + Send>> {
+        Box::pin(self.response_rx.clone())
+    }
+
+    fn receive_err(&self) -> Pin<Box<dyn Stream<Item = String> + Send>> {
+        Box::pin(self.error_rx.clone())
+    }
+
+    async fn shutdown(&self, grace_period: Duration) -> Result<Shutdown> {
+        self.outgoing_tx.send(Outgoing::Close).await.ok();
+        let timeout = smol::Timer::after(grace_period);
+        match future::select(self.closed.clone(), timeout).await {
+            Either::Left(_) => Ok(Shutdown::Exited),
+            Either::Right(_) => {
+                self.connection.abort();
+                Ok(Shutdown::Killed)
+            }
+        }
+    }
+}
+
+impl Drop for WebSocketTransport {
+    fn drop(&mut self) {
+        self.connection.abort();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{ContextServer, ContextServerId, types};
+    use async_tungstenite::tungstenite::handshake::server::{ErrorResponse, Request, Response};
+    use gpui::TestAppContext;
+    use serde_json::{Value, json};
+    use tokio::net::TcpListener;
+
+    /// Accepts a single connection, answering JSON-RPC requests and sending the headers of
+    /// the handshake to `headers_tx`. A `test/close` request makes the server close the
+    /// connection with code 4000.
+    async fn serve(listener: TcpListener, headers_tx: oneshot::Sender<HashMap<String, String>>) {
+        let (stream, _) = listener.accept().await.unwrap();
+        let mut socket = async_tungstenite::tokio::accept_hdr_async(
+            stream,
+            |request: &Request, response: Response| -> Result<Response, ErrorResponse> {
+                let headers = request
+                    .headers()
+                    .iter()
+                    .map(|(name, value)| {
+                        (name.to_string(), value.to_str().unwrap_or("").to_string())
+                    })
+                    .collect();
+                headers_tx.send(headers).ok();
+                Ok(response)
+            },
+        )
+        .await
+        .unwrap();
+
+        while let Some(Ok(message)) = socket.next().await {
+            let Message::Text(text) = message else {
+                continue;
+            };
+            let message: Value = serde_json::from_str(text.as_str()).unwrap();
+            let Some(id) = message.get("id") else {
+                continue;
+            };
+            let result = match message["method"].as_str() {
+                Some("initialize") => json!({
+                    "protocolVersion": types::LATEST_PROTOCOL_VERSION,
+                    "capabilities": {},
+                    "serverInfo": { "name": "websocket-server", "version": "1.0.0" },
+                }),
+                Some("test/close") => {
+                    socket
+                        .close(Some(CloseFrame {
+                            code: CloseCode::Library(4000),
+                            reason: "test is over".into(),
+                        }))
+                        .await
+                        .ok();
+                    continue;
+                }
+                _ => json!({}),
+            };
+            let response = json!({ "jsonrpc": "2.0", "id": id, "result": result });
+            socket
+                .send(Message::text(response.to_string()))
+                .await
+                .unwrap();
+        }
+    }
+
+    fn start_fixture(
+        runtime: &tokio::runtime::Runtime,
+    ) -> (Url, oneshot::Receiver<HashMap<String, String>>) {
+        let listener = runtime.block_on(TcpListener::bind("127.0.0.1:0")).unwrap();
+        let url = format!("ws://{}/mcp", listener.local_addr().unwrap());
+        let (headers_tx, headers_rx) = oneshot::channel();
+        runtime.spawn(serve(listener, headers_tx));
+        (Url::parse(&url).unwrap(), headers_rx)
+    }
+
+    #[test]
+    fn test_websocket_transport() {
+        let runtime = tokio::runtime::Runtime::new().unwrap();
+        let (url, headers_rx) = start_fixture(&runtime);
+        let headers = HashMap::from_iter([("Authorization".into(), "Bearer secret".into())]);
+        let transport = WebSocketTransport::new(
+            url,
+            headers,
+            crate::tls::client_config(None).unwrap(),
+            runtime.handle().clone(),
+        );
+
+        runtime.block_on(async {
+            let mut responses = transport.receive();
+            let mut errors = transport.receive_err();
+
+            let request = json!({ "jsonrpc": "2.0", "id": 1, "method": "ping" });
+            transport.send(request.to_string()).await.unwrap();
+            let response: Value = serde_json::from_str(&responses.next().await.unwrap()).unwrap();
+            assert_eq!(response, json!({ "jsonrpc": "2.0", "id": 1, "result": {} }));
+            assert_eq!(headers_rx.await.unwrap()["authorization"], "Bearer secret");
+
+            let request = json!({ "jsonrpc": "2.0", "id": 2, "method": "test/close" });
+            transport.send(request.to_string()).await.unwrap();
+            assert_eq!(
+                errors.next().await.unwrap(),
+                "WebSocket closed with code 4000: test is over"
+            );
+            assert_eq!(responses.next().await, None);
+        });
+    }
+
+    #[gpui::test]
+    async fn test_websocket_context_server(cx: &mut TestAppContext) {
+        cx.executor().allow_parking();
+        let runtime = tokio::runtime::Runtime::new().unwrap();
+        let (url, _headers_rx) = start_fixture(&runtime);
+
+        let server = ContextServer::websocket(
+            ContextServerId("websocket".into()),
+            &url,
+            HashMap::default(),
+            crate::tls::client_config(None).unwrap(),
+            runtime.handle().clone(),
+        )
+        .unwrap();
+        server.start(&cx.to_async()).await.unwrap();
+        let client = server.client().unwrap();
+        assert_eq!(client.initialize.server_info.name, "websocket-server");
+
+        assert_eq!(server.stop().await.unwrap(), Shutdown::Exited);
+    }
+}
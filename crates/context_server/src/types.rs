@@ -34,13 +34,13 @@ pub mod requests {
         "resources/unsubscribe",
         ResourcesUnsubscribe,
         ResourcesUnsubscribeParams,
-        ()
+        EmptyResponse
     );
     request!(
         "resources/subscribe",
         ResourcesSubscribe,
         ResourcesSubscribeParams,
-        ()
+        EmptyResponse
     );
     request!(
         "resources/read",
@@ -48,12 +48,17 @@ pub mod requests {
         ResourcesReadParams,
         ResourcesReadResponse
     );
-    request!("resources/list", ResourcesList, (), ResourcesListResponse);
+    request!(
+        "resources/list",
+        ResourcesList,
+        PaginatedRequestParams,
+        ResourcesListResponse
+    );
     request!(
         "logging/setLevel",
         LoggingSetLevel,
         LoggingSetLevelParams,
-        ()
+        EmptyResponse
     );
     request!(
         "prompts/get",
@@ -61,22 +66,44 @@ pub mod requests {
         PromptsGetParams,
         PromptsGetResponse
     );
-    request!("prompts/list", PromptsList, (), PromptsListResponse);
+    request!(
+        "prompts/list",
+        PromptsList,
+        PaginatedRequestParams,
+        PromptsListResponse
+    );
     request!(
         "completion/complete",
         CompletionComplete,
         CompletionCompleteParams,
         CompletionCompleteResponse
     );
-    request!("ping", Ping, (), ());
-    request!("tools/list", ListTools, (), ListToolsResponse);
+    request!("ping", Ping, (), EmptyResponse);
+    request!(
+        "tools/list",
+        ListTools,
+        PaginatedRequestParams,
+        ListToolsResponse
+    );
     request!(
         "resources/templates/list",
         ListResourceTemplates,
-        (),
+        PaginatedRequestParams,
         ListResourceTemplatesResponse
     );
-    request!("roots/list", ListRoots, (), ListRootsResponse);
+    request!(
+        "roots/list",
+        ListRoots,
+        Option<ListRootsParams>,
+        ListRootsResponse
+    );
+    request!(
+        "sampling/createMessage",
+        CreateMessage,
+        CreateMessageRequest,
+        CreateMessageResult
+    );
+    request!("elicitation/create", Elicit, ElicitRequest, ElicitResult);
 }
 
 pub trait Request {
@@ -151,7 +178,7 @@ pub struct InitializeParams {
     pub meta: Option<HashMap<String, serde_json::Value>>,
 }
 
-#[derive(Debug, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 #[serde(rename_all = "camelCase")]
 pub struct CallToolParams {
     pub name: String,
@@ -185,6 +212,15 @@ pub struct ResourcesReadParams {
     pub meta: Option<HashMap<String, serde_json::Value>>,
 }
 
+#[derive(Debug, Default, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct PaginatedRequestParams {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub cursor: Option<String>,
+    #[serde(rename = "_meta", skip_serializing_if = "Option::is_none")]
+    pub meta: Option<HashMap<String, serde_json::Value>>,
+}
+
 #[derive(Debug, Serialize, Deserialize)]
 #[serde(rename_all = "camelCase")]
 pub struct LoggingSetLevelParams {
@@ -233,7 +269,8 @@ pub struct PromptReference {
 pub struct ResourceReference {
     #[serde(rename = "type")]
     pub ty: PromptReferenceType,
-    pub uri: Url,
+    /// The URI of a resource, or the URI template of a resource template.
+    pub uri: String,
 }
 
 #[derive(Debug, Serialize, Deserialize)]
@@ -262,6 +299,14 @@ pub struct InitializeResponse {
     pub meta: Option<HashMap<String, serde_json::Value>>,
 }
 
+/// The result of requests that only acknowledge success, which servers send as `{}`.
+#[derive(Debug, Default, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct EmptyResponse {
+    #[serde(rename = "_meta", skip_serializing_if = "Option::is_none")]
+    pub meta: Option<HashMap<String, serde_json::Value>>,
+}
+
 #[derive(Debug, Serialize, Deserialize)]
 #[serde(rename_all = "camelCase")]
 pub struct ResourcesReadResponse {
@@ -277,6 +322,39 @@ pub enum ResourceContentsType {
     Blob(BlobResourceContents),
 }
 
+impl ResourceContentsType {
+    pub fn uri(&self) -> &Url {
+        match self {
+            ResourceContentsType::Text(contents) => &contents.uri,
+            ResourceContentsType::Blob(contents) => &contents.uri,
+        }
+    }
+
+    pub fn mime_type(&self) -> Option<&str> {
+        match self {
+            ResourceContentsType::Text(contents) => contents.mime_type.as_deref(),
+            ResourceContentsType::Blob(contents) => contents.mime_type.as_deref(),
+        }
+    }
+
+    pub fn text(&self) -> Option<&str> {
+        if let ResourceContentsType::Text(contents) = self {
+            Some(&contents.text)
+        } else {
+            None
+        }
+    }
+
+    /// Returns the base64-encoded payload of a binary resource.
+    pub fn blob(&self) -> Option<&str> {
+        if let ResourceContentsType::Blob(contents) = self {
+            Some(&contents.blob)
+        } else {
+            None
+        }
+    }
+}
+
 #[derive(Debug, Serialize, Deserialize)]
 #[serde(rename_all = "camelCase")]
 pub struct ResourcesListResponse {
@@ -313,7 +391,7 @@ pub struct CreateMessageRequest {
     pub metadata: Option<serde_json::Value>,
 }
 
-#[derive(Debug, Deserialize)]
+#[derive(Debug, Serialize, Deserialize)]
 #[serde(rename_all = "camelCase")]
 pub struct CreateMessageResult {
     pub role: Role,
@@ -323,6 +401,31 @@ pub struct CreateMessageResult {
     pub stop_reason: Option<String>,
 }
 
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ElicitRequest {
+    pub message: String,
+    /// An object schema whose properties are primitive values.
+    pub requested_schema: serde_json::Value,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ElicitResult {
+    pub action: ElicitAction,
+    /// The values the user entered, only sent when they accepted.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub content: Option<serde_json::Map<String, serde_json::Value>>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ElicitAction {
+    Accept,
+    Decline,
+    Cancel,
+}
+
 #[derive(Debug, Serialize, Deserialize)]
 #[serde(rename_all = "camelCase")]
 pub struct PromptMessage {
@@ -368,6 +471,16 @@ pub enum MessageContent {
     },
 }
 
+impl MessageContent {
+    pub fn text(&self) -> Option<&str> {
+        if let MessageContent::Text { text, .. } = self {
+            Some(text)
+        } else {
+            None
+        }
+    }
+}
+
 #[derive(Debug, Serialize, Deserialize)]
 #[serde(rename_all = "camelCase")]
 pub struct MessageAnnotations {
@@ -417,7 +530,7 @@ pub struct CompletionResult {
     pub meta: Option<HashMap<String, serde_json::Value>>,
 }
 
-#[derive(Debug, Serialize, Deserialize)]
+#[derive(Clone, Debug, Serialize, Deserialize)]
 #[serde(rename_all = "camelCase")]
 pub struct Prompt {
     pub name: String,
@@ -427,7 +540,7 @@ pub struct Prompt {
     pub arguments: Option<Vec<PromptArgument>>,
 }
 
-#[derive(Debug, Serialize, Deserialize)]
+#[derive(Clone, Debug, Serialize, Deserialize)]
 #[serde(rename_all = "camelCase")]
 pub struct PromptArgument {
     pub name: String,
@@ -445,10 +558,12 @@ pub struct ClientCapabilities {
     #[serde(skip_serializing_if = "Option::is_none")]
     pub sampling: Option<serde_json::Value>,
     #[serde(skip_serializing_if = "Option::is_none")]
+    pub elicitation: Option<serde_json::Value>,
+    #[serde(skip_serializing_if = "Option::is_none")]
     pub roots: Option<RootsCapabilities>,
 }
 
-#[derive(Default, Debug, Serialize, Deserialize)]
+#[derive(Default, Clone, Debug, PartialEq, Serialize, Deserialize)]
 #[serde(rename_all = "camelCase")]
 pub struct ServerCapabilities {
     #[serde(skip_serializing_if = "Option::is_none")]
@@ -465,14 +580,14 @@ pub struct ServerCapabilities {
     pub tools: Option<ToolsCapabilities>,
 }
 
-#[derive(Debug, Serialize, Deserialize)]
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
 #[serde(rename_all = "camelCase")]
 pub struct PromptsCapabilities {
     #[serde(skip_serializing_if = "Option::is_none")]
     pub list_changed: Option<bool>,
 }
 
-#[derive(Debug, Serialize, Deserialize)]
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
 #[serde(rename_all = "camelCase")]
 pub struct ResourcesCapabilities {
     #[serde(skip_serializing_if = "Option::is_none")]
@@ -481,7 +596,7 @@ pub struct ResourcesCapabilities {
     pub list_changed: Option<bool>,
 }
 
-#[derive(Debug, Serialize, Deserialize)]
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
 #[serde(rename_all = "camelCase")]
 pub struct ToolsCapabilities {
     #[serde(skip_serializing_if = "Option::is_none")]
@@ -508,7 +623,7 @@ pub struct Tool {
     pub annotations: Option<ToolAnnotations>,
 }
 
-#[derive(Clone, Debug, Serialize, Deserialize)]
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
 #[serde(rename_all = "camelCase")]
 pub struct ToolAnnotations {
     /// A human-readable title for the tool.
@@ -528,14 +643,14 @@ pub struct ToolAnnotations {
     pub open_world_hint: Option<bool>,
 }
 
-#[derive(Debug, Serialize, Deserialize)]
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
 #[serde(rename_all = "camelCase")]
 pub struct Implementation {
     pub name: String,
     pub version: String,
 }
 
-#[derive(Debug, Serialize, Deserialize)]
+#[derive(Clone, Debug, Serialize, Deserialize)]
 #[serde(rename_all = "camelCase")]
 pub struct Resource {
     pub uri: Url,
@@ -572,7 +687,7 @@ pub struct BlobResourceContents {
     pub blob: String,
 }
 
-#[derive(Debug, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 #[serde(rename_all = "camelCase")]
 pub struct ResourceTemplate {
     pub uri_template: String,
@@ -583,7 +698,7 @@ pub struct ResourceTemplate {
     pub mime_type: Option<String>,
 }
 
-#[derive(Debug, Serialize, Deserialize)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
 #[serde(rename_all = "lowercase")]
 pub enum LoggingLevel {
     Debug,
@@ -596,6 +711,36 @@ pub enum LoggingLevel {
     Emergency,
 }
 
+impl LoggingLevel {
+    /// The closest level in the `log` crate, used when forwarding server logs to Zed's log.
+    pub fn log_level(self) -> log::Level {
+        match self {
+            LoggingLevel::Debug => log::Level::Debug,
+            LoggingLevel::Info | LoggingLevel::Notice => log::Level::Info,
+            LoggingLevel::Warning => log::Level::Warn,
+            LoggingLevel::Error
+            | LoggingLevel::Critical
+            | LoggingLevel::Alert
+            | LoggingLevel::Emergency => log::Level::Error,
+        }
+    }
+}
+
+impl From<settings::ContextServerLogLevel> for LoggingLevel {
+    fn from(level: settings::ContextServerLogLevel) -> Self {
+        match level {
+            settings::ContextServerLogLevel::Debug => LoggingLevel::Debug,
+            settings::ContextServerLogLevel::Info => LoggingLevel::Info,
+            settings::ContextServerLogLevel::Notice => LoggingLevel::Notice,
+            settings::ContextServerLogLevel::Warning => LoggingLevel::Warning,
+            settings::ContextServerLogLevel::Error => LoggingLevel::Error,
+            settings::ContextServerLogLevel::Critical => LoggingLevel::Critical,
+            settings::ContextServerLogLevel::Alert => LoggingLevel::Alert,
+            settings::ContextServerLogLevel::Emergency => LoggingLevel::Emergency,
+        }
+    }
+}
+
 #[derive(Debug, Serialize, Deserialize)]
 #[serde(rename_all = "camelCase")]
 pub struct ModelPreferences {
@@ -707,8 +852,20 @@ pub enum ToolResponseContent {
     Image { data: String, mime_type: String },
     #[serde(rename = "audio", rename_all = "camelCase")]
     Audio { data: String, mime_type: String },
+    #[serde(rename = "resource_link", rename_all = "camelCase")]
+    ResourceLink {
+        uri: Url,
+        name: String,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        description: Option<String>,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        mime_type: Option<String>,
+    },
     #[serde(rename = "resource")]
-    Resource { resource: ResourceContents },
+    Resource { resource: ResourceContentsType },
+    /// Content of a type that isn't known, or that doesn't match its type.
+    #[serde(untagged)]
+    Unknown(serde_json::Value),
 }
 
 impl ToolResponseContent {
@@ -741,6 +898,13 @@ pub struct ListResourceTemplatesResponse {
     pub meta: Option<HashMap<String, serde_json::Value>>,
 }
 
+#[derive(Debug, Default, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ListRootsParams {
+    #[serde(rename = "_meta", skip_serializing_if = "Option::is_none")]
+    pub meta: Option<HashMap<String, serde_json::Value>>,
+}
+
 #[derive(Debug, Serialize, Deserialize)]
 #[serde(rename_all = "camelCase")]
 pub struct ListRootsResponse {
@@ -0,0 +1,188 @@
+//! The URI templates of resource templates.
+
+use std::fmt::Write as _;
+
+use anyhow::{Result, anyhow, bail};
+use collections::HashMap;
+
+/// A URI template like `repo://{owner}/{name}/issues/{id}`, limited to the simple `{name}`
+/// expressions of level 1 of RFC 6570.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct UriTemplate {
+    parts: Vec<Part>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Part {
+    Literal(String),
+    Variable(String),
+}
+
+impl UriTemplate {
+    pub fn parse(input: &str) -> Result<Self> {
+        let mut parts = Vec::new();
+        let mut rest = input;
+        while let Some(start) = rest.find(['{', '}']) {
+            if rest[start..].starts_with('}') {
+                bail!("unmatched }} in {input:?}");
+            }
+            if start > 0 {
+                parts.push(Part::Literal(rest[..start].to_string()));
+            }
+            let after = &rest[start + 1..];
+            let end = after
+                .find('}')
+                .ok_or_else(|| anyhow!("unterminated expression in {input:?}"))?;
+            let expression = &after[..end];
+            if !is_variable_name(expression) {
+                bail!(
+                    "unsupported expression {{{expression}}} in {input:?}, only {{name}} is \
+                    supported"
+                );
+            }
+            parts.push(Part::Variable(expression.to_string()));
+            rest = &after[end + 1..];
+        }
+        if !rest.is_empty() {
+            parts.push(Part::Literal(rest.to_string()));
+        }
+        Ok(Self { parts })
+    }
+
+    /// The names of the variables, in the order they appear in the template.
+    pub fn variables(&self) -> impl Iterator<Item = &str> {
+        self.parts.iter().filter_map(|part| match part {
+            Part::Variable(name) => Some(name.as_str()),
+            Part::Literal(_) => None,
+        })
+    }
+
+    /// Replaces the variables with their percent-encoded values, failing if any of them
+    /// has no value.
+    pub fn expand(&self, params: &HashMap<String, String>) -> Result<String> {
+        let mut missing = Vec::new();
+        for name in self.variables() {
+            if !params.contains_key(name) && !missing.contains(&name) {
+                missing.push(name);
+            }
+        }
+        if !missing.is_empty() {
+            bail!(
+                "missing values for template variables {}",
+                missing.join(", ")
+            );
+        }
+
+        let mut output = String::new();
+        for part in &self.parts {
+            match part {
+                Part::Literal(text) => output.push_str(text),
+                Part::Variable(name) => percent_encode(&params[name], &mut output),
+            }
+        }
+        Ok(output)
+    }
+}
+
+/// Expands a URI template with the given values, see [`UriTemplate::expand`].
+pub fn expand_template(template: &str, params: &HashMap<String, String>) -> Result<String> {
+    UriTemplate::parse(template)?.expand(params)
+}
+
+/// Variable names are made of letters, digits and underscores, optionally separated by
+/// single dots.
+fn is_variable_name(name: &str) -> bool {
+    !name.is_empty()
+        && name.split('.').all(|segment| {
+            !segment.is_empty()
+                && segment
+                    .chars()
+                    .all(|char| char == '_' || char.is_ascii_alphanumeric())
+        })
+}
+
+/// Encodes everything but unreserved characters, as simple string expansion does.
+fn percent_encode(value: &str, output: &mut String) {
+    for byte in value.bytes() {
+        if byte.is_ascii_alphanumeric() || b"-._~".contains(&byte) {
+            output.push(byte as char);
+        } else {
+            write!(output, "%{byte:02X}").unwrap();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn params(pairs: &[(&str, &str)]) -> HashMap<String, String> {
+        pairs
+            .iter()
+            .map(|(name, value)| (name.to_string(), value.to_string()))
+            .collect()
+    }
+
+    #[test]
+    fn test_expand_template() {
+        let template = "repo://{owner}/{name}/issues/{id}";
+        assert_eq!(
+            UriTemplate::parse(template)
+                .unwrap()
+                .variables()
+                .collect::<Vec<_>>(),
+            vec!["owner", "name", "id"]
+        );
+        assert_eq!(
+            expand_template(
+                template,
+                &params(&[("owner", "zed-industries"), ("name", "zed"), ("id", "42")])
+            )
+            .unwrap(),
+            "repo://zed-industries/zed/issues/42"
+        );
+        assert_eq!(
+            expand_template(
+                "file:///{path}?q={query}",
+                &params(&[("path", "src/main.rs"), ("query", "50% off & more é")])
+            )
+            .unwrap(),
+            "file:///src%2Fmain.rs?q=50%25%20off%20%26%20more%20%C3%A9"
+        );
+        assert_eq!(
+            expand_template("{a.b}-{a.b}", &params(&[("a.b", "x")])).unwrap(),
+            "x-x"
+        );
+        assert_eq!(
+            expand_template("docs://index", &params(&[])).unwrap(),
+            "docs://index"
+        );
+
+        assert_eq!(
+            expand_template(template, &params(&[("name", "zed")]))
+                .unwrap_err()
+                .to_string(),
+            "missing values for template variables owner, id"
+        );
+    }
+
+    #[test]
+    fn test_parse_errors() {
+        let error = |input: &str| UriTemplate::parse(input).unwrap_err().to_string();
+        assert_eq!(
+            error("repo://{owner"),
+            "unterminated expression in \"repo://{owner\""
+        );
+        assert_eq!(error("repo://owner}"), "unmatched } in \"repo://owner}\"");
+        for expression in ["+path", "#section", "a,b", "name*", "name:3", "", "a..b"] {
+            let input = format!("repo://{{{expression}}}");
+            assert_eq!(
+                error(&input),
+                format!(
+                    "unsupported expression {{{expression}}} in {input:?}, only {{name}} is \
+                    supported"
+                )
+            );
+        }
+    }
+}
@@ -0,0 +1,17 @@
+#!/bin/sh
+# A context server that crashes after it answers the first tool call.
+
+while IFS= read -r line; do
+    id=$(printf '%s' "$line" | sed -n 's/.*"id":\([0-9]*\).*/\1/p')
+    case "$line" in
+        *'"method":"initialize"'*)
+            printf '{"jsonrpc":"2.0","id":%s,"result":{"protocolVersion":"2025-03-26","capabilities":{"tools":{}},"serverInfo":{"name":"crashing-server","version":"1.0.0"}}}\n' "$id"
+            ;;
+        *'"method":"tools/call"'*)
+            printf '{"jsonrpc":"2.0","id":%s,"result":{"content":[{"type":"text","text":"done"}]}}\n' "$id"
+            echo "fatal: out of memory" >&2
+            sleep 0.1
+            exit 7
+            ;;
+    esac
+done
@@ -0,0 +1,6 @@
+#!/bin/sh
+# A context server that fails before it answers the initialize request.
+
+echo "error: MCP_API_KEY is not set" >&2
+sleep 0.1
+exit 3
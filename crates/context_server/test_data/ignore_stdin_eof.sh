@@ -0,0 +1,11 @@
+#!/bin/sh
+# A context server that keeps running after its stdin is closed.
+# With --ignore-term, it also ignores SIGTERM and has to be killed.
+
+if [ "$1" = "--ignore-term" ]; then
+    trap '' TERM
+fi
+
+while true; do
+    sleep 0.05
+done
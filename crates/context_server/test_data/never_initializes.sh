@@ -0,0 +1,5 @@
+#!/bin/sh
+# A context server that prints a banner instead of speaking JSON-RPC, and then hangs.
+
+echo "Starting server..."
+exec sleep 60
@@ -0,0 +1,22 @@
+#!/bin/sh
+# A context server whose first tool call answers with a 2000 character text, and whose
+# later tool calls answer normally.
+
+answered=0
+while IFS= read -r line; do
+    id=$(printf '%s' "$line" | sed -n 's/.*"id":\([0-9]*\).*/\1/p')
+    case "$line" in
+        *'"method":"initialize"'*)
+            printf '{"jsonrpc":"2.0","id":%s,"result":{"protocolVersion":"2025-03-26","capabilities":{"tools":{}},"serverInfo":{"name":"oversized-server","version":"1.0.0"}}}\n' "$id"
+            ;;
+        *'"method":"tools/call"'*)
+            if [ "$answered" = 0 ]; then
+                text=$(head -c 2000 /dev/zero | tr '\0' x)
+                answered=1
+            else
+                text="done"
+            fi
+            printf '{"jsonrpc":"2.0","id":%s,"result":{"content":[{"type":"text","text":"%s"}]}}\n' "$id" "$text"
+            ;;
+    esac
+done
@@ -0,0 +1,19 @@
+#!/bin/sh
+# A context server that reports its niceness when the "nice" tool is called, and allocates
+# memory until it fails when the "allocate" tool is called.
+
+while IFS= read -r line; do
+    id=$(printf '%s' "$line" | sed -n 's/.*"id":\([0-9]*\).*/\1/p')
+    case "$line" in
+        *'"method":"initialize"'*)
+            printf '{"jsonrpc":"2.0","id":%s,"result":{"protocolVersion":"2025-03-26","capabilities":{"tools":{}},"serverInfo":{"name":"limited-server","version":"1.0.0"}}}\n' "$id"
+            ;;
+        *'"name":"nice"'*)
+            printf '{"jsonrpc":"2.0","id":%s,"result":{"content":[{"type":"text","text":"%s"}]}}\n' "$id" "$(nice)"
+            ;;
+        *'"name":"allocate"'*)
+            memory=$(head -c 100000000 /dev/zero | tr '\0' x)
+            printf '{"jsonrpc":"2.0","id":%s,"result":{"content":[{"type":"text","text":"allocated %s bytes"}]}}\n' "$id" "${#memory}"
+            ;;
+    esac
+done
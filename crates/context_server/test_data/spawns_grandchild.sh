@@ -0,0 +1,22 @@
+#!/bin/sh
+# A context server started through a wrapper like `npx` or `uv run`, which leaves a process
+# of its own running in the background. Writes that process's PID to the file given as the
+# first argument. With --exit-on-eof, the server exits once its stdin is closed, leaving the
+# background process running.
+
+sleep 1000 < /dev/null > /dev/null 2>&1 &
+echo $! > "$1.tmp"
+mv "$1.tmp" "$1"
+
+while IFS= read -r line; do
+    id=$(printf '%s' "$line" | sed -n 's/.*"id":\([0-9]*\).*/\1/p')
+    case "$line" in
+        *'"method":"initialize"'*)
+            printf '{"jsonrpc":"2.0","id":%s,"result":{"protocolVersion":"2025-03-26","capabilities":{},"serverInfo":{"name":"wrapped-server","version":"1.0.0"}}}\n' "$id"
+            ;;
+    esac
+done
+
+if [ "$2" != "--exit-on-eof" ]; then
+    wait
+fi
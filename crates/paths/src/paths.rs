@@ -173,6 +173,12 @@ pub fn logs_dir() -> &'static PathBuf {
     })
 }
 
+/// Returns the path to the directory the JSON-RPC traffic of context servers is logged to.
+pub fn context_server_logs_dir() -> &'static PathBuf {
+    static CONTEXT_SERVER_LOGS_DIR: OnceLock<PathBuf> = OnceLock::new();
+    CONTEXT_SERVER_LOGS_DIR.get_or_init(|| logs_dir().join("context_servers"))
+}
+
 /// Returns the path to the Zed server directory on this SSH host.
 pub fn remote_server_state_dir() -> &'static PathBuf {
     static REMOTE_SERVER_STATE: OnceLock<PathBuf> = OnceLock::new();
@@ -1,21 +1,43 @@
 pub mod extension;
 pub mod registry;
 
-use std::sync::Arc;
+use std::{
+    path::{Path, PathBuf},
+    sync::Arc,
+    time::Duration,
+};
 
 use anyhow::{Context as _, Result};
 use collections::{HashMap, HashSet};
-use context_server::{ContextServer, ContextServerCommand, ContextServerId};
-use futures::{FutureExt as _, future::join_all};
+use context_server::{
+    ContextServer, ContextServerCommand, ContextServerConnectionSettings,
+    ContextServerHeaderCommand, ContextServerHttpTransport, ContextServerId, ContextServerOptions,
+    ContextServerTlsSettings, Crashed, ServerInfo,
+    error::ContextServerError,
+    executable::expand_home,
+    header_provider::CommandHeaderProvider,
+    protocol::{CapabilityNotSupported, IncompatibleProtocol},
+    transport::DEFAULT_HTTP_REQUEST_TIMEOUT,
+    types::LoggingLevel,
+    types::ServerCapabilities,
+};
+use futures::{
+    FutureExt as _, StreamExt as _,
+    channel::mpsc,
+    future::{Shared, join_all},
+};
 use gpui::{App, AsyncApp, Context, Entity, EventEmitter, Subscription, Task, WeakEntity, actions};
-use registry::ContextServerDescriptorRegistry;
+use http_client::{HttpClient, Url};
+use registry::{ContextServerDescriptorRegistry, ContextServerSource, DuplicateContextServerId};
+use reqwest_client::{ConnectionOptions, ReqwestClient};
 use settings::{Settings as _, SettingsStore};
-use util::{ResultExt as _, rel_path::RelPath};
+use task::Shell;
+use util::{ResultExt as _, paths::home_dir, rel_path::RelPath};
 
 use crate::{
     Project,
     project_settings::{ContextServerSettings, ProjectSettings},
-    worktree_store::WorktreeStore,
+    worktree_store::{WorktreeStore, WorktreeStoreEvent},
 };
 
 pub fn init(cx: &mut App) {
@@ -36,6 +58,8 @@ pub enum ContextServerStatus {
     Running,
     Stopped,
     Error(Arc<str>),
+    /// The server went away by itself while it was running, like a process that exited.
+    Crashed(Arc<str>),
 }
 
 impl ContextServerStatus {
@@ -45,6 +69,9 @@ impl ContextServerStatus {
             ContextServerState::Running { .. } => ContextServerStatus::Running,
             ContextServerState::Stopped { .. } => ContextServerStatus::Stopped,
             ContextServerState::Error { error, .. } => ContextServerStatus::Error(error.clone()),
+            ContextServerState::Crashed { error, .. } => {
+                ContextServerStatus::Crashed(error.clone())
+            }
         }
     }
 }
@@ -58,6 +85,8 @@ enum ContextServerState {
     Running {
         server: Arc<ContextServer>,
         configuration: Arc<ContextServerConfiguration>,
+        _health_check: Task<()>,
+        _crash_monitor: Task<()>,
     },
     Stopped {
         server: Arc<ContextServer>,
@@ -68,6 +97,11 @@ enum ContextServerState {
         configuration: Arc<ContextServerConfiguration>,
         error: Arc<str>,
     },
+    Crashed {
+        server: Arc<ContextServer>,
+        configuration: Arc<ContextServerConfiguration>,
+        error: Arc<str>,
+    },
 }
 
 impl ContextServerState {
@@ -77,6 +111,7 @@ impl ContextServerState {
             ContextServerState::Running { server, .. } => server.clone(),
             ContextServerState::Stopped { server, .. } => server.clone(),
             ContextServerState::Error { server, .. } => server.clone(),
+            ContextServerState::Crashed { server, .. } => server.clone(),
         }
     }
 
@@ -86,34 +121,67 @@ impl ContextServerState {
             ContextServerState::Running { configuration, .. } => configuration.clone(),
             ContextServerState::Stopped { configuration, .. } => configuration.clone(),
             ContextServerState::Error { configuration, .. } => configuration.clone(),
+            ContextServerState::Crashed { configuration, .. } => configuration.clone(),
         }
     }
 }
 
-#[derive(Debug, PartialEq, Eq)]
+#[derive(Debug, Clone, PartialEq, Eq)]
 pub enum ContextServerConfiguration {
     Custom {
         command: ContextServerCommand,
+        options: ContextServerOptions,
     },
     Extension {
         command: ContextServerCommand,
         settings: serde_json::Value,
+        options: ContextServerOptions,
     },
     Http {
         url: url::Url,
         headers: HashMap<String, String>,
+        timeout: Option<u64>,
+        transport: ContextServerHttpTransport,
+        proxy: Option<String>,
+        no_proxy: Option<String>,
+        tls: Option<ContextServerTlsSettings>,
+        connection: Option<ContextServerConnectionSettings>,
+        header_command: Option<ContextServerHeaderCommand>,
+        options: ContextServerOptions,
     },
 }
 
 impl ContextServerConfiguration {
     pub fn command(&self) -> Option<&ContextServerCommand> {
         match self {
-            ContextServerConfiguration::Custom { command } => Some(command),
+            ContextServerConfiguration::Custom { command, .. } => Some(command),
             ContextServerConfiguration::Extension { command, .. } => Some(command),
             ContextServerConfiguration::Http { .. } => None,
         }
     }
 
+    pub fn options(&self) -> &ContextServerOptions {
+        match self {
+            ContextServerConfiguration::Custom { options, .. }
+            | ContextServerConfiguration::Extension { options, .. }
+            | ContextServerConfiguration::Http { options, .. } => options,
+        }
+    }
+
+    /// Whether the configurations only differ in the log level, which can be changed without
+    /// restarting the server.
+    fn differs_only_in_log_level(&self, other: &Self) -> bool {
+        let mut other = other.clone();
+        match &mut other {
+            ContextServerConfiguration::Custom { options, .. }
+            | ContextServerConfiguration::Extension { options, .. }
+            | ContextServerConfiguration::Http { options, .. } => {
+                options.log_level = self.options().log_level;
+            }
+        }
+        *self == other
+    }
+
     pub async fn from_settings(
         settings: ContextServerSettings,
         id: ContextServerId,
@@ -125,10 +193,12 @@ impl ContextServerConfiguration {
             ContextServerSettings::Custom {
                 enabled: _,
                 command,
-            } => Some(ContextServerConfiguration::Custom { command }),
+                options,
+            } => Some(ContextServerConfiguration::Custom { command, options }),
             ContextServerSettings::Extension {
                 enabled: _,
                 settings,
+                options,
             } => {
                 let descriptor = cx
                     .update(|cx| registry.read(cx).context_server_descriptor(&id.0))
@@ -136,9 +206,11 @@ impl ContextServerConfiguration {
                     .flatten()?;
 
                 match descriptor.command(worktree_store, cx).await {
-                    Ok(command) => {
-                        Some(ContextServerConfiguration::Extension { command, settings })
-                    }
+                    Ok(command) => Some(ContextServerConfiguration::Extension {
+                        command,
+                        settings,
+                        options,
+                    }),
                     Err(e) => {
                         log::error!(
                             "Failed to create context server configuration from settings: {e:#}"
@@ -151,14 +223,41 @@ impl ContextServerConfiguration {
                 enabled: _,
                 url,
                 headers: auth,
+                timeout,
+                transport,
+                proxy,
+                no_proxy,
+                tls,
+                connection,
+                header_command,
+                options,
             } => {
                 let url = url::Url::parse(&url).log_err()?;
-                Some(ContextServerConfiguration::Http { url, headers: auth })
+                Some(ContextServerConfiguration::Http {
+                    url,
+                    headers: auth,
+                    timeout,
+                    transport: transport.unwrap_or_default(),
+                    proxy,
+                    no_proxy,
+                    tls,
+                    connection,
+                    header_command,
+                    options,
+                })
             }
         }
     }
 }
 
+const DEFAULT_PING_INTERVAL: Duration = Duration::from_secs(30);
+const PING_TIMEOUT: Duration = Duration::from_secs(10);
+/// How many pings in a row a server may miss before it is considered unresponsive.
+const MAX_MISSED_PINGS: u32 = 3;
+const DEFAULT_MAX_RESTARTS: u32 = 5;
+const INITIAL_RESTART_BACKOFF: Duration = Duration::from_secs(1);
+const MAX_RESTART_BACKOFF: Duration = Duration::from_secs(5 * 60);
+
 pub type ContextServerFactory =
     Box<dyn Fn(ContextServerId, Arc<ContextServerConfiguration>) -> Arc<ContextServer>>;
 
@@ -170,6 +269,9 @@ pub struct ContextServerStore {
     registry: Entity<ContextServerDescriptorRegistry>,
     update_servers_task: Option<Task<Result<()>>>,
     context_server_factory: Option<ContextServerFactory>,
+    /// Consecutive automatic restarts of servers that stopped responding.
+    restart_attempts: HashMap<ContextServerId, u32>,
+    pending_restarts: HashMap<ContextServerId, Task<()>>,
     needs_server_update: bool,
     _subscriptions: Vec<Subscription>,
 }
@@ -178,7 +280,34 @@ pub enum Event {
     ServerStatusChanged {
         server_id: ContextServerId,
         status: ContextServerStatus,
+        /// The name and version of the server, while it is running.
+        server_info: Option<ServerInfo>,
+        /// The capabilities the server declared, while it is running.
+        capabilities: Option<ServerCapabilities>,
     },
+    /// A change in the settings affected a server. It's emitted before the server's status
+    /// changes as a result. Servers whose settings didn't change are left alone.
+    ServerSettingsChanged {
+        server_id: ContextServerId,
+        change: ServerSettingsChange,
+    },
+}
+
+/// How a server was affected by a change in the settings.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ServerSettingsChange {
+    /// The server was added to the settings, and is started.
+    Added,
+    /// The server was removed from the settings, and is stopped.
+    Removed,
+    /// The server was disabled, and is stopped.
+    Disabled,
+    /// The server was stopped, and is started again with the same configuration.
+    Started,
+    /// The server's configuration changed, and it's restarted with the new one.
+    Restarted,
+    /// Only the server's log level changed, which is applied without restarting it.
+    Reconfigured,
 }
 
 impl EventEmitter<Event> for ContextServerStore {}
@@ -208,6 +337,31 @@ impl ContextServerStore {
             .collect()
     }
 
+    /// Checks that a server with the given ID can be added from `source`, which isn't the
+    /// case when the settings or an extension already define a server with the ID.
+    pub fn check_new_server_id(
+        &self,
+        id: &ContextServerId,
+        source: ContextServerSource,
+        cx: &App,
+    ) -> Result<(), DuplicateContextServerId> {
+        let existing = match self.context_server_settings.get(&id.0) {
+            // The settings of an extension's server don't define another server.
+            Some(ContextServerSettings::Extension { .. }) | None => {
+                self.registry.read(cx).context_server_source(&id.0)
+            }
+            Some(_) => Some(ContextServerSource::Settings { path: None }),
+        };
+        match existing {
+            Some(existing) => Err(DuplicateContextServerId {
+                id: id.clone(),
+                existing,
+                duplicate: source,
+            }),
+            None => Ok(()),
+        }
+    }
+
     #[cfg(any(test, feature = "test-support"))]
     pub fn test(
         registry: Entity<ContextServerDescriptorRegistry>,
@@ -244,7 +398,7 @@ impl ContextServerStore {
         weak_project: WeakEntity<Project>,
         cx: &mut Context<Self>,
     ) -> Self {
-        let subscriptions = if maintain_server_loop {
+        let mut subscriptions = if maintain_server_loop {
             vec![
                 cx.observe(&registry, |this, _registry, cx| {
                     this.available_context_servers_changed(cx);
@@ -261,6 +415,16 @@ impl ContextServerStore {
         } else {
             Vec::new()
         };
+        subscriptions.push(cx.subscribe(&worktree_store, |this, _, event, cx| {
+            if matches!(
+                event,
+                WorktreeStoreEvent::WorktreeAdded(_)
+                    | WorktreeStoreEvent::WorktreeRemoved(..)
+                    | WorktreeStoreEvent::WorktreeOrderChanged
+            ) {
+                this.worktree_roots_changed(cx);
+            }
+        }));
 
         let mut this = Self {
             _subscriptions: subscriptions,
@@ -273,6 +437,8 @@ impl ContextServerStore {
             servers: HashMap::default(),
             update_servers_task: None,
             context_server_factory,
+            restart_attempts: HashMap::default(),
+            pending_restarts: HashMap::default(),
         };
         if maintain_server_loop {
             this.available_context_servers_changed(cx);
@@ -331,6 +497,7 @@ impl ContextServerStore {
     }
 
     pub fn start_server(&mut self, server: Arc<ContextServer>, cx: &mut Context<Self>) {
+        self.reset_restarts(&server.id());
         cx.spawn(async move |this, cx| {
             let this = this.upgrade().context("Context server store dropped")?;
             let settings = this
@@ -366,6 +533,7 @@ impl ContextServerStore {
     }
 
     pub fn stop_server(&mut self, id: &ContextServerId, cx: &mut Context<Self>) -> Result<()> {
+        self.reset_restarts(id);
         if matches!(
             self.servers.get(id),
             Some(ContextServerState::Stopped { .. })
@@ -380,9 +548,8 @@ impl ContextServerStore {
 
         let server = state.server();
         let configuration = state.configuration();
-        let mut result = Ok(());
         if let ContextServerState::Running { server, .. } = &state {
-            result = server.stop();
+            Self::shut_down(server, true, cx);
         }
         drop(state);
 
@@ -395,7 +562,24 @@ impl ContextServerStore {
             cx,
         );
 
-        result
+        Ok(())
+    }
+
+    /// Stops the server, ending its session unless it's about to be restarted.
+    fn shut_down(server: &ContextServer, end_session: bool, cx: &mut Context<Self>) {
+        let id = server.id();
+        let stop = if end_session {
+            server.stop().boxed()
+        } else {
+            server.disconnect().boxed()
+        };
+        cx.background_spawn(async move {
+            match stop.await {
+                Ok(shutdown) => log::debug!("context server {id} stopped: {shutdown:?}"),
+                Err(error) => log::error!("failed to stop context server {id}: {error:#}"),
+            }
+        })
+        .detach();
     }
 
     fn run_server(
@@ -405,28 +589,59 @@ impl ContextServerStore {
         cx: &mut Context<Self>,
     ) {
         let id = server.id();
-        if matches!(
-            self.servers.get(&id),
-            Some(ContextServerState::Starting { .. } | ContextServerState::Running { .. })
-        ) {
+        // Servers that were started before are restarted, which keeps their session and
+        // fails the tool calls in flight, rather than stopped and started again.
+        let restart = self.servers.get(&id).is_some_and(|state| {
+            !matches!(state, ContextServerState::Stopped { .. })
+                && Arc::ptr_eq(&state.server(), &server)
+        });
+        if !restart
+            && matches!(
+                self.servers.get(&id),
+                Some(ContextServerState::Starting { .. } | ContextServerState::Running { .. })
+            )
+        {
             self.stop_server(&id, cx).log_err();
         }
 
+        server.set_roots(self.worktree_roots(cx));
+        let shell_environment = configuration
+            .command()
+            .and_then(|_| self.shell_environment(cx));
+
         let task = cx.spawn({
             let id = server.id();
             let server = server.clone();
             let configuration = configuration.clone();
             async move |this, cx| {
-                match server.clone().start(cx).await {
+                if let Some(shell_environment) = shell_environment {
+                    let search_path = shell_environment
+                        .await
+                        .and_then(|mut environment| environment.remove("PATH"));
+                    server.set_search_path(search_path);
+                }
+                // Subscribed before starting, so that crashes right after the start are seen.
+                let crashes = server.crashes();
+                let started = if restart {
+                    server.restart(cx).await
+                } else {
+                    server.start(cx).await
+                };
+                match started {
                     Ok(_) => {
                         debug_assert!(server.client().is_some());
 
                         this.update(cx, |this, cx| {
+                            let health_check =
+                                Self::check_health(server.clone(), &configuration, cx);
+                            let crash_monitor = Self::monitor_crashes(server.clone(), crashes, cx);
                             this.update_server_state(
                                 id.clone(),
                                 ContextServerState::Running {
                                     server,
                                     configuration,
+                                    _health_check: health_check,
+                                    _crash_monitor: crash_monitor,
                                 },
                                 cx,
                             )
@@ -436,15 +651,21 @@ impl ContextServerStore {
                     Err(err) => {
                         log::error!("{} context server failed to start: {}", id, err);
                         this.update(cx, |this, cx| {
-                            this.update_server_state(
-                                id.clone(),
-                                ContextServerState::Error {
-                                    configuration,
-                                    server,
-                                    error: err.to_string().into(),
-                                },
-                                cx,
-                            )
+                            let error = start_error(&err, &server);
+                            // A failed automatic restart is retried like an unresponsive server.
+                            if this.restart_attempts.contains_key(&id) {
+                                this.schedule_restart(server, configuration, error, cx);
+                            } else {
+                                this.update_server_state(
+                                    id.clone(),
+                                    ContextServerState::Error {
+                                        configuration,
+                                        server,
+                                        error,
+                                    },
+                                    cx,
+                                )
+                            }
                         })
                         .log_err()
                     }
@@ -463,7 +684,241 @@ impl ContextServerStore {
         );
     }
 
+    /// Pings the server periodically, restarting it once it misses too many pings in a row.
+    fn check_health(
+        server: Arc<ContextServer>,
+        configuration: &ContextServerConfiguration,
+        cx: &mut Context<Self>,
+    ) -> Task<()> {
+        let interval = match configuration.options().ping_interval {
+            Some(0) => return Task::ready(()),
+            Some(interval) => Duration::from_millis(interval),
+            None => DEFAULT_PING_INTERVAL,
+        };
+        cx.spawn(async move |this, cx| {
+            let mut missed_pings = 0;
+            loop {
+                cx.background_executor().timer(interval).await;
+                match server.ping(PING_TIMEOUT).await {
+                    Ok(()) => {
+                        missed_pings = 0;
+                        this.update(cx, |this, _| this.restart_attempts.remove(&server.id()))
+                            .ok();
+                    }
+                    Err(error) => {
+                        missed_pings += 1;
+                        log::warn!(
+                            "context server {} missed a ping ({missed_pings}/{MAX_MISSED_PINGS}): {error:#}",
+                            server.id()
+                        );
+                        if missed_pings >= MAX_MISSED_PINGS {
+                            this.update(cx, |this, cx| {
+                                this.restart_unresponsive_server(&server.id(), cx)
+                            })
+                            .ok();
+                            return;
+                        }
+                    }
+                }
+            }
+        })
+    }
+
+    /// Marks the server as crashed once it goes away by itself, leaving it to the user to
+    /// start it again.
+    fn monitor_crashes(
+        server: Arc<ContextServer>,
+        mut crashes: mpsc::UnboundedReceiver<Crashed>,
+        cx: &mut Context<Self>,
+    ) -> Task<()> {
+        cx.spawn(async move |this, cx| {
+            let Some(crashed) = crashes.next().await else {
+                return;
+            };
+            this.update(cx, |this, cx| {
+                let id = server.id();
+                let Some(ContextServerState::Running {
+                    server: running_server,
+                    configuration,
+                    ..
+                }) = this.servers.get(&id)
+                else {
+                    return;
+                };
+                if !Arc::ptr_eq(running_server, &server) {
+                    return;
+                }
+                let configuration = configuration.clone();
+                let error = with_traffic_log_path(crashed.to_string(), &server);
+                this.update_server_state(
+                    id,
+                    ContextServerState::Crashed {
+                        server,
+                        configuration,
+                        error,
+                    },
+                    cx,
+                );
+            })
+            .ok();
+        })
+    }
+
+    fn restart_unresponsive_server(&mut self, id: &ContextServerId, cx: &mut Context<Self>) {
+        let Some(ContextServerState::Running {
+            server,
+            configuration,
+            ..
+        }) = self.servers.get(id)
+        else {
+            return;
+        };
+        let server = server.clone();
+        let configuration = configuration.clone();
+        // The server is restarted because it stopped responding, so it can keep its session.
+        Self::shut_down(&server, false, cx);
+        self.schedule_restart(
+            server,
+            configuration,
+            "Context server stopped responding".into(),
+            cx,
+        );
+    }
+
+    /// Marks the server as failed and starts it again after an exponential backoff, unless it
+    /// has already been restarted `max_restarts` times in a row.
+    fn schedule_restart(
+        &mut self,
+        server: Arc<ContextServer>,
+        configuration: Arc<ContextServerConfiguration>,
+        error: Arc<str>,
+        cx: &mut Context<Self>,
+    ) {
+        let id = server.id();
+        let attempt = self.restart_attempts.entry(id.clone()).or_default();
+        *attempt += 1;
+        let attempt = *attempt;
+        let max_restarts = configuration
+            .options()
+            .max_restarts
+            .unwrap_or(DEFAULT_MAX_RESTARTS);
+
+        self.update_server_state(
+            id.clone(),
+            ContextServerState::Error {
+                server: server.clone(),
+                configuration: configuration.clone(),
+                error,
+            },
+            cx,
+        );
+
+        if attempt > max_restarts {
+            log::error!(
+                "context server {id} is unresponsive, giving up after {max_restarts} restarts"
+            );
+            self.restart_attempts.remove(&id);
+            return;
+        }
+
+        let backoff = INITIAL_RESTART_BACKOFF
+            .saturating_mul(2u32.saturating_pow(attempt - 1))
+            .min(MAX_RESTART_BACKOFF);
+        log::info!("restarting context server {id} in {backoff:?} (attempt {attempt})");
+        let task = cx.spawn({
+            let id = id.clone();
+            async move |this, cx| {
+                cx.background_executor().timer(backoff).await;
+                this.update(cx, |this, cx| {
+                    this.pending_restarts.remove(&id);
+                    this.run_server(server, configuration, cx);
+                })
+                .log_err();
+            }
+        });
+        self.pending_restarts.insert(id, task);
+    }
+
+    fn reset_restarts(&mut self, id: &ContextServerId) {
+        self.restart_attempts.remove(id);
+        self.pending_restarts.remove(id);
+    }
+
+    /// The environment of the user's shell, which stdio servers' executables are looked up in.
+    fn shell_environment(
+        &self,
+        cx: &mut Context<Self>,
+    ) -> Option<Shared<Task<Option<HashMap<String, String>>>>> {
+        let project = self.project.upgrade()?;
+        let directory: Arc<Path> = self
+            .worktree_roots(cx)
+            .into_iter()
+            .next()
+            .unwrap_or_else(|| home_dir().clone())
+            .into();
+        Some(
+            project
+                .read(cx)
+                .environment()
+                .update(cx, |environment, cx| {
+                    environment.local_directory_environment(&Shell::System, directory, cx)
+                }),
+        )
+    }
+
+    /// The visible worktree directories, advertised to servers as roots.
+    fn worktree_roots(&self, cx: &App) -> Vec<PathBuf> {
+        self.worktree_store
+            .read(cx)
+            .visible_worktrees(cx)
+            .map(|worktree| worktree.read(cx).abs_path().to_path_buf())
+            .collect()
+    }
+
+    fn worktree_roots_changed(&mut self, cx: &mut Context<Self>) {
+        let roots = self.worktree_roots(cx);
+        for state in self.servers.values() {
+            if let ContextServerState::Starting { server, .. }
+            | ContextServerState::Running { server, .. } = state
+            {
+                server.set_roots(roots.clone());
+            }
+        }
+    }
+
+    fn update_log_level(
+        &mut self,
+        id: &ContextServerId,
+        configuration: ContextServerConfiguration,
+        cx: &mut Context<Self>,
+    ) {
+        let Some(ContextServerState::Running {
+            server,
+            configuration: current_configuration,
+            ..
+        }) = self.servers.get_mut(id)
+        else {
+            return;
+        };
+        let level = configuration
+            .options()
+            .log_level
+            .map_or(LoggingLevel::Info, Into::into);
+        *current_configuration = Arc::new(configuration);
+
+        let server = server.clone();
+        cx.spawn(async move |_, _| {
+            if let Err(error) = server.set_log_level(level).await
+                && !error.is::<CapabilityNotSupported>()
+            {
+                log::error!("failed to update log level of {}: {error:#}", server.id());
+            }
+        })
+        .detach();
+    }
+
     fn remove_server(&mut self, id: &ContextServerId, cx: &mut Context<Self>) -> Result<()> {
+        self.reset_restarts(id);
         let state = self
             .servers
             .remove(id)
@@ -472,6 +927,8 @@ impl ContextServerStore {
         cx.emit(Event::ServerStatusChanged {
             server_id: id.clone(),
             status: ContextServerStatus::Stopped,
+            server_info: None,
+            capabilities: None,
         });
         Ok(())
     }
@@ -486,14 +943,76 @@ impl ContextServerStore {
             return Ok(factory(id, configuration));
         }
 
-        match configuration.as_ref() {
-            ContextServerConfiguration::Http { url, headers } => Ok(Arc::new(ContextServer::http(
-                id,
+        let server = match configuration.as_ref() {
+            ContextServerConfiguration::Http {
                 url,
-                headers.clone(),
-                cx.http_client(),
-                cx.background_executor().clone(),
-            )?)),
+                headers,
+                timeout,
+                transport,
+                proxy,
+                no_proxy,
+                tls,
+                connection,
+                header_command,
+                ..
+            } => {
+                let server = if *transport == ContextServerHttpTransport::Tcp
+                    || matches!(url.scheme(), "tcp" | "tls")
+                {
+                    ContextServer::tcp(
+                        id,
+                        url,
+                        context_server::tls::client_config(tls.as_ref())?,
+                        gpui_tokio::Tokio::handle(cx),
+                    )?
+                } else if *transport == ContextServerHttpTransport::WebSocket
+                    || matches!(url.scheme(), "ws" | "wss")
+                {
+                    ContextServer::websocket(
+                        id,
+                        url,
+                        headers.clone(),
+                        context_server::tls::client_config(tls.as_ref())?,
+                        gpui_tokio::Tokio::handle(cx),
+                    )?
+                } else {
+                    let server = ContextServer::http(
+                        id,
+                        url,
+                        headers.clone(),
+                        *transport,
+                        server_http_client(
+                            proxy.as_deref(),
+                            no_proxy.as_deref(),
+                            tls.as_ref(),
+                            connection.as_ref(),
+                            cx,
+                        )?,
+                        cx.background_executor().clone(),
+                    )?;
+                    // Servers that only answer tool calls once the tool finished mustn't
+                    // time out before the tool call does.
+                    let request_timeout = connection
+                        .as_ref()
+                        .and_then(|connection| connection.request_timeout)
+                        .map(Duration::from_millis)
+                        .unwrap_or_else(|| {
+                            DEFAULT_HTTP_REQUEST_TIMEOUT
+                                .max(timeout.map_or(Duration::ZERO, Duration::from_millis))
+                        });
+                    let server = server.with_http_request_timeout(request_timeout);
+                    match header_command {
+                        Some(command) => server.with_header_provider(Arc::new(
+                            CommandHeaderProvider::new(command.clone()),
+                        )),
+                        None => server,
+                    }
+                };
+                match timeout {
+                    Some(timeout) => server.with_tool_timeout(Duration::from_millis(*timeout)),
+                    None => server,
+                }
+            }
             _ => {
                 let root_path = self
                     .project
@@ -511,13 +1030,99 @@ impl ContextServerStore {
                             })
                         })
                     });
-                Ok(Arc::new(ContextServer::stdio(
-                    id,
-                    configuration.command().unwrap().clone(),
-                    root_path,
-                )))
+                let root_path = match root_path {
+                    Some(root_path) => root_path,
+                    None => {
+                        log::info!(
+                            "starting context server {id} in the home directory, since the \
+                            project has no folder"
+                        );
+                        home_dir().as_path().into()
+                    }
+                };
+                let worktrees = self
+                    .worktree_store
+                    .read(cx)
+                    .visible_worktrees(cx)
+                    .filter_map(|worktree| {
+                        let worktree = worktree.read(cx);
+                        Some((worktree.root_name_str().to_string(), worktree.root_dir()?))
+                    })
+                    .collect::<Vec<_>>();
+                let expand = |input: &str, is_arg: bool| {
+                    expand_worktree_placeholders(input, &root_path, &worktrees, is_arg)
+                        .with_context(|| format!("invalid configuration for context server {id}"))
+                };
+
+                let mut command = configuration.command().unwrap().clone();
+                command.args = command
+                    .args
+                    .iter()
+                    .map(|arg| expand(arg, true))
+                    .collect::<Result<_>>()?;
+                let working_directory = match &configuration.options().cwd {
+                    Some(cwd) => {
+                        let cwd = expand(cwd, false)?;
+                        root_path.join(expand_home(Path::new(&cwd))).into()
+                    }
+                    None => root_path.clone(),
+                };
+                ContextServer::stdio(id, command, Some(working_directory))
             }
-        }
+        };
+
+        let options = configuration.options();
+        let server = match options.log_level {
+            Some(level) => server.with_log_level(level.into()),
+            None => server,
+        };
+        let server = match options.shutdown_timeout {
+            Some(timeout) => server.with_shutdown_timeout(Duration::from_millis(timeout)),
+            None => server,
+        };
+        let server = match options.initialize_timeout {
+            Some(timeout) => server.with_initialize_timeout(Duration::from_millis(timeout)),
+            None => server,
+        };
+        let server = match options.reconnect_timeout {
+            Some(timeout) => server.with_reconnect_timeout(Duration::from_millis(timeout)),
+            None => server,
+        };
+        let server = match options.validate_tool_arguments {
+            Some(enabled) => server.with_tool_argument_validation(enabled),
+            None => server,
+        };
+        let server = match options.max_tools {
+            Some(max_tools) => server.with_max_tools(max_tools),
+            None => server,
+        };
+        let server = match &options.tools {
+            Some(tools) => server.with_tool_filter(tools.try_into()?),
+            None => server,
+        };
+        let server = match options.max_message_bytes {
+            Some(max_message_bytes) => server.with_max_message_bytes(max_message_bytes),
+            None => server,
+        };
+        let server = match options.list_changed_debounce {
+            Some(debounce) => server.with_list_changed_debounce(Duration::from_millis(debounce)),
+            None => server,
+        };
+        let server = match options.max_concurrent_tool_calls {
+            Some(max) => server.with_max_concurrent_tool_calls(max),
+            None => server,
+        };
+        let server = match &options.retry {
+            Some(retry) => server.with_retry_policy(retry.into()),
+            None => server,
+        };
+        let server = match &options.resource_limits {
+            Some(limits) => server.with_resource_limits(limits.into()),
+            None => server,
+        };
+        let server = server.with_traffic_log_path(traffic_log_path(&server.id()));
+        server.set_log_traffic(options.log_traffic == Some(true));
+        Ok(Arc::new(server))
     }
 
     fn resolve_context_server_settings<'a>(
@@ -542,10 +1147,13 @@ impl ContextServerStore {
         cx: &mut Context<Self>,
     ) {
         let status = ContextServerStatus::from_state(&state);
+        let server = state.server();
         self.servers.insert(id.clone(), state);
         cx.emit(Event::ServerStatusChanged {
             server_id: id,
             status,
+            server_info: server.server_info(),
+            capabilities: server.capabilities(),
         });
     }
 
@@ -580,12 +1188,32 @@ impl ContextServerStore {
             )
         })?;
 
-        for (id, _) in
-            registry.read_with(cx, |registry, _| registry.context_server_descriptors())?
-        {
-            configured_servers
-                .entry(id)
-                .or_insert(ContextServerSettings::default_extension());
+        let extension_servers = registry.read_with(cx, |registry, _| {
+            registry
+                .context_server_descriptors()
+                .into_iter()
+                .filter_map(|(id, _)| {
+                    let source = registry.context_server_source(&id)?;
+                    Some((id, source))
+                })
+                .collect::<Vec<_>>()
+        })?;
+        for (id, source) in extension_servers {
+            match configured_servers.get(&id) {
+                None => {
+                    configured_servers.insert(id, ContextServerSettings::default_extension());
+                }
+                Some(ContextServerSettings::Extension { .. }) => {}
+                // The server defined in the settings takes precedence.
+                Some(_) => log::error!(
+                    "{}",
+                    DuplicateContextServerId {
+                        id: ContextServerId(id),
+                        existing: source,
+                        duplicate: ContextServerSource::Settings { path: None },
+                    }
+                ),
+            }
         }
 
         let (enabled_servers, disabled_servers): (HashMap<_, _>, HashMap<_, _>) =
@@ -610,40 +1238,65 @@ impl ContextServerStore {
         .collect::<HashMap<_, _>>();
 
         let mut servers_to_start = Vec::new();
+        let mut servers_to_reconfigure = Vec::new();
         let mut servers_to_remove = HashSet::default();
         let mut servers_to_stop = HashSet::default();
+        let mut changes = Vec::new();
 
         this.update(cx, |this, cx| {
-            for server_id in this.servers.keys() {
+            for (server_id, state) in &this.servers {
                 // All servers that are not in desired_servers should be removed from the store.
                 // This can happen if the user removed a server from the context server settings.
                 if !configured_servers.contains_key(server_id) {
                     if disabled_servers.contains_key(&server_id.0) {
-                        servers_to_stop.insert(server_id.clone());
+                        if !matches!(state, ContextServerState::Stopped { .. }) {
+                            servers_to_stop.insert(server_id.clone());
+                            changes.push((server_id.clone(), ServerSettingsChange::Disabled));
+                        }
                     } else {
                         servers_to_remove.insert(server_id.clone());
+                        changes.push((server_id.clone(), ServerSettingsChange::Removed));
                     }
                 }
             }
 
             for (id, config) in configured_servers {
                 let state = this.servers.get(&id);
+                if let Some(ContextServerState::Running { configuration, .. }) = state
+                    && **configuration != config
+                    && configuration.differs_only_in_log_level(&config)
+                {
+                    changes.push((id.clone(), ServerSettingsChange::Reconfigured));
+                    servers_to_reconfigure.push((id, config));
+                    continue;
+                }
                 let is_stopped = matches!(state, Some(ContextServerState::Stopped { .. }));
                 let existing_config = state.as_ref().map(|state| state.configuration());
-                if existing_config.as_deref() != Some(&config) || is_stopped {
-                    let config = Arc::new(config);
-                    let server = this.create_context_server(id.clone(), config.clone(), cx)?;
-                    servers_to_start.push((server, config));
-                    if this.servers.contains_key(&id) {
-                        servers_to_stop.insert(id);
+                let change = match existing_config {
+                    None => ServerSettingsChange::Added,
+                    Some(existing_config) if *existing_config != config => {
+                        ServerSettingsChange::Restarted
                     }
+                    Some(_) if is_stopped => ServerSettingsChange::Started,
+                    Some(_) => continue,
+                };
+                let config = Arc::new(config);
+                let server = this.create_context_server(id.clone(), config.clone(), cx)?;
+                servers_to_start.push((server, config));
+                if this.servers.contains_key(&id) {
+                    servers_to_stop.insert(id.clone());
                 }
+                changes.push((id, change));
             }
 
             anyhow::Ok(())
         })??;
 
+        changes.sort_by(|(a, _), (b, _)| a.0.cmp(&b.0));
         this.update(cx, |this, cx| {
+            for (server_id, change) in changes {
+                cx.emit(Event::ServerSettingsChanged { server_id, change });
+            }
             for id in servers_to_stop {
                 this.stop_server(&id, cx)?;
             }
@@ -653,11 +1306,145 @@ impl ContextServerStore {
             for (server, config) in servers_to_start {
                 this.run_server(server, config, cx);
             }
+            for (id, config) in servers_to_reconfigure {
+                this.update_log_level(&id, config, cx);
+            }
             anyhow::Ok(())
         })?
     }
 }
 
+/// The error to show for a server that failed to start.
+fn start_error(error: &ContextServerError, server: &ContextServer) -> Arc<str> {
+    let error = if error.is::<IncompatibleProtocol>() {
+        format!("{error}. Check for a newer version of the server.")
+    } else {
+        error.to_string()
+    };
+    with_traffic_log_path(error, server)
+}
+
+/// Points to the server's traffic log in an error it ran into, if it logs its traffic, so
+/// that the log can be attached to bug reports.
+fn with_traffic_log_path(error: String, server: &ContextServer) -> Arc<str> {
+    match server.traffic_log_path() {
+        Some(path) => format!(
+            "{error}\n\nJSON-RPC traffic is logged to {}",
+            path.display()
+        )
+        .into(),
+        None => error.into(),
+    }
+}
+
+/// The file the traffic of a server is logged to when it's enabled, named after the server
+/// with the characters that aren't safe in file names replaced.
+fn traffic_log_path(id: &ContextServerId) -> PathBuf {
+    let name =
+        id.0.chars()
+            .map(|c| {
+                if c.is_ascii_alphanumeric() || matches!(c, '-' | '_' | '.') {
+                    c
+                } else {
+                    '_'
+                }
+            })
+            .collect::<String>();
+    paths::context_server_logs_dir().join(format!("{name}.log"))
+}
+
+/// The HTTP client for a remote server, which only differs from Zed's own when the server's
+/// settings override the proxy, TLS configuration, connect timeout or keepalive.
+fn server_http_client(
+    proxy: Option<&str>,
+    no_proxy: Option<&str>,
+    tls: Option<&ContextServerTlsSettings>,
+    connection: Option<&ContextServerConnectionSettings>,
+    cx: &App,
+) -> Result<Arc<dyn HttpClient>> {
+    let http_client = cx.http_client();
+    let connection = ConnectionOptions {
+        connect_timeout: connection
+            .and_then(|connection| connection.connect_timeout)
+            .map(Duration::from_millis),
+        keep_alive_interval: connection
+            .and_then(|connection| connection.keep_alive_interval)
+            .map(Duration::from_millis),
+    };
+    if proxy.is_none()
+        && no_proxy.is_none()
+        && tls.is_none()
+        && connection == ConnectionOptions::default()
+    {
+        return Ok(http_client);
+    }
+
+    let proxy = match proxy {
+        Some(proxy) => {
+            Some(Url::parse(proxy).with_context(|| format!("invalid proxy url {proxy:?}"))?)
+        }
+        None => http_client.proxy().cloned(),
+    };
+    let user_agent = http_client
+        .user_agent()
+        .and_then(|user_agent| user_agent.to_str().ok())
+        .unwrap_or("Zed");
+    let tls_config = tls
+        .map(|tls| context_server::tls::client_config(Some(tls)))
+        .transpose()?
+        .map(Arc::unwrap_or_clone);
+    let client = ReqwestClient::proxy_tls_and_user_agent(
+        proxy, no_proxy, tls_config, connection, user_agent,
+    )?;
+    Ok(Arc::new(client))
+}
+
+/// Replaces `${worktree}` in a stdio server's configuration with `default_directory`, and
+/// `${worktree:NAME}` with the root of the worktree named `NAME`.
+///
+/// The server expands environment variables in arguments afterwards, so `$` in the paths
+/// substituted into arguments is escaped.
+fn expand_worktree_placeholders(
+    input: &str,
+    default_directory: &Path,
+    worktrees: &[(String, Arc<Path>)],
+    is_arg: bool,
+) -> Result<String> {
+    const PLACEHOLDER: &str = "${worktree";
+    let escape = |path: &Path| {
+        let path = path.to_string_lossy();
+        if is_arg {
+            path.replace('$', "$$")
+        } else {
+            path.into_owned()
+        }
+    };
+
+    let mut output = String::with_capacity(input.len());
+    let mut rest = input;
+    while let Some(ix) = rest.find(PLACEHOLDER) {
+        output.push_str(&rest[..ix]);
+        rest = &rest[ix + PLACEHOLDER.len()..];
+        if let Some(after) = rest.strip_prefix('}') {
+            output.push_str(&escape(default_directory));
+            rest = after;
+        } else if let Some((name, after)) =
+            rest.strip_prefix(':').and_then(|rest| rest.split_once('}'))
+        {
+            let (_, path) = worktrees
+                .iter()
+                .find(|(root_name, _)| root_name == name)
+                .with_context(|| format!("no project folder named {name:?} in {input:?}"))?;
+            output.push_str(&escape(path));
+            rest = after;
+        } else {
+            output.push_str(PLACEHOLDER);
+        }
+    }
+    output.push_str(rest);
+    Ok(output)
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -669,25 +1456,94 @@ mod tests {
     use gpui::{AppContext, TestAppContext, UpdateGlobal as _};
     use http_client::{FakeHttpClient, Response};
     use serde_json::json;
-    use std::{cell::RefCell, path::PathBuf, rc::Rc};
+    use std::{
+        cell::RefCell,
+        path::{Path, PathBuf},
+        rc::Rc,
+    };
     use util::path;
 
     #[gpui::test]
-    async fn test_context_server_status(cx: &mut TestAppContext) {
-        const SERVER_1_ID: &str = "mcp-1";
-        const SERVER_2_ID: &str = "mcp-2";
+    fn test_server_http_client_proxy(cx: &mut TestAppContext) {
+        cx.update(|cx| {
+            let client = server_http_client(None, None, None, None, cx).unwrap();
+            assert_eq!(client.proxy(), cx.http_client().proxy());
+
+            let proxy = "http://proxy.example.com:8080";
+            let client =
+                server_http_client(Some(proxy), Some("localhost"), None, None, cx).unwrap();
+            assert_eq!(client.proxy(), Some(&Url::parse(proxy).unwrap()));
+
+            let error = server_http_client(Some("not a url"), None, None, None, cx).unwrap_err();
+            assert_eq!(error.to_string(), "invalid proxy url \"not a url\"");
+        });
+    }
 
+    #[test]
+    fn test_expand_worktree_placeholders() {
+        let worktrees = vec![
+            ("frontend".to_string(), Path::new("/code/frontend").into()),
+            ("backend".to_string(), Path::new("/code/$backend").into()),
+        ];
+        let expand = |input: &str| {
+            expand_worktree_placeholders(input, Path::new("/code/frontend"), &worktrees, true)
+        };
+
+        assert_eq!(
+            expand("--root=${worktree}").unwrap(),
+            "--root=/code/frontend"
+        );
+        assert_eq!(
+            expand("${worktree:frontend}:${worktree:backend}").unwrap(),
+            "/code/frontend:/code/$$backend"
+        );
+        assert_eq!(expand("${HOME}/${worktree").unwrap(), "${HOME}/${worktree");
+        assert_eq!(expand("${worktrees}").unwrap(), "${worktrees}");
+        assert_eq!(
+            expand("${worktree:docs}").unwrap_err().to_string(),
+            "no project folder named \"docs\" in \"${worktree:docs}\""
+        );
+
+        assert_eq!(
+            expand_worktree_placeholders(
+                "${worktree:backend}/src",
+                Path::new("/code/frontend"),
+                &worktrees,
+                false
+            )
+            .unwrap(),
+            "/code/$backend/src"
+        );
+    }
+
+    #[gpui::test]
+    async fn test_duplicate_server_ids(cx: &mut TestAppContext) {
         let (_fs, project) = setup_context_server_test(
             cx,
             json!({"code.rs": ""}),
             vec![
-                (SERVER_1_ID.into(), dummy_server_settings()),
-                (SERVER_2_ID.into(), dummy_server_settings()),
+                ("from-settings".into(), dummy_server_settings()),
+                (
+                    "from-extension".into(),
+                    ContextServerSettings::default_extension(),
+                ),
             ],
         )
         .await;
 
-        let registry = cx.new(|_| ContextServerDescriptorRegistry::new());
+        let extension = |name: &str| ContextServerSource::Extension { name: name.into() };
+        let registry = cx.new(|cx| {
+            let mut registry = ContextServerDescriptorRegistry::new();
+            registry
+                .register_context_server_descriptor(
+                    "from-extension".into(),
+                    Arc::new(FakeContextServerDescriptor::new("ext-server")),
+                    extension("my-extension"),
+                    cx,
+                )
+                .unwrap();
+            registry
+        });
         let store = cx.new(|cx| {
             ContextServerStore::test(
                 registry.clone(),
@@ -697,15 +1553,97 @@ mod tests {
             )
         });
 
-        let server_1_id = ContextServerId(SERVER_1_ID.into());
-        let server_2_id = ContextServerId(SERVER_2_ID.into());
+        let settings_file = ContextServerSource::Settings {
+            path: Some(Path::new("/home/user/.config/zed/settings.json").into()),
+        };
+        cx.update(|cx| {
+            let check = |id: &str| {
+                store.read(cx).check_new_server_id(
+                    &ContextServerId(id.into()),
+                    settings_file.clone(),
+                    cx,
+                )
+            };
+
+            let error = check("from-settings").unwrap_err();
+            assert_eq!(error.existing, ContextServerSource::Settings { path: None });
+            assert_eq!(error.duplicate, settings_file);
+            assert_eq!(
+                error.to_string(),
+                "context server \"from-settings\" from settings \
+                (/home/user/.config/zed/settings.json) is already defined by settings"
+            );
 
-        let server_1 = Arc::new(ContextServer::new(
-            server_1_id.clone(),
-            Arc::new(create_fake_transport(SERVER_1_ID, cx.executor())),
-        ));
-        let server_2 = Arc::new(ContextServer::new(
-            server_2_id.clone(),
+            let error = check("from-extension").unwrap_err();
+            assert_eq!(error.existing, extension("my-extension"));
+            assert_eq!(
+                error.to_string(),
+                "context server \"from-extension\" from settings \
+                (/home/user/.config/zed/settings.json) is already defined by extension \
+                my-extension"
+            );
+
+            check("new-server").unwrap();
+        });
+
+        registry.update(cx, |registry, cx| {
+            let error = registry
+                .register_context_server_descriptor(
+                    "from-extension".into(),
+                    Arc::new(FakeContextServerDescriptor::new("other-server")),
+                    extension("other-extension"),
+                    cx,
+                )
+                .unwrap_err();
+            assert_eq!(error.existing, extension("my-extension"));
+            assert_eq!(error.duplicate, extension("other-extension"));
+
+            // Extensions can register their servers again when they are reloaded.
+            registry
+                .register_context_server_descriptor(
+                    "from-extension".into(),
+                    Arc::new(FakeContextServerDescriptor::new("ext-server")),
+                    extension("my-extension"),
+                    cx,
+                )
+                .unwrap();
+        });
+    }
+
+    #[gpui::test]
+    async fn test_context_server_status(cx: &mut TestAppContext) {
+        const SERVER_1_ID: &str = "mcp-1";
+        const SERVER_2_ID: &str = "mcp-2";
+
+        let (_fs, project) = setup_context_server_test(
+            cx,
+            json!({"code.rs": ""}),
+            vec![
+                (SERVER_1_ID.into(), dummy_server_settings()),
+                (SERVER_2_ID.into(), dummy_server_settings()),
+            ],
+        )
+        .await;
+
+        let registry = cx.new(|_| ContextServerDescriptorRegistry::new());
+        let store = cx.new(|cx| {
+            ContextServerStore::test(
+                registry.clone(),
+                project.read(cx).worktree_store(),
+                project.downgrade(),
+                cx,
+            )
+        });
+
+        let server_1_id = ContextServerId(SERVER_1_ID.into());
+        let server_2_id = ContextServerId(SERVER_2_ID.into());
+
+        let server_1 = Arc::new(ContextServer::new(
+            server_1_id.clone(),
+            Arc::new(create_fake_transport(SERVER_1_ID, cx.executor())),
+        ));
+        let server_2 = Arc::new(ContextServer::new(
+            server_2_id.clone(),
             Arc::new(create_fake_transport(SERVER_2_ID, cx.executor())),
         ));
 
@@ -752,6 +1690,83 @@ mod tests {
         });
     }
 
+    #[gpui::test]
+    async fn test_context_server_roots(cx: &mut TestAppContext) {
+        const SERVER_1_ID: &str = "mcp-1";
+
+        let (fs, _) = setup_context_server_test(
+            cx,
+            json!({"a": {"a.rs": ""}, "b": {"b.rs": ""}, "c": {"c.rs": ""}}),
+            vec![(SERVER_1_ID.into(), dummy_server_settings())],
+        )
+        .await;
+        let project = Project::test(
+            fs,
+            [path!("/test/a").as_ref(), path!("/test/b").as_ref()],
+            cx,
+        )
+        .await;
+
+        let registry = cx.new(|_| ContextServerDescriptorRegistry::new());
+        let store = cx.new(|cx| {
+            ContextServerStore::test(
+                registry.clone(),
+                project.read(cx).worktree_store(),
+                project.downgrade(),
+                cx,
+            )
+        });
+
+        let transport = Arc::new(create_fake_transport(SERVER_1_ID, cx.executor()));
+        let server = Arc::new(ContextServer::new(
+            ContextServerId(SERVER_1_ID.into()),
+            transport.clone(),
+        ));
+        store.update(cx, |store, cx| store.start_server(server, cx));
+        cx.run_until_parked();
+
+        let list_roots = |id| {
+            let response =
+                transport.request::<context_server::types::requests::ListRoots>(id, None);
+            async move {
+                let response = response.await.unwrap();
+                serde_json::from_value::<context_server::types::ListRootsResponse>(
+                    response["result"].clone(),
+                )
+                .unwrap()
+                .roots
+                .into_iter()
+                .map(|root| root.uri.to_file_path().unwrap())
+                .collect::<Vec<_>>()
+            }
+        };
+
+        assert_eq!(
+            list_roots(1).await,
+            vec![
+                PathBuf::from(path!("/test/a")),
+                PathBuf::from(path!("/test/b"))
+            ]
+        );
+
+        project
+            .update(cx, |project, cx| {
+                project.find_or_create_worktree(path!("/test/c"), true, cx)
+            })
+            .await
+            .unwrap();
+        cx.run_until_parked();
+
+        assert_eq!(
+            list_roots(2).await,
+            vec![
+                PathBuf::from(path!("/test/a")),
+                PathBuf::from(path!("/test/b")),
+                PathBuf::from(path!("/test/c"))
+            ]
+        );
+    }
+
     #[gpui::test]
     async fn test_context_server_status_events(cx: &mut TestAppContext) {
         const SERVER_1_ID: &str = "mcp-1";
@@ -814,6 +1829,79 @@ mod tests {
             .unwrap();
     }
 
+    #[gpui::test]
+    async fn test_context_server_restarts_when_unresponsive(cx: &mut TestAppContext) {
+        const SERVER_ID: &str = "mcp-1";
+
+        let (_fs, project) = setup_context_server_test(
+            cx,
+            json!({"code.rs": ""}),
+            vec![(
+                SERVER_ID.into(),
+                ContextServerSettings::Custom {
+                    enabled: true,
+                    command: ContextServerCommand {
+                        path: "somebinary".into(),
+                        args: vec!["arg".to_string()],
+                        env: None,
+                        timeout: None,
+                    },
+                    options: ContextServerOptions {
+                        ping_interval: Some(1000),
+                        max_restarts: Some(1),
+                        ..Default::default()
+                    },
+                },
+            )],
+        )
+        .await;
+
+        let registry = cx.new(|_| ContextServerDescriptorRegistry::new());
+        let store = cx.new(|cx| {
+            ContextServerStore::test(
+                registry.clone(),
+                project.read(cx).worktree_store(),
+                project.downgrade(),
+                cx,
+            )
+        });
+
+        let server_id = ContextServerId(SERVER_ID.into());
+        // The fake transport never answers pings.
+        let server = Arc::new(ContextServer::new(
+            server_id.clone(),
+            Arc::new(create_fake_transport(SERVER_ID, cx.executor())),
+        ));
+        let stopped_responding =
+            ContextServerStatus::Error("Context server stopped responding".into());
+
+        let _server_events = assert_server_events(
+            &store,
+            vec![
+                (server_id.clone(), ContextServerStatus::Starting),
+                (server_id.clone(), ContextServerStatus::Running),
+                (server_id.clone(), stopped_responding.clone()),
+                (server_id.clone(), ContextServerStatus::Starting),
+                (server_id.clone(), ContextServerStatus::Running),
+                (server_id.clone(), stopped_responding.clone()),
+            ],
+            cx,
+        );
+
+        store.update(cx, |store, cx| store.start_server(server, cx));
+        cx.run_until_parked();
+
+        // Give up once the server has been restarted `max_restarts` times.
+        for _ in 0..120 {
+            cx.executor().advance_clock(Duration::from_secs(1));
+            cx.run_until_parked();
+        }
+        assert_eq!(
+            store.read_with(cx, |store, _| store.status_for_server(&server_id)),
+            Some(stopped_responding)
+        );
+    }
+
     #[gpui::test(iterations = 25)]
     async fn test_context_server_concurrent_starts(cx: &mut TestAppContext) {
         const SERVER_1_ID: &str = "mcp-1";
@@ -895,6 +1983,7 @@ mod tests {
                     settings: json!({
                         "somevalue": true
                     }),
+                    options: Default::default(),
                 },
             )],
         )
@@ -903,7 +1992,16 @@ mod tests {
         let executor = cx.executor();
         let registry = cx.new(|cx| {
             let mut registry = ContextServerDescriptorRegistry::new();
-            registry.register_context_server_descriptor(SERVER_1_ID.into(), fake_descriptor_1, cx);
+            registry
+                .register_context_server_descriptor(
+                    SERVER_1_ID.into(),
+                    fake_descriptor_1,
+                    ContextServerSource::Extension {
+                        name: "fake-extension".into(),
+                    },
+                    cx,
+                )
+                .unwrap();
             registry
         });
         let store = cx.new(|cx| {
@@ -953,6 +2051,7 @@ mod tests {
                         settings: json!({
                             "somevalue": false
                         }),
+                        options: Default::default(),
                     },
                 )],
                 cx,
@@ -972,6 +2071,30 @@ mod tests {
                         settings: json!({
                             "somevalue": false
                         }),
+                        options: Default::default(),
+                    },
+                )],
+                cx,
+            );
+
+            cx.run_until_parked();
+        }
+
+        // Ensure that mcp-1 is not restarted when only the log level was changed
+        {
+            let _server_events = assert_server_events(&store, vec![], cx);
+            set_context_server_configuration(
+                vec![(
+                    server_1_id.0.clone(),
+                    settings::ContextServerSettingsContent::Extension {
+                        enabled: true,
+                        settings: json!({
+                            "somevalue": false
+                        }),
+                        options: ContextServerOptions {
+                            log_level: Some(settings::ContextServerLogLevel::Debug),
+                            ..Default::default()
+                        },
                     },
                 )],
                 cx,
@@ -999,6 +2122,7 @@ mod tests {
                             settings: json!({
                                 "somevalue": false
                             }),
+                            options: Default::default(),
                         },
                     ),
                     (
@@ -1011,6 +2135,7 @@ mod tests {
                                 env: None,
                                 timeout: None,
                             },
+                            options: Default::default(),
                         },
                     ),
                 ],
@@ -1040,6 +2165,7 @@ mod tests {
                             settings: json!({
                                 "somevalue": false
                             }),
+                            options: Default::default(),
                         },
                     ),
                     (
@@ -1052,6 +2178,7 @@ mod tests {
                                 env: None,
                                 timeout: None,
                             },
+                            options: Default::default(),
                         },
                     ),
                 ],
@@ -1076,6 +2203,7 @@ mod tests {
                         settings: json!({
                             "somevalue": false
                         }),
+                        options: Default::default(),
                     },
                 )],
                 cx,
@@ -1099,6 +2227,7 @@ mod tests {
                         settings: json!({
                             "somevalue": false
                         }),
+                        options: Default::default(),
                     },
                 )],
                 cx,
@@ -1135,6 +2264,7 @@ mod tests {
                         env: None,
                         timeout: None,
                     },
+                    options: Default::default(),
                 },
             )],
         )
@@ -1188,6 +2318,7 @@ mod tests {
                             env: None,
                             timeout: None,
                         },
+                        options: Default::default(),
                     },
                 )],
                 cx,
@@ -1217,6 +2348,7 @@ mod tests {
                             timeout: None,
                             env: None,
                         },
+                        options: Default::default(),
                     },
                 )],
                 cx,
@@ -1226,6 +2358,213 @@ mod tests {
         }
     }
 
+    #[gpui::test]
+    async fn test_context_server_settings_edit_one_of_three(cx: &mut TestAppContext) {
+        let server_ids = ["mcp-1", "mcp-2", "mcp-3"].map(|id| ContextServerId(id.into()));
+        let store = setup_settings_diff_test(cx).await;
+
+        {
+            let (changes, _subscription) = record_settings_changes(&store, cx);
+            set_context_server_configuration(
+                server_ids
+                    .iter()
+                    .map(|id| (id.0.clone(), custom_server_settings("arg", None)))
+                    .collect(),
+                cx,
+            );
+            cx.run_until_parked();
+            assert_eq!(
+                *changes.borrow(),
+                server_ids
+                    .iter()
+                    .map(|id| (id.clone(), ServerSettingsChange::Added))
+                    .collect::<Vec<_>>()
+            );
+        }
+
+        // Only the server whose arguments changed is restarted.
+        {
+            let (changes, _subscription) = record_settings_changes(&store, cx);
+            let _server_events = assert_server_events(
+                &store,
+                vec![
+                    (server_ids[1].clone(), ContextServerStatus::Stopped),
+                    (server_ids[1].clone(), ContextServerStatus::Starting),
+                    (server_ids[1].clone(), ContextServerStatus::Running),
+                ],
+                cx,
+            );
+            set_context_server_configuration(
+                vec![
+                    (server_ids[0].0.clone(), custom_server_settings("arg", None)),
+                    (
+                        server_ids[1].0.clone(),
+                        custom_server_settings("other", None),
+                    ),
+                    (server_ids[2].0.clone(), custom_server_settings("arg", None)),
+                ],
+                cx,
+            );
+            cx.run_until_parked();
+            assert_eq!(
+                *changes.borrow(),
+                vec![(server_ids[1].clone(), ServerSettingsChange::Restarted)]
+            );
+        }
+
+        // Removing one server and disabling another leaves the third running.
+        {
+            let (changes, _subscription) = record_settings_changes(&store, cx);
+            let mut disabled = custom_server_settings("arg", None);
+            disabled.set_enabled(false);
+            set_context_server_configuration(
+                vec![
+                    (server_ids[0].0.clone(), custom_server_settings("arg", None)),
+                    (server_ids[2].0.clone(), disabled),
+                ],
+                cx,
+            );
+            cx.run_until_parked();
+            assert_eq!(
+                *changes.borrow(),
+                vec![
+                    (server_ids[1].clone(), ServerSettingsChange::Removed),
+                    (server_ids[2].clone(), ServerSettingsChange::Disabled),
+                ]
+            );
+            cx.update(|cx| {
+                let store = store.read(cx);
+                assert_eq!(
+                    store.status_for_server(&server_ids[0]),
+                    Some(ContextServerStatus::Running)
+                );
+                assert_eq!(store.status_for_server(&server_ids[1]), None);
+                assert_eq!(
+                    store.status_for_server(&server_ids[2]),
+                    Some(ContextServerStatus::Stopped)
+                );
+            });
+        }
+    }
+
+    #[gpui::test]
+    async fn test_context_server_settings_env_change(cx: &mut TestAppContext) {
+        let server_id = ContextServerId("mcp-1".into());
+        let store = setup_settings_diff_test(cx).await;
+
+        set_context_server_configuration(
+            vec![(
+                server_id.0.clone(),
+                custom_server_settings("arg", Some(&[("A", "1"), ("B", "2")])),
+            )],
+            cx,
+        );
+        cx.run_until_parked();
+
+        // The order the variables are listed in doesn't matter.
+        {
+            let (changes, _subscription) = record_settings_changes(&store, cx);
+            let _server_events = assert_server_events(&store, vec![], cx);
+            set_context_server_configuration(
+                vec![(
+                    server_id.0.clone(),
+                    custom_server_settings("arg", Some(&[("B", "2"), ("A", "1")])),
+                )],
+                cx,
+            );
+            cx.run_until_parked();
+            assert!(changes.borrow().is_empty());
+        }
+
+        {
+            let (changes, _subscription) = record_settings_changes(&store, cx);
+            let _server_events = assert_server_events(
+                &store,
+                vec![
+                    (server_id.clone(), ContextServerStatus::Stopped),
+                    (server_id.clone(), ContextServerStatus::Starting),
+                    (server_id.clone(), ContextServerStatus::Running),
+                ],
+                cx,
+            );
+            set_context_server_configuration(
+                vec![(
+                    server_id.0.clone(),
+                    custom_server_settings("arg", Some(&[("A", "1"), ("B", "3")])),
+                )],
+                cx,
+            );
+            cx.run_until_parked();
+            assert_eq!(
+                *changes.borrow(),
+                vec![(server_id.clone(), ServerSettingsChange::Restarted)]
+            );
+        }
+    }
+
+    async fn setup_settings_diff_test(cx: &mut TestAppContext) -> Entity<ContextServerStore> {
+        let (_fs, project) = setup_context_server_test(cx, json!({"code.rs": ""}), vec![]).await;
+        let executor = cx.executor();
+        let registry = cx.new(|_| ContextServerDescriptorRegistry::new());
+        let store = cx.new(|cx| {
+            ContextServerStore::test_maintain_server_loop(
+                Some(Box::new(move |id, _| {
+                    Arc::new(ContextServer::new(
+                        id.clone(),
+                        Arc::new(create_fake_transport(id.0.to_string(), executor.clone())),
+                    ))
+                })),
+                registry,
+                project.read(cx).worktree_store(),
+                project.downgrade(),
+                cx,
+            )
+        });
+        cx.run_until_parked();
+        store
+    }
+
+    fn custom_server_settings(
+        arg: &str,
+        env: Option<&[(&str, &str)]>,
+    ) -> settings::ContextServerSettingsContent {
+        settings::ContextServerSettingsContent::Custom {
+            enabled: true,
+            command: ContextServerCommand {
+                path: "somebinary".into(),
+                args: vec![arg.to_string()],
+                env: env.map(|env| {
+                    env.iter()
+                        .map(|(key, value)| (key.to_string(), value.to_string()))
+                        .collect()
+                }),
+                timeout: None,
+            },
+            options: Default::default(),
+        }
+    }
+
+    fn record_settings_changes(
+        store: &Entity<ContextServerStore>,
+        cx: &mut TestAppContext,
+    ) -> (
+        Rc<RefCell<Vec<(ContextServerId, ServerSettingsChange)>>>,
+        Subscription,
+    ) {
+        let changes = Rc::new(RefCell::new(Vec::new()));
+        let subscription = cx.update(|cx| {
+            cx.subscribe(store, {
+                let changes = changes.clone();
+                move |_, event, _| {
+                    if let Event::ServerSettingsChanged { server_id, change } = event {
+                        changes.borrow_mut().push((server_id.clone(), *change));
+                    }
+                }
+            })
+        });
+        (changes, subscription)
+    }
+
     fn set_context_server_configuration(
         context_servers: Vec<(Arc<str>, settings::ContextServerSettingsContent)>,
         cx: &mut TestAppContext,
@@ -1257,6 +2596,14 @@ mod tests {
                     enabled: true,
                     url: server_url.to_string(),
                     headers: Default::default(),
+                    timeout: None,
+                    transport: None,
+                    proxy: None,
+                    no_proxy: None,
+                    tls: None,
+                    connection: None,
+                    header_command: None,
+                    options: Default::default(),
                 },
             )],
         )
@@ -1336,6 +2683,7 @@ mod tests {
                 env: None,
                 timeout: None,
             },
+            options: Default::default(),
         }
     }
 
@@ -1354,6 +2702,7 @@ mod tests {
                     Event::ServerStatusChanged {
                         server_id: actual_server_id,
                         status: actual_status,
+                        ..
                     } => {
                         let (expected_server_id, expected_status) = &expected_events[ix];
 
@@ -1370,6 +2719,7 @@ mod tests {
                         ix += 1;
                         *received_event_count.borrow_mut() += 1;
                     }
+                    Event::ServerSettingsChanged { .. } => {}
                 }
             });
             ServerEvents {
@@ -7,6 +7,7 @@ use extension::{
     ProjectDelegate,
 };
 use gpui::{App, AsyncApp, Entity, Task};
+use util::ResultExt as _;
 
 use crate::worktree_store::WorktreeStore;
 
@@ -100,15 +101,20 @@ struct ContextServerDescriptorRegistryProxy {
 
 impl ExtensionContextServerProxy for ContextServerDescriptorRegistryProxy {
     fn register_context_server(&self, extension: Arc<dyn Extension>, id: Arc<str>, cx: &mut App) {
+        let source = registry::ContextServerSource::Extension {
+            name: extension.manifest().id.clone(),
+        };
         self.context_server_factory_registry
             .update(cx, |registry, cx| {
                 registry.register_context_server_descriptor(
                     id.clone(),
                     Arc::new(ContextServerDescriptor { id, extension })
                         as Arc<dyn registry::ContextServerDescriptor>,
+                    source,
                     cx,
                 )
-            });
+            })
+            .log_err();
     }
 
     fn unregister_context_server(&self, server_id: Arc<str>, cx: &mut App) {
@@ -1,8 +1,10 @@
+use std::fmt;
+use std::path::Path;
 use std::sync::Arc;
 
 use anyhow::Result;
 use collections::HashMap;
-use context_server::ContextServerCommand;
+use context_server::{ContextServerCommand, ContextServerId};
 use extension::ContextServerConfiguration;
 use gpui::{App, AppContext as _, AsyncApp, Context, Entity, Global, Task};
 
@@ -21,13 +23,57 @@ pub trait ContextServerDescriptor {
     ) -> Task<Result<Option<ContextServerConfiguration>>>;
 }
 
+/// Where a context server is defined.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ContextServerSource {
+    /// An entry in `context_servers`, in the given settings file if it's known.
+    Settings { path: Option<Arc<Path>> },
+    /// An extension, identified by its ID.
+    Extension { name: Arc<str> },
+}
+
+impl fmt::Display for ContextServerSource {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Settings { path: Some(path) } => write!(f, "settings ({})", path.display()),
+            Self::Settings { path: None } => write!(f, "settings"),
+            Self::Extension { name } => write!(f, "extension {name}"),
+        }
+    }
+}
+
+/// A context server was defined with the ID of another one.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DuplicateContextServerId {
+    pub id: ContextServerId,
+    pub existing: ContextServerSource,
+    pub duplicate: ContextServerSource,
+}
+
+impl std::error::Error for DuplicateContextServerId {}
+
+impl fmt::Display for DuplicateContextServerId {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "context server {:?} from {} is already defined by {}",
+            self.id.0, self.duplicate, self.existing
+        )
+    }
+}
+
 struct GlobalContextServerDescriptorRegistry(Entity<ContextServerDescriptorRegistry>);
 
 impl Global for GlobalContextServerDescriptorRegistry {}
 
 #[derive(Default)]
 pub struct ContextServerDescriptorRegistry {
-    context_servers: HashMap<Arc<str>, Arc<dyn ContextServerDescriptor>>,
+    context_servers: HashMap<Arc<str>, RegisteredDescriptor>,
+}
+
+struct RegisteredDescriptor {
+    descriptor: Arc<dyn ContextServerDescriptor>,
+    source: ContextServerSource,
 }
 
 impl ContextServerDescriptorRegistry {
@@ -53,23 +99,47 @@ impl ContextServerDescriptorRegistry {
     pub fn context_server_descriptors(&self) -> Vec<(Arc<str>, Arc<dyn ContextServerDescriptor>)> {
         self.context_servers
             .iter()
-            .map(|(id, factory)| (id.clone(), factory.clone()))
+            .map(|(id, registered)| (id.clone(), registered.descriptor.clone()))
             .collect()
     }
 
     pub fn context_server_descriptor(&self, id: &str) -> Option<Arc<dyn ContextServerDescriptor>> {
-        self.context_servers.get(id).cloned()
+        self.context_servers
+            .get(id)
+            .map(|registered| registered.descriptor.clone())
     }
 
-    /// Registers the provided [`ContextServerDescriptor`].
+    /// Returns where the server with the given ID was registered from.
+    pub fn context_server_source(&self, id: &str) -> Option<ContextServerSource> {
+        self.context_servers
+            .get(id)
+            .map(|registered| registered.source.clone())
+    }
+
+    /// Registers the provided [`ContextServerDescriptor`], replacing the one registered
+    /// for the same ID from the same source.
+    ///
+    /// Fails if another source already registered a server with the ID.
     pub fn register_context_server_descriptor(
         &mut self,
         id: Arc<str>,
         descriptor: Arc<dyn ContextServerDescriptor>,
+        source: ContextServerSource,
         cx: &mut Context<Self>,
-    ) {
-        self.context_servers.insert(id, descriptor);
+    ) -> Result<(), DuplicateContextServerId> {
+        if let Some(existing) = self.context_servers.get(&id)
+            && existing.source != source
+        {
+            return Err(DuplicateContextServerId {
+                id: ContextServerId(id),
+                existing: existing.source.clone(),
+                duplicate: source,
+            });
+        }
+        self.context_servers
+            .insert(id, RegisteredDescriptor { descriptor, source });
         cx.notify();
+        Ok(())
     }
 
     /// Unregisters the [`ContextServerDescriptor`] for the server with the given ID.
@@ -1,6 +1,9 @@
 use anyhow::Context as _;
 use collections::HashMap;
-use context_server::ContextServerCommand;
+use context_server::{
+    ContextServerCommand, ContextServerConnectionSettings, ContextServerHeaderCommand,
+    ContextServerHttpTransport, ContextServerOptions, ContextServerTlsSettings,
+};
 use dap::adapters::DebugAdapterName;
 use fs::Fs;
 use futures::StreamExt as _;
@@ -124,6 +127,9 @@ pub enum ContextServerSettings {
 
         #[serde(flatten)]
         command: ContextServerCommand,
+
+        #[serde(flatten)]
+        options: ContextServerOptions,
     },
     Extension {
         /// Whether the context server is enabled.
@@ -134,6 +140,9 @@ pub enum ContextServerSettings {
         /// Consult the documentation for the context server to see what settings
         /// are supported.
         settings: serde_json::Value,
+
+        #[serde(flatten)]
+        options: ContextServerOptions,
     },
     Http {
         /// Whether the context server is enabled.
@@ -144,26 +153,80 @@ pub enum ContextServerSettings {
         /// Optional authentication configuration for the remote server.
         #[serde(skip_serializing_if = "HashMap::is_empty", default)]
         headers: HashMap<String, String>,
+        /// Timeout for tool calls in milliseconds. Defaults to 60000 (60 seconds) if not specified.
+        #[serde(skip_serializing_if = "Option::is_none", default)]
+        timeout: Option<u64>,
+        /// The protocol used to talk to the remote context server. Defaults to `auto`.
+        #[serde(skip_serializing_if = "Option::is_none", default)]
+        transport: Option<ContextServerHttpTransport>,
+        /// The proxy to connect to the remote context server through, overriding Zed's
+        /// `proxy` setting.
+        #[serde(skip_serializing_if = "Option::is_none", default)]
+        proxy: Option<String>,
+        /// A comma-separated list of hosts to connect to directly rather than through the
+        /// proxy, overriding the `NO_PROXY` environment variable. `*` bypasses the proxy.
+        #[serde(skip_serializing_if = "Option::is_none", default)]
+        no_proxy: Option<String>,
+        /// TLS settings for servers using a private CA or requiring client certificates.
+        #[serde(skip_serializing_if = "Option::is_none", default)]
+        tls: Option<ContextServerTlsSettings>,
+        /// Timeouts and keepalive of the connections to the remote server.
+        #[serde(skip_serializing_if = "Option::is_none", default)]
+        connection: Option<ContextServerConnectionSettings>,
+        /// A command printing a token to authenticate with, sent in addition to `headers`.
+        #[serde(skip_serializing_if = "Option::is_none", default)]
+        header_command: Option<ContextServerHeaderCommand>,
+
+        #[serde(flatten)]
+        options: ContextServerOptions,
     },
 }
 
 impl From<settings::ContextServerSettingsContent> for ContextServerSettings {
     fn from(value: settings::ContextServerSettingsContent) -> Self {
         match value {
-            settings::ContextServerSettingsContent::Custom { enabled, command } => {
-                ContextServerSettings::Custom { enabled, command }
-            }
-            settings::ContextServerSettingsContent::Extension { enabled, settings } => {
-                ContextServerSettings::Extension { enabled, settings }
-            }
+            settings::ContextServerSettingsContent::Custom {
+                enabled,
+                command,
+                options,
+            } => ContextServerSettings::Custom {
+                enabled,
+                command,
+                options,
+            },
+            settings::ContextServerSettingsContent::Extension {
+                enabled,
+                settings,
+                options,
+            } => ContextServerSettings::Extension {
+                enabled,
+                settings,
+                options,
+            },
             settings::ContextServerSettingsContent::Http {
                 enabled,
                 url,
                 headers,
+                timeout,
+                transport,
+                proxy,
+                no_proxy,
+                tls,
+                connection,
+                header_command,
+                options,
             } => ContextServerSettings::Http {
                 enabled,
                 url,
                 headers,
+                timeout,
+                transport,
+                proxy,
+                no_proxy,
+                tls,
+                connection,
+                header_command,
+                options,
             },
         }
     }
@@ -171,20 +234,48 @@ impl From<settings::ContextServerSettingsContent> for ContextServerSettings {
 impl Into<settings::ContextServerSettingsContent> for ContextServerSettings {
     fn into(self) -> settings::ContextServerSettingsContent {
         match self {
-            ContextServerSettings::Custom { enabled, command } => {
-                settings::ContextServerSettingsContent::Custom { enabled, command }
-            }
-            ContextServerSettings::Extension { enabled, settings } => {
-                settings::ContextServerSettingsContent::Extension { enabled, settings }
-            }
+            ContextServerSettings::Custom {
+                enabled,
+                command,
+                options,
+            } => settings::ContextServerSettingsContent::Custom {
+                enabled,
+                command,
+                options,
+            },
+            ContextServerSettings::Extension {
+                enabled,
+                settings,
+                options,
+            } => settings::ContextServerSettingsContent::Extension {
+                enabled,
+                settings,
+                options,
+            },
             ContextServerSettings::Http {
                 enabled,
                 url,
                 headers,
+                timeout,
+                transport,
+                proxy,
+                no_proxy,
+                tls,
+                connection,
+                header_command,
+                options,
             } => settings::ContextServerSettingsContent::Http {
                 enabled,
                 url,
                 headers,
+                timeout,
+                transport,
+                proxy,
+                no_proxy,
+                tls,
+                connection,
+                header_command,
+                options,
             },
         }
     }
@@ -195,6 +286,15 @@ impl ContextServerSettings {
         Self::Extension {
             enabled: true,
             settings: serde_json::json!({}),
+            options: ContextServerOptions::default(),
+        }
+    }
+
+    pub fn options(&self) -> &ContextServerOptions {
+        match self {
+            ContextServerSettings::Custom { options, .. } => options,
+            ContextServerSettings::Extension { options, .. } => options,
+            ContextServerSettings::Http { options, .. } => options,
         }
     }
 
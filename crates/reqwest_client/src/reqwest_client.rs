@@ -16,6 +16,17 @@ const DEFAULT_CAPACITY: usize = 4096;
 static RUNTIME: OnceLock<tokio::runtime::Runtime> = OnceLock::new();
 static REDACT_REGEX: LazyLock<Regex> = LazyLock::new(|| Regex::new(r"key=[^&]+").unwrap());
 
+/// How the connections of a client are established and kept alive, where that differs from
+/// the defaults.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct ConnectionOptions {
+    /// How long to wait for a connection. Defaults to 10 seconds.
+    pub connect_timeout: Option<Duration>,
+    /// How often to check that idle connections are alive, with TCP keepalive probes and
+    /// HTTP/2 pings. Defaults to the operating system's TCP keepalive settings.
+    pub keep_alive_interval: Option<Duration>,
+}
+
 pub struct ReqwestClient {
     client: reqwest::Client,
     proxy: Option<Url>,
@@ -45,11 +56,33 @@ impl ReqwestClient {
     }
 
     pub fn proxy_and_user_agent(proxy: Option<Url>, user_agent: &str) -> anyhow::Result<Self> {
+        Self::proxy_tls_and_user_agent(proxy, None, None, ConnectionOptions::default(), user_agent)
+    }
+
+    /// Like [`Self::proxy_and_user_agent`], but with the hosts that bypass the proxy given
+    /// as a comma-separated list rather than read from `NO_PROXY`, a TLS configuration to
+    /// use instead of the default one, and options for the client's connections.
+    pub fn proxy_tls_and_user_agent(
+        proxy: Option<Url>,
+        no_proxy: Option<&str>,
+        tls_config: Option<rustls::ClientConfig>,
+        connection: ConnectionOptions,
+        user_agent: &str,
+    ) -> anyhow::Result<Self> {
         let user_agent = HeaderValue::from_str(user_agent)?;
 
         let mut map = HeaderMap::new();
         map.insert(http::header::USER_AGENT, user_agent.clone());
         let mut client = Self::builder().default_headers(map);
+        if let Some(connect_timeout) = connection.connect_timeout {
+            client = client.connect_timeout(connect_timeout);
+        }
+        if let Some(interval) = connection.keep_alive_interval {
+            client = client
+                .tcp_keepalive(interval)
+                .http2_keep_alive_interval(interval)
+                .http2_keep_alive_while_idle(true);
+        }
         let client_has_proxy;
 
         if let Some(proxy) = proxy.as_ref().and_then(|proxy_url| {
@@ -63,15 +96,19 @@ impl ReqwestClient {
                 })
                 .ok()
         }) {
-            // Respect NO_PROXY env var
-            client = client.proxy(proxy.no_proxy(reqwest::NoProxy::from_env()));
+            let no_proxy = match no_proxy {
+                Some(no_proxy) => reqwest::NoProxy::from_string(no_proxy),
+                // Respect NO_PROXY env var
+                None => reqwest::NoProxy::from_env(),
+            };
+            client = client.proxy(proxy.no_proxy(no_proxy));
             client_has_proxy = true;
         } else {
             client_has_proxy = false;
         };
 
         let client = client
-            .use_preconfigured_tls(http_client_tls::tls_config())
+            .use_preconfigured_tls(tls_config.unwrap_or_else(http_client_tls::tls_config))
             .build()?;
         let mut client: ReqwestClient = client.into();
         client.proxy = client_has_proxy.then_some(proxy).flatten();
@@ -205,6 +205,9 @@ pub enum ContextServerSettingsContent {
 
         #[serde(flatten)]
         command: ContextServerCommand,
+
+        #[serde(flatten)]
+        options: ContextServerOptions,
     },
     Http {
         /// Whether the context server is enabled.
@@ -213,8 +216,40 @@ pub enum ContextServerSettingsContent {
         /// The URL of the remote context server.
         url: String,
         /// Optional headers to send.
+        ///
+        /// Values can reference environment variables with `${env:NAME}` and secrets from
+        /// the system keychain with `${secret:NAME}`, which are resolved whenever the server
+        /// starts. Use `$${` for a literal `${`.
         #[serde(skip_serializing_if = "HashMap::is_empty", default)]
         headers: HashMap<String, String>,
+        /// Timeout for tool calls in milliseconds. Defaults to 60000 (60 seconds) if not specified.
+        #[serde(skip_serializing_if = "Option::is_none", default)]
+        timeout: Option<u64>,
+        /// The protocol used to talk to the remote context server. Defaults to `auto`.
+        #[serde(skip_serializing_if = "Option::is_none", default)]
+        transport: Option<ContextServerHttpTransport>,
+        /// The proxy to connect to the remote context server through, overriding Zed's
+        /// `proxy` setting.
+        #[serde(skip_serializing_if = "Option::is_none", default)]
+        proxy: Option<String>,
+        /// A comma-separated list of hosts to connect to directly rather than through the
+        /// proxy, overriding the `NO_PROXY` environment variable. `*` bypasses the proxy.
+        #[serde(skip_serializing_if = "Option::is_none", default)]
+        no_proxy: Option<String>,
+        /// TLS settings for servers using a private CA or requiring client certificates.
+        #[serde(skip_serializing_if = "Option::is_none", default)]
+        tls: Option<ContextServerTlsSettings>,
+        /// Timeouts and keepalive of the connections to the remote context server.
+        #[serde(skip_serializing_if = "Option::is_none", default)]
+        connection: Option<ContextServerConnectionSettings>,
+        /// A command printing a token to authenticate with, for servers expecting
+        /// short-lived tokens. It is run whenever the server starts, and periodically while
+        /// it runs, and its header takes precedence over `headers`.
+        #[serde(skip_serializing_if = "Option::is_none", default)]
+        header_command: Option<ContextServerHeaderCommand>,
+
+        #[serde(flatten)]
+        options: ContextServerOptions,
     },
     Extension {
         /// Whether the context server is enabled.
@@ -225,6 +260,9 @@ pub enum ContextServerSettingsContent {
         /// Consult the documentation for the context server to see what settings
         /// are supported.
         settings: serde_json::Value,
+
+        #[serde(flatten)]
+        options: ContextServerOptions,
     },
 }
 
@@ -249,6 +287,281 @@ impl ContextServerSettingsContent {
     }
 }
 
+/// Settings that apply to a context server regardless of how it is launched.
+#[skip_serializing_none]
+#[derive(Default, Deserialize, Serialize, Clone, PartialEq, Eq, Debug, JsonSchema, MergeFrom)]
+pub struct ContextServerOptions {
+    /// The minimum level of log messages from the context server to keep.
+    ///
+    /// Default: info
+    pub log_level: Option<ContextServerLogLevel>,
+    /// How often to ping the context server to check that it is still responsive,
+    /// in milliseconds. Servers that miss several pings in a row are restarted.
+    /// Set to 0 to disable health checks.
+    ///
+    /// Default: 30000
+    pub ping_interval: Option<u64>,
+    /// How many times in a row an unresponsive context server is restarted
+    /// before giving up.
+    ///
+    /// Default: 5
+    pub max_restarts: Option<u32>,
+    /// How long a stopped context server gets to exit, in milliseconds, before it is
+    /// sent SIGTERM and then killed.
+    ///
+    /// Default: 2000
+    pub shutdown_timeout: Option<u64>,
+    /// How long the context server gets to respond to the initialize request when it
+    /// starts, in milliseconds. Servers that don't respond in time fail to start.
+    ///
+    /// Default: 10000
+    pub initialize_timeout: Option<u64>,
+    /// How long a context server using the legacy SSE transport keeps trying to
+    /// reconnect after its event stream is lost, in milliseconds. Requests that were
+    /// in flight when the stream was lost fail, later ones wait for the reconnection.
+    ///
+    /// Default: 300000
+    pub reconnect_timeout: Option<u64>,
+    /// Whether to check the arguments of tool calls against the tool's input schema
+    /// before calling it. Turn this off for servers whose schemas are wrong.
+    ///
+    /// Default: true
+    pub validate_tool_arguments: Option<bool>,
+    /// How many tools to load from the context server at most. Servers that
+    /// generate tools, like OpenAPI bridges, can expose more than are useful.
+    ///
+    /// Default: 1000
+    pub max_tools: Option<usize>,
+    /// Which of the context server's tools to expose, by glob patterns matched against
+    /// their names, like `search_*`. Tools that are filtered out aren't listed, and
+    /// calls to them are refused.
+    ///
+    /// Default: all tools are exposed
+    pub tools: Option<ContextServerToolsSettings>,
+    /// The prefix that qualifies the names of the context server's tools for the agent, as
+    /// `<prefix>_<tool>`. Without it, the tools keep their names unless another server has
+    /// a tool with the same name, and are qualified with the context server's ID then.
+    ///
+    /// Default: none
+    pub tool_prefix: Option<String>,
+    /// How large the messages exchanged with the context server may be, in bytes.
+    /// Larger responses are dropped, failing the request they answer, and larger
+    /// requests aren't sent.
+    ///
+    /// Default: 16777216 (16 MiB)
+    pub max_message_bytes: Option<usize>,
+    /// Log the JSON-RPC messages exchanged with the context server to a file in
+    /// Zed's logs directory, for debugging servers that misbehave. Values of fields
+    /// that look like credentials are redacted.
+    ///
+    /// Default: false
+    pub log_traffic: Option<bool>,
+    /// How long to wait for more notifications after the context server announced
+    /// that its tools, prompts or resources changed before fetching them again, in
+    /// milliseconds. Servers often announce a change for every entry they register.
+    ///
+    /// Default: 500
+    pub list_changed_debounce: Option<u64>,
+    /// How many tool calls the context server may handle at once. Further calls
+    /// wait for earlier ones to finish, which counts towards their timeout.
+    ///
+    /// Default: unlimited
+    pub max_concurrent_tool_calls: Option<usize>,
+    /// Retry tool calls that fail because the connection to the context server broke.
+    /// Only calls to tools the server marks as idempotent or read-only are retried,
+    /// since the server may have run the tool before the connection broke.
+    ///
+    /// Default: calls aren't retried
+    pub retry: Option<ContextServerRetrySettings>,
+    /// Limits on the resources the context server's process may use, for servers
+    /// that are started with a command. Limits that aren't supported on the current
+    /// platform are ignored.
+    ///
+    /// Default: no limits
+    pub resource_limits: Option<ContextServerResourceLimits>,
+    /// The directory to start the context server's command in, for servers that are
+    /// started with a command. `${worktree}` stands for the active project folder and
+    /// `${worktree:NAME}` for the project folder named NAME, in this path and in the
+    /// command's arguments. Relative paths are relative to the active project folder.
+    ///
+    /// Default: the active project folder, or the home directory if there is none
+    pub cwd: Option<String>,
+}
+
+/// TLS settings for a remote context server. Paths may start with `~`.
+#[skip_serializing_none]
+#[derive(Default, Deserialize, Serialize, Clone, PartialEq, Eq, Debug, JsonSchema, MergeFrom)]
+pub struct ContextServerTlsSettings {
+    /// A PEM file with CA certificates to trust in addition to the system's.
+    pub ca_cert_path: Option<String>,
+    /// A PEM file with the certificate to authenticate to the server with, for mutual TLS.
+    pub client_cert_path: Option<String>,
+    /// A PEM file with the private key of the client certificate.
+    pub client_key_path: Option<String>,
+    /// Whether to accept any certificate the server presents, including invalid and
+    /// self-signed ones. This makes the connection vulnerable to interception.
+    ///
+    /// Default: false
+    pub accept_invalid_certs: Option<bool>,
+}
+
+/// Timeouts and keepalive of the HTTP connections to a remote context server.
+#[skip_serializing_none]
+#[derive(Default, Deserialize, Serialize, Clone, PartialEq, Eq, Debug, JsonSchema, MergeFrom)]
+pub struct ContextServerConnectionSettings {
+    /// How long to wait for a connection to the server, in milliseconds.
+    ///
+    /// Default: 10000
+    pub connect_timeout: Option<u64>,
+    /// How long to wait for the server to start answering a request, in milliseconds.
+    /// Streamed responses may take longer to finish. Servers that only answer tool
+    /// calls once the tool finished need more than the tool call `timeout`.
+    ///
+    /// Default: 300000, or the tool call `timeout` if it's longer
+    pub request_timeout: Option<u64>,
+    /// How often to check that idle connections to the server are still alive, in
+    /// milliseconds, so that long-lived streams the network dropped are noticed. Sends
+    /// TCP keepalive probes, and pings to servers using HTTP/2.
+    ///
+    /// Default: the operating system's TCP keepalive settings
+    pub keep_alive_interval: Option<u64>,
+}
+
+/// Which of a context server's tools are exposed. Tools matching both `allow` and `deny`
+/// are hidden.
+#[skip_serializing_none]
+#[derive(Default, Deserialize, Serialize, Clone, PartialEq, Eq, Debug, JsonSchema, MergeFrom)]
+pub struct ContextServerToolsSettings {
+    /// Patterns of the tools to expose. Tools that match none of them are hidden.
+    ///
+    /// Default: all tools
+    pub allow: Option<Vec<String>>,
+    /// Patterns of the tools to hide.
+    ///
+    /// Default: none
+    pub deny: Option<Vec<String>>,
+}
+
+/// Limits on the resources a context server's process may use.
+#[skip_serializing_none]
+#[derive(Default, Deserialize, Serialize, Clone, PartialEq, Eq, Debug, JsonSchema, MergeFrom)]
+pub struct ContextServerResourceLimits {
+    /// The niceness to run the context server at, from -20 (the highest priority) to
+    /// 19 (the lowest). On Windows, this selects the closest priority class. Raising
+    /// the priority above the default usually needs elevated privileges.
+    ///
+    /// Default: 0
+    pub nice: Option<i32>,
+    /// How many CPUs the context server may run on. Not supported on macOS.
+    ///
+    /// Default: all of them
+    pub cpu_count: Option<usize>,
+    /// How much memory the context server may allocate, in MiB. Servers that need
+    /// more usually crash. Not supported on macOS.
+    ///
+    /// Default: unlimited
+    pub max_memory: Option<u64>,
+}
+
+/// How tool calls to a context server are retried.
+#[skip_serializing_none]
+#[derive(Default, Deserialize, Serialize, Clone, PartialEq, Eq, Debug, JsonSchema, MergeFrom)]
+pub struct ContextServerRetrySettings {
+    /// How many times a call is made at most, including the first attempt.
+    ///
+    /// Default: 3
+    pub max_attempts: Option<u32>,
+    /// How long to wait before retrying a call the first time, in milliseconds. The
+    /// delay doubles with every retry.
+    ///
+    /// Default: 500
+    pub initial_backoff: Option<u64>,
+    /// Which failures are retried. Errors reported by the server or the tool never are.
+    ///
+    /// Default: ["transport"]
+    pub retry_on: Option<Vec<ContextServerRetryableError>>,
+}
+
+/// A kind of tool call failure that can be retried.
+#[derive(Clone, Copy, Debug, Eq, PartialEq, Serialize, Deserialize, JsonSchema, MergeFrom)]
+#[serde(rename_all = "snake_case")]
+pub enum ContextServerRetryableError {
+    /// The connection to the server broke before it answered.
+    Transport,
+    /// The server didn't answer before the call timed out.
+    Timeout,
+}
+
+/// A command printing the token a remote context server is authenticated to with, like
+/// `gcloud auth print-identity-token`. Its path may start with `~`.
+#[skip_serializing_none]
+#[derive(Default, Deserialize, Serialize, Clone, PartialEq, Eq, Debug, JsonSchema, MergeFrom)]
+pub struct ContextServerHeaderCommand {
+    pub command: String,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub args: Vec<String>,
+    /// The header to send the token in.
+    ///
+    /// Default: Authorization
+    pub header: Option<String>,
+    /// The scheme to put in front of the token. Set to an empty string to send the token
+    /// by itself.
+    ///
+    /// Default: Bearer
+    pub scheme: Option<String>,
+}
+
+/// The protocol used to talk to a remote context server.
+#[derive(
+    Clone, Copy, Debug, Default, Eq, PartialEq, Serialize, Deserialize, JsonSchema, MergeFrom,
+)]
+#[serde(rename_all = "snake_case")]
+pub enum ContextServerHttpTransport {
+    /// Use streamable HTTP, falling back to HTTP with SSE if the server doesn't support it.
+    /// `ws://` and `wss://` URLs always use a WebSocket, and `tcp://` and `tls://` URLs a
+    /// TCP connection.
+    #[default]
+    Auto,
+    /// The streamable HTTP transport.
+    StreamableHttp,
+    /// The legacy HTTP with server-sent events transport.
+    Sse,
+    /// JSON-RPC messages over a WebSocket, for `ws://` and `wss://` URLs.
+    #[serde(rename = "websocket")]
+    WebSocket,
+    /// Newline-delimited JSON-RPC messages over a TCP connection, for `tcp://host:port` URLs,
+    /// or `tls://host:port` URLs to encrypt the connection with TLS.
+    Tcp,
+}
+
+/// The severity of a log message sent by a context server, as defined by MCP.
+#[derive(
+    Clone,
+    Copy,
+    Debug,
+    Eq,
+    PartialEq,
+    Ord,
+    PartialOrd,
+    Serialize,
+    Deserialize,
+    JsonSchema,
+    MergeFrom,
+    strum::VariantArray,
+    strum::VariantNames,
+)]
+#[serde(rename_all = "snake_case")]
+pub enum ContextServerLogLevel {
+    Debug,
+    Info,
+    Notice,
+    Warning,
+    Error,
+    Critical,
+    Alert,
+    Emergency,
+}
+
 #[skip_serializing_none]
 #[derive(Deserialize, Serialize, Clone, PartialEq, Eq, JsonSchema, MergeFrom)]
 pub struct ContextServerCommand {
@@ -577,6 +577,7 @@ impl VsCodeSettings {
                                 env: cmd.env,
                                 timeout: None,
                             })?,
+                        options: Default::default(),
                     },
                 ))
             })
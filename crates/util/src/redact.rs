@@ -1,5 +1,7 @@
-/// Whether a given environment variable name should have its value redacted
-pub fn should_redact(env_var_name: &str) -> bool {
+/// Whether the value of a given environment variable or HTTP header should be redacted.
+///
+/// Names are compared case-insensitively, so that both `GITHUB_TOKEN` and `X-Api-Key` match.
+pub fn should_redact(name: &str) -> bool {
     const REDACTED_SUFFIXES: &[&str] = &[
         "KEY",
         "TOKEN",
@@ -8,8 +10,36 @@ pub fn should_redact(env_var_name: &str) -> bool {
         "PASS",
         "CREDENTIALS",
         "LICENSE",
+        "AUTHORIZATION",
+        "COOKIE",
     ];
-    REDACTED_SUFFIXES
-        .iter()
-        .any(|suffix| env_var_name.ends_with(suffix))
+    REDACTED_SUFFIXES.iter().any(|suffix| {
+        name.len() >= suffix.len()
+            && name.as_bytes()[name.len() - suffix.len()..].eq_ignore_ascii_case(suffix.as_bytes())
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_should_redact() {
+        for name in [
+            "GITHUB_TOKEN",
+            "OPENAI_API_KEY",
+            "db_password",
+            "Authorization",
+            "proxy-authorization",
+            "X-Api-Key",
+            "X-Auth-Token",
+            "Cookie",
+            "Set-Cookie",
+        ] {
+            assert!(should_redact(name), "{name} should be redacted");
+        }
+        for name in ["PATH", "HOME", "Content-Type", "Accept", "KE"] {
+            assert!(!should_redact(name), "{name} should not be redacted");
+        }
+    }
 }